@@ -1,30 +1,77 @@
-use std::{collections::HashMap, error::Error, fmt, rc::Rc};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
 
 use clap::Parser;
 
 use self::{
-    high_level::{Agent, ConflictTreeNode, Constraint, Path},
-    low_level::{AStarLowLevelSolver, Grid},
-    search::a_star,
+    cancellation::CancellationToken,
+    conflict_tree::{ConflictTree, NodeRecording, NodeStatus},
+    high_level::{ConflictTreeNode, Constraint},
+    low_level::Grid,
+    mdd::{MDDCache, MDDCacheStatistics},
+    partial::{Cutoff, PartialSolution},
+    progress::{ProgressObserver, ProgressTracker},
+    search::{a_star_monitored, SearchError, SearchProgress},
+    validation::InstanceProblem,
 };
 
+pub use self::high_level::{Agent, Path};
+pub use self::low_level::AStarLowLevelSolver;
+
+pub mod batch;
+pub mod cancellation;
+pub mod conflict_tree;
+pub mod generators;
 mod high_level;
+pub mod icts;
+pub mod independence;
 pub(crate) mod io;
+pub mod lacam;
 mod low_level;
+pub mod m_star;
 mod mdd;
+pub mod operator_decomposition;
 mod optimisations;
+mod parallel;
+pub mod partial;
+pub mod pibt;
+pub mod portfolio;
+pub mod progress;
 pub mod search;
+pub mod service;
+pub mod terrain;
+pub mod validation;
 mod vertex_cover;
 
 #[derive(Debug, Clone)]
 pub enum CBSError {
     AlreadySolved,
+    InvalidInstance(Vec<InstanceProblem>),
+    NodeLimitReached(usize),
 }
 
 impl fmt::Display for CBSError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             CBSError::AlreadySolved => write!(f, "CBS instance already solved"),
+            CBSError::InvalidInstance(problems) => {
+                write!(f, "invalid CBS instance: ")?;
+                for (i, problem) in problems.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", problem)?;
+                }
+                Ok(())
+            }
+            CBSError::NodeLimitReached(limit) => {
+                write!(f, "expanded {} high-level nodes without a solution", limit)
+            }
         }
     }
 }
@@ -47,6 +94,7 @@ impl From<String> for HighLevelHeuristic {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct DiagonalSubsolverConfig {
     slackness: i32,
     promotion_enabled: bool,
@@ -61,6 +109,7 @@ impl DiagonalSubsolverConfig {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct CBSOptimisationConfig {
     priotising_conflicts: bool,
     bypassing_conflicts: bool,
@@ -87,9 +136,49 @@ impl CBSOptimisationConfig {
     }
 }
 
+#[derive(Clone)]
 pub struct CBSInstance {
     map: Grid,
     agents: Vec<Agent>,
+    scenario: HashMap<String, io::ScenarioEntry>,
+}
+
+impl CBSInstance {
+    pub fn new(map: Grid, agents: Vec<Agent>) -> Self {
+        CBSInstance {
+            map,
+            agents,
+            scenario: HashMap::new(),
+        }
+    }
+
+    pub fn agents(&self) -> &[Agent] {
+        &self.agents
+    }
+
+    /// Fails if entering some cell of the map takes more than one timestep,
+    /// for `solver`s that move every agent a cell per timestep.
+    pub(crate) fn require_unit_costs(&self, solver: &str) -> Result<(), SearchError> {
+        if self.map.has_terrain_costs() {
+            return Err(SearchError::InvalidArguments(format!(
+                "{} cannot plan for terrain costs",
+                solver
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fails if the map has timed obstacles, for `solver`s that only plan on
+    /// the static map.
+    pub(crate) fn require_no_timed_obstacles(&self, solver: &str) -> Result<(), SearchError> {
+        if !self.map.timed_obstacles.is_empty() {
+            return Err(SearchError::InvalidArguments(format!(
+                "{} cannot plan around timed obstacles",
+                solver
+            )));
+        }
+        Ok(())
+    }
 }
 
 pub struct CBS {
@@ -97,7 +186,16 @@ pub struct CBS {
     solved: bool,
     pub high_level_generated: usize,
     pub low_level_generated: usize,
+    pub high_level_pruned: usize,
     optimisation_config: CBSOptimisationConfig,
+    low_level_solver: Arc<AStarLowLevelSolver>,
+    mdds: Arc<MDDCache>,
+    cancellation: Option<CancellationToken>,
+    conflict_tree: Option<Arc<Mutex<ConflictTree>>>,
+    progress: Option<(ProgressObserver, Duration)>,
+    node_limit: Option<usize>,
+    threads: usize,
+    partial_solution: Option<PartialSolution>,
 }
 
 impl CBS {
@@ -106,22 +204,98 @@ impl CBS {
             instance,
             high_level_generated: 0,
             low_level_generated: 0,
+            high_level_pruned: 0,
             solved: false,
             optimisation_config: optimisation_config
                 .unwrap_or(CBSOptimisationConfig::new(false, false, None, false, None)),
+            low_level_solver: Arc::new(AStarLowLevelSolver::new()),
+            mdds: Arc::new(MDDCache::new()),
+            cancellation: None,
+            conflict_tree: None,
+            progress: None,
+            node_limit: None,
+            threads: 1,
+            partial_solution: None,
         }
     }
 
+    /// Makes [`Self::solve`] fail with [`SearchError::Cancelled`] once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Makes [`Self::solve`] fail with [`CBSError::NodeLimitReached`] after
+    /// expanding `node_limit` high-level nodes.
+    pub fn with_node_limit(mut self, node_limit: usize) -> Self {
+        self.node_limit = Some(node_limit);
+        self
+    }
+
+    /// Generates the children of each conflict tree node, plans the root's
+    /// paths if the CAT is disabled, and computes the DG heuristic on up to
+    /// `threads` threads.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The best node found if [`Self::solve`] was cancelled, timed out or reached the node limit.
+    pub fn partial_solution(&self) -> Option<&PartialSolution> {
+        self.partial_solution.as_ref()
+    }
+
+    /// Reports the progress of [`Self::solve`] to `observer` every `interval`,
+    /// whenever the lower bound or the best conflict count improves, and when the search ends.
+    pub fn with_progress(mut self, observer: ProgressObserver, interval: Duration) -> Self {
+        self.progress = Some((observer, interval));
+        self
+    }
+
+    /// Records the conflict tree explored by [`Self::solve`], see [`Self::conflict_tree`].
+    pub fn record_conflict_tree(mut self) -> Self {
+        self.conflict_tree = Some(Arc::new(Mutex::new(ConflictTree::default())));
+        self
+    }
+
+    /// The recorded conflict tree, if recording was enabled. Also available after a failed search.
+    pub fn conflict_tree(&self) -> Option<ConflictTree> {
+        self.conflict_tree
+            .as_ref()
+            .map(|tree| tree.lock().expect("lock is not poisoned").clone())
+    }
+
+    /// How often the MDDs needed by [`Self::solve`] were already known.
+    pub fn mdd_statistics(&self) -> MDDCacheStatistics {
+        self.mdds.statistics()
+    }
+
+    /// Reuses the distance tables of `low_level_solver`, which must
+    /// only have been used on the map of this instance.
+    pub(crate) fn with_low_level_solver(
+        mut self,
+        low_level_solver: Arc<AStarLowLevelSolver>,
+    ) -> Self {
+        self.low_level_solver = low_level_solver;
+        self
+    }
+
     pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
         if self.solved {
             return Err(Box::new(CBSError::AlreadySolved));
         }
-        let low_level_solver = AStarLowLevelSolver::new();
-        let root = ConflictTreeNode::new(
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        if self.optimisation_config.diagonal_subsolver.is_some() {
+            self.instance
+                .require_unit_costs("CBS with the diagonal sub-solver")?;
+        }
+        let mut root = ConflictTreeNode::new_without_init(
             self.instance.agents.iter().collect(),
             Vec::<Box<Constraint>>::new(),
-            HashMap::<&Agent, Vec<(i32, i32)>>::new(),
             &self.instance.map,
+            HashMap::<&Agent, Vec<(i32, i32)>>::new(),
             if self.optimisation_config.priotising_conflicts {
                 Some(optimisations::conflict_prioritisation::pick_conflict)
             } else {
@@ -133,7 +307,7 @@ impl CBS {
                 None
             },
             if let Some(subsolver_config) = &self.optimisation_config.diagonal_subsolver {
-                Some(Rc::new(
+                Some(Arc::new(
                     optimisations::diagonal_subsolver::DiagonalSubsolver::new(
                         subsolver_config.slackness,
                         subsolver_config.promotion_enabled,
@@ -143,22 +317,91 @@ impl CBS {
                 None
             },
             self.optimisation_config.conflict_avoidance_table,
-            &low_level_solver,
+            self.low_level_solver.as_ref(),
             match self.optimisation_config.heuristic {
                 HighLevelHeuristic::ZeroHeuristic => {
-                    Rc::new(high_level::heuristic::ZeroHeuristic::new())
+                    Arc::new(high_level::heuristic::ZeroHeuristic::new())
                 }
                 HighLevelHeuristic::DGHeuristic => {
-                    Rc::new(high_level::heuristic::DGHeuristic::new())
+                    Arc::new(high_level::heuristic::DGHeuristic::new())
+                }
+            },
+        );
+        root.threads = self.threads;
+        root.mdds = Arc::clone(&self.mdds);
+        root.init();
+        if let Some(tree) = &self.conflict_tree {
+            root.recording = Some(NodeRecording::root(Arc::clone(tree), &root));
+        }
+        if !root.is_feasible() {
+            self.solved = true;
+            return Err(Box::new(SearchError::NotFound));
+        }
+        let statistics = Arc::clone(&root.statistics);
+        let cancellation = self.cancellation.clone();
+        let node_limit = self.node_limit;
+        let mut tracker = self
+            .progress
+            .take()
+            .map(|(observer, interval)| ProgressTracker::new(observer, interval));
+        let num_agents = self.instance.agents.len() as f64;
+        let mut best_node = None::<ConflictTreeNode>;
+        let mut lower_bound = 0.0;
+        let mut nodes_generated = 0;
+        let solution = a_star_monitored(
+            root,
+            &mut |node: &ConflictTreeNode, search: &SearchProgress| {
+                // g counts the start of every path, unlike the sum of costs
+                let f = node.known_f() - num_agents;
+                if let Some(tracker) = &mut tracker {
+                    tracker.expanding(f, node.conflicts.len(), search);
+                }
+                lower_bound = f64::max(lower_bound, f);
+                nodes_generated = search.nodes_generated;
+                let is_better = best_node.as_ref().is_none_or(|best| {
+                    (node.conflicts.len(), f) < (best.conflicts.len(), best.known_f() - num_agents)
+                });
+                if is_better {
+                    best_node = Some(node.clone());
                 }
+                // a solution found at the limit is still returned
+                let over_limit = node_limit.is_some_and(|limit| search.nodes_expanded >= limit)
+                    && !node.conflicts.is_empty();
+                over_limit
+                    || cancellation
+                        .as_ref()
+                        .is_some_and(|cancellation| cancellation.is_cancelled())
             },
         );
-        let solution = a_star(root);
+        if let Some(tracker) = &mut tracker {
+            tracker.finished();
+        }
         self.solved = true;
+        self.high_level_pruned += statistics.infeasible_pruned.load(Ordering::Relaxed);
+        if let (Err(SearchError::Cancelled), Some(best_node)) = (&solution, &best_node) {
+            self.high_level_generated += nodes_generated;
+            let cutoff = match &self.cancellation {
+                Some(cancellation) if cancellation.timed_out() => Cutoff::TimedOut,
+                Some(cancellation) if cancellation.is_cancelled() => Cutoff::Cancelled,
+                _ => Cutoff::NodeLimitReached,
+            };
+            self.partial_solution = Some(PartialSolution::new(
+                cutoff,
+                &self.instance.agents,
+                best_node,
+                lower_bound,
+            ));
+            if let (Cutoff::NodeLimitReached, Some(limit)) = (cutoff, node_limit) {
+                return Err(Box::new(CBSError::NodeLimitReached(limit)));
+            }
+        }
         match solution {
             Ok(solution) => {
                 self.high_level_generated += solution.nodes_generated as usize;
                 let last_node = solution.path.last().unwrap();
+                if let Some(recording) = &last_node.recording {
+                    recording.set_status(NodeStatus::Solution);
+                }
                 let mut paths = HashMap::<&Agent, Path>::new();
                 for agent in self.instance.agents.iter() {
                     paths.insert(agent, last_node.paths[agent].clone());
@@ -0,0 +1,281 @@
+//! Solves a stream of JSON-lines instances in one process, reusing parsed
+//! maps and distance tables across instances on the same map.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    io::read_map,
+    low_level::{AStarLowLevelSolver, Grid},
+    partial::{Cutoff, UnresolvedConflict},
+    progress::ProgressObserver,
+    terrain::TerrainTable,
+    CBSInstance, CBSOptimisationConfig, CBS,
+};
+
+/// A line of batch input, e.g.
+/// `{"id": "a", "map": "maps/empty-16-16.map", "agents": [{"start": [0, 0], "goal": [3, 4]}]}`.
+#[derive(Debug, Deserialize)]
+struct BatchInstance {
+    /// Echoed in the result. Defaults to the line number.
+    id: Option<String>,
+    /// Path of a Moving AI map.
+    map: String,
+    agents: Vec<BatchAgent>,
+    /// Stop after expanding this many high-level nodes.
+    node_limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct BatchAgent {
+    /// Defaults to the index of the agent.
+    name: Option<String>,
+    start: [i32; 2],
+    goal: [i32; 2],
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchResult {
+    pub(crate) id: String,
+    #[serde(flatten)]
+    pub(crate) outcome: BatchOutcome,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum BatchOutcome {
+    Solved {
+        cost: usize,
+        makespan: usize,
+        high_level_generated: usize,
+        high_level_pruned: usize,
+        runtime_ms: u128,
+        paths: Vec<BatchPath>,
+    },
+    Cancelled {
+        #[serde(flatten)]
+        partial: Option<BatchPartial>,
+    },
+    TimedOut {
+        #[serde(flatten)]
+        partial: Option<BatchPartial>,
+    },
+    NodeLimitReached {
+        #[serde(flatten)]
+        partial: Option<BatchPartial>,
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// The best node of a search that was cut off.
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchPartial {
+    cost: usize,
+    makespan: usize,
+    lower_bound: f64,
+    high_level_generated: usize,
+    runtime_ms: u128,
+    conflicts: Vec<UnresolvedConflict>,
+    paths: Vec<BatchPath>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchPath {
+    agent: String,
+    path: Vec<[i32; 2]>,
+}
+
+pub struct BatchSolver {
+    terrain: TerrainTable,
+    optimisation_config: Option<CBSOptimisationConfig>,
+    distance_cache: Option<PathBuf>,
+    maps: HashMap<String, (Grid, Arc<AStarLowLevelSolver>)>,
+}
+
+impl BatchSolver {
+    pub fn new(terrain: TerrainTable, optimisation_config: Option<CBSOptimisationConfig>) -> Self {
+        Self {
+            terrain,
+            optimisation_config,
+            distance_cache: None,
+            maps: HashMap::new(),
+        }
+    }
+
+    /// Keeps the distance tables in `directory`, so that they also outlive the process.
+    pub fn with_distance_cache(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.distance_cache = Some(directory.into());
+        self
+    }
+
+    /// Solves every instance read from `reader`, writing one result line per
+    /// instance to `writer`. Blank lines are skipped; a malformed line yields an error result.
+    pub fn run(&mut self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let result = self.solve_line(&line, i + 1);
+            writeln!(
+                writer,
+                "{}",
+                serde_json::to_string(&result).expect("results are serialisable")
+            )?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn solve_line(&mut self, line: &str, line_no: usize) -> BatchResult {
+        let instance = match serde_json::from_str::<BatchInstance>(line) {
+            Ok(instance) => instance,
+            Err(e) => {
+                return BatchResult {
+                    id: line_no.to_string(),
+                    outcome: BatchOutcome::Error {
+                        error: format!("invalid instance: {}", e),
+                    },
+                }
+            }
+        };
+        let id = instance.id.clone().unwrap_or(line_no.to_string());
+        let outcome = self
+            .solve(instance)
+            .unwrap_or_else(|error| BatchOutcome::Error { error });
+        BatchResult { id, outcome }
+    }
+
+    fn solve(&mut self, instance: BatchInstance) -> Result<BatchOutcome, String> {
+        let (map, low_level_solver) = self.load_map(&instance.map)?;
+        Ok(solve_instance(
+            map,
+            low_level_solver,
+            instance.agents,
+            self.optimisation_config.clone(),
+            None,
+            instance.node_limit,
+            None,
+        ))
+    }
+
+    fn load_map(&mut self, map_file: &str) -> Result<(Grid, Arc<AStarLowLevelSolver>), String> {
+        if !self.maps.contains_key(map_file) {
+            let file = File::open(map_file).map_err(|e| format!("{}: {}", map_file, e))?;
+            let map = read_map(file, &self.terrain).map_err(|e| format!("{}: {}", map_file, e))?;
+            let mut low_level_solver = AStarLowLevelSolver::new();
+            if let Some(directory) = &self.distance_cache {
+                low_level_solver = low_level_solver.with_distance_cache(directory);
+            }
+            self.maps
+                .insert(map_file.to_string(), (map, Arc::new(low_level_solver)));
+        }
+        let (map, low_level_solver) = &self.maps[map_file];
+        Ok((map.clone(), Arc::clone(low_level_solver)))
+    }
+}
+
+/// Solves `agents` on `map`, reusing the distance tables of `low_level_solver`.
+pub(crate) fn solve_instance(
+    map: Grid,
+    low_level_solver: Arc<AStarLowLevelSolver>,
+    agents: Vec<BatchAgent>,
+    optimisation_config: Option<CBSOptimisationConfig>,
+    cancellation: Option<CancellationToken>,
+    node_limit: Option<usize>,
+    progress: Option<(ProgressObserver, Duration)>,
+) -> BatchOutcome {
+    let t0 = Instant::now();
+    let agents = agents
+        .into_iter()
+        .enumerate()
+        .map(|(i, agent)| Agent {
+            id: agent.name.unwrap_or(i.to_string()),
+            start: (agent.start[0], agent.start[1]),
+            goal: (agent.goal[0], agent.goal[1]),
+        })
+        .collect::<Vec<_>>();
+    let mut cbs = CBS::new(CBSInstance::new(map, agents.clone()), optimisation_config)
+        .with_low_level_solver(low_level_solver);
+    if let Some(cancellation) = &cancellation {
+        cbs = cbs.with_cancellation(cancellation.clone());
+    }
+    if let Some(node_limit) = node_limit {
+        cbs = cbs.with_node_limit(node_limit);
+    }
+    if let Some((observer, interval)) = progress {
+        cbs = cbs.with_progress(observer, interval);
+    }
+    let solution = cbs.solve();
+    let solution = match solution {
+        Ok(solution) => solution,
+        Err(e) => {
+            let Some(partial) = cbs.partial_solution() else {
+                return BatchOutcome::Error {
+                    error: e.to_string(),
+                };
+            };
+            let paths = batch_paths(partial.paths.iter().map(|(agent, path)| (agent, path)));
+            let (cost, makespan) = cost_and_makespan(&paths);
+            let partial_result = Some(BatchPartial {
+                cost,
+                makespan,
+                lower_bound: partial.lower_bound,
+                high_level_generated: cbs.high_level_generated,
+                runtime_ms: t0.elapsed().as_millis(),
+                conflicts: partial.conflicts.clone(),
+                paths,
+            });
+            return match partial.cutoff {
+                Cutoff::Cancelled => BatchOutcome::Cancelled {
+                    partial: partial_result,
+                },
+                Cutoff::TimedOut => BatchOutcome::TimedOut {
+                    partial: partial_result,
+                },
+                Cutoff::NodeLimitReached => BatchOutcome::NodeLimitReached {
+                    partial: partial_result,
+                },
+            };
+        }
+    };
+    let paths = batch_paths(agents.iter().map(|agent| (agent, &solution[agent])));
+    let (cost, makespan) = cost_and_makespan(&paths);
+    BatchOutcome::Solved {
+        cost,
+        makespan,
+        high_level_generated: cbs.high_level_generated,
+        high_level_pruned: cbs.high_level_pruned,
+        runtime_ms: t0.elapsed().as_millis(),
+        paths,
+    }
+}
+
+fn batch_paths<'a>(paths: impl Iterator<Item = (&'a Agent, &'a Path)>) -> Vec<BatchPath> {
+    paths
+        .map(|(agent, path)| BatchPath {
+            agent: agent.id.clone(),
+            path: path.iter().map(|(x, y)| [*x, *y]).collect(),
+        })
+        .collect()
+}
+
+fn cost_and_makespan(paths: &[BatchPath]) -> (usize, usize) {
+    let arrivals = paths.iter().map(|p| p.path.len().saturating_sub(1));
+    (arrivals.clone().sum(), arrivals.max().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,63 @@
+use super::*;
+use serde_json::Value;
+
+fn run(solver: &mut BatchSolver, input: &str) -> Vec<Value> {
+    let mut output = Vec::<u8>::new();
+    solver.run(input.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_batch() {
+    let mut solver = BatchSolver::new(TerrainTable::default(), None);
+    let input = r#"
+{"id": "first", "map": "tests/testdata/maps/empty-16-16.map", "agents": [{"name": "a", "start": [0, 0], "goal": [3, 0]}, {"start": [3, 0], "goal": [3, 2]}]}
+not json
+{"map": "tests/testdata/maps/empty-16-16.map", "agents": [{"start": [0, 0], "goal": [3, 0]}, {"start": [1, 1], "goal": [3, 0]}]}
+{"map": "tests/testdata/maps/empty-16-16.map", "agents": [{"start": [5, 5], "goal": [3, 0]}]}
+{"map": "no/such.map", "agents": []}
+"#;
+    let results = run(&mut solver, input);
+    assert_eq!(results.len(), 5);
+
+    assert_eq!(results[0]["id"], "first");
+    assert_eq!(results[0]["status"], "solved");
+    assert_eq!(results[0]["paths"][0]["agent"], "a");
+    assert_eq!(results[0]["paths"][1]["agent"], "1");
+    assert_eq!(results[0]["paths"][0]["path"][0], serde_json::json!([0, 0]));
+    assert_eq!(results[0]["cost"], 5);
+
+    assert_eq!(results[1]["id"], "3");
+    assert_eq!(results[1]["status"], "error");
+
+    assert_eq!(results[2]["status"], "error");
+    assert!(results[2]["error"]
+        .as_str()
+        .unwrap()
+        .contains("share the goal (3, 0)"));
+
+    assert_eq!(results[3]["status"], "solved");
+    assert_eq!(results[3]["cost"], 7);
+
+    assert_eq!(results[4]["status"], "error");
+    assert_eq!(solver.maps.len(), 1);
+}
+
+#[test]
+fn test_node_limit() {
+    let mut solver = BatchSolver::new(TerrainTable::default(), None);
+    let input = r#"{"map": "tests/testdata/maps/empty-16-16.map", "agents": [{"start": [0, 1], "goal": [2, 1]}, {"start": [1, 0], "goal": [1, 2]}], "node_limit": 0}"#;
+    let results = run(&mut solver, input);
+    assert_eq!(results[0]["status"], "node_limit_reached");
+    assert_eq!(results[0]["cost"], 4);
+    assert_eq!(results[0]["lower_bound"], 4.0);
+    let conflicts = results[0]["conflicts"].as_array().unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0]["time"], 1);
+    assert_eq!(conflicts[0]["locations"], serde_json::json!([[1, 1]]));
+    assert_eq!(results[0]["paths"].as_array().unwrap().len(), 2);
+}
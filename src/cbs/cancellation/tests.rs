@@ -0,0 +1,18 @@
+use super::*;
+
+#[test]
+fn test_cancel_is_shared_by_clones() {
+    let token = CancellationToken::new();
+    let clone = token.clone();
+    assert!(!clone.is_cancelled());
+    token.cancel();
+    assert!(clone.is_cancelled());
+    assert!(!clone.timed_out());
+}
+
+#[test]
+fn test_timeout() {
+    assert!(CancellationToken::with_timeout(Duration::ZERO).timed_out());
+    let token = CancellationToken::with_timeout(Duration::from_secs(3600));
+    assert!(!token.is_cancelled());
+}
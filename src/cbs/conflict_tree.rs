@@ -0,0 +1,288 @@
+//! Records the explored conflict tree for debugging, and exports it as
+//! Graphviz DOT or JSON.
+
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+use super::{
+    high_level::{Conflict, ConflictTreeNode, Constraint},
+    optimisations::conflict_prioritisation::{cardinality, ConflictCardinality},
+    search::AStarNode,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    /// Generated, but never expanded.
+    Generated,
+    /// Split on its conflict into children.
+    Expanded,
+    /// Replaced by a child with fewer conflicts instead of being split.
+    Bypassed,
+    /// A child that was dropped because its parent was bypassed.
+    Discarded,
+    /// Some agent has no path under the node's constraints.
+    Infeasible,
+    /// The conflict-free node the search ended with.
+    Solution,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordedConstraint {
+    pub agent: String,
+    pub time: i32,
+    pub location: (i32, i32),
+    /// Set for edge constraints.
+    pub prev_location: Option<(i32, i32)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordedConflict {
+    pub agents: (String, String),
+    pub time: i32,
+    /// One location for vertex conflicts, the two swapped locations for edge conflicts.
+    pub locations: Vec<(i32, i32)>,
+    pub cardinality: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordedNode {
+    pub id: usize,
+    pub parent: Option<usize>,
+    /// The constraint added to the parent's constraints. `None` for the
+    /// root and for nodes replacing a bypassed parent.
+    pub constraint: Option<RecordedConstraint>,
+    pub g: f64,
+    /// Only known once the search has ranked the node.
+    pub h: Option<f64>,
+    pub num_conflicts: usize,
+    pub status: NodeStatus,
+    /// The conflict the node was split or bypassed on.
+    pub conflict: Option<RecordedConflict>,
+    /// Position in the order of expansion, starting at 0.
+    pub expansion_order: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConflictTree {
+    pub nodes: Vec<RecordedNode>,
+    #[serde(skip)]
+    num_expanded: usize,
+}
+
+impl ConflictTree {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("conflict trees are serialisable")
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph ConflictTree {\n    node [shape=box, fontname=monospace];\n");
+        for node in self.nodes.iter() {
+            let mut label = format!("#{} g={}", node.id, node.g);
+            if let Some(h) = node.h {
+                write!(label, " h={}", h).unwrap();
+            }
+            write!(label, "\\nconflicts={}", node.num_conflicts).unwrap();
+            if let Some(order) = node.expansion_order {
+                write!(label, "\\nexpansion {}", order).unwrap();
+            }
+            if let Some(conflict) = &node.conflict {
+                write!(
+                    label,
+                    "\\n{} {}-{} t={} {:?}",
+                    conflict.cardinality,
+                    escape_label(&conflict.agents.0),
+                    escape_label(&conflict.agents.1),
+                    conflict.time,
+                    conflict.locations
+                )
+                .unwrap();
+            }
+            let style = match node.status {
+                NodeStatus::Generated => "color=gray",
+                NodeStatus::Expanded => "color=black",
+                NodeStatus::Bypassed => "color=orange",
+                NodeStatus::Discarded => "color=gray, style=dashed",
+                NodeStatus::Infeasible => "color=red",
+                NodeStatus::Solution => "color=green, penwidth=2",
+            };
+            writeln!(dot, "    n{} [label=\"{}\", {}];", node.id, label, style).unwrap();
+            if let Some(parent) = node.parent {
+                let edge_label = match &node.constraint {
+                    Some(c) => match c.prev_location {
+                        Some(prev) => format!(
+                            "{} !{:?}->{:?} t={}",
+                            escape_label(&c.agent),
+                            prev,
+                            c.location,
+                            c.time
+                        ),
+                        None => {
+                            format!("{} !{:?} t={}", escape_label(&c.agent), c.location, c.time)
+                        }
+                    },
+                    None => "bypass".to_string(),
+                };
+                writeln!(
+                    dot,
+                    "    n{} -> n{} [label=\"{}\"];",
+                    parent, node.id, edge_label
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn add(
+        &mut self,
+        parent: Option<usize>,
+        constraint: Option<&Constraint>,
+        node: &ConflictTreeNode,
+    ) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(RecordedNode {
+            id,
+            parent,
+            constraint: constraint.map(|c| RecordedConstraint {
+                agent: c.agent().id.clone(),
+                time: c.time(),
+                location: c.location(),
+                prev_location: c.prev_location(),
+            }),
+            g: node.g(),
+            h: None,
+            num_conflicts: node.conflicts.len(),
+            status: if node.is_feasible() {
+                NodeStatus::Generated
+            } else {
+                NodeStatus::Infeasible
+            },
+            conflict: None,
+            expansion_order: None,
+        });
+        id
+    }
+}
+
+/// Where a conflict tree node is recorded. Shared by all nodes of a search.
+#[derive(Debug, Clone)]
+pub(crate) struct NodeRecording {
+    tree: Arc<Mutex<ConflictTree>>,
+    id: usize,
+}
+
+impl NodeRecording {
+    pub(crate) fn root(tree: Arc<Mutex<ConflictTree>>, root: &ConflictTreeNode) -> Self {
+        let id = tree
+            .lock()
+            .expect("lock is not poisoned")
+            .add(None, None, root);
+        Self { tree, id }
+    }
+
+    pub(crate) fn child(&self, constraint: Option<&Constraint>, child: &ConflictTreeNode) -> Self {
+        let id =
+            self.tree
+                .lock()
+                .expect("lock is not poisoned")
+                .add(Some(self.id), constraint, child);
+        Self {
+            tree: Arc::clone(&self.tree),
+            id,
+        }
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    pub(crate) fn set_h(&self, h: f64) {
+        self.tree.lock().expect("lock is not poisoned").nodes[self.id].h = Some(h);
+    }
+
+    pub(crate) fn set_status(&self, status: NodeStatus) {
+        self.tree.lock().expect("lock is not poisoned").nodes[self.id].status = status;
+    }
+
+    /// Records that `parent` was expanded on `conflict` into `children`, which
+    /// contain a clone of the parent if the conflict was bypassed.
+    pub(crate) fn expanded<'a>(
+        &self,
+        parent: &ConflictTreeNode<'a>,
+        conflict: &Conflict<'a>,
+        generated: &[usize],
+        children: Option<&mut Vec<Box<ConflictTreeNode<'a>>>>,
+    ) {
+        let (agent1, agent2, time, locations) = match conflict {
+            Conflict::Vertex(c) => (c.agent1, c.agent2, c.time, vec![c.location]),
+            Conflict::Edge(c) => (c.agent1, c.agent2, c.time, vec![c.location1, c.location2]),
+        };
+        let cardinality = match cardinality(parent.scenario, &parent.paths, &parent.mdds, conflict)
+        {
+            ConflictCardinality::Cardinal => "cardinal",
+            ConflictCardinality::SemiCardinal => "semi-cardinal",
+            ConflictCardinality::NonCardinal => "non-cardinal",
+        };
+        {
+            let mut tree = self.tree.lock().expect("lock is not poisoned");
+            let order = tree.num_expanded;
+            tree.num_expanded += 1;
+            let node = &mut tree.nodes[self.id];
+            node.status = NodeStatus::Expanded;
+            node.expansion_order = Some(order);
+            node.conflict = Some(RecordedConflict {
+                agents: (agent1.id.clone(), agent2.id.clone()),
+                time,
+                locations,
+                cardinality: cardinality.to_string(),
+            });
+        }
+        let Some(children) = children else {
+            return;
+        };
+        let mut kept = Vec::<usize>::new();
+        for child in children.iter_mut() {
+            let Some(recording) = &child.recording else {
+                continue;
+            };
+            if recording.id == self.id {
+                self.set_status(NodeStatus::Bypassed);
+                child.recording = Some(self.child(None, child));
+            } else {
+                kept.push(recording.id);
+            }
+        }
+        for id in generated.iter().filter(|id| !kept.contains(id)) {
+            let mut tree = self.tree.lock().expect("lock is not poisoned");
+            if tree.nodes[*id].status == NodeStatus::Generated {
+                tree.nodes[*id].status = NodeStatus::Discarded;
+            }
+        }
+    }
+}
+
+/// Escapes text for a quoted DOT label, where agent ids may hold quotes,
+/// backslashes or line breaks.
+fn escape_label(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests;
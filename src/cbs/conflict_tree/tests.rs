@@ -0,0 +1,131 @@
+use rstest::rstest;
+
+use super::*;
+use crate::cbs::{
+    high_level::Agent, low_level::Grid, CBSInstance, CBSOptimisationConfig, HighLevelHeuristic, CBS,
+};
+
+fn crossing_instance() -> CBSInstance {
+    crossing_instance_of("a", "b")
+}
+
+fn crossing_instance_of(a: &str, b: &str) -> CBSInstance {
+    CBSInstance::new(
+        Grid::new(3, 3, Grid::to_conditional_obstacles(Vec::new()), (2, 2)),
+        vec![
+            Agent {
+                id: a.to_string(),
+                start: (0, 1),
+                goal: (2, 1),
+            },
+            Agent {
+                id: b.to_string(),
+                start: (1, 0),
+                goal: (1, 2),
+            },
+        ],
+    )
+}
+
+fn solve_recorded(optimisation_config: Option<CBSOptimisationConfig>) -> ConflictTree {
+    let mut cbs = CBS::new(crossing_instance(), optimisation_config).record_conflict_tree();
+    cbs.solve().expect("crossing agents are solvable");
+    cbs.conflict_tree().expect("recording was enabled")
+}
+
+#[rstest]
+#[case::plain(None)]
+#[case::prioritising(Some(CBSOptimisationConfig::new(true, false, None, false, None)))]
+#[case::bypassing(Some(CBSOptimisationConfig::new(
+    true,
+    true,
+    None,
+    false,
+    Some(HighLevelHeuristic::DGHeuristic)
+)))]
+fn test_records_path_to_solution(#[case] optimisation_config: Option<CBSOptimisationConfig>) {
+    let tree = solve_recorded(optimisation_config);
+    let root = &tree.nodes[0];
+    assert_eq!(root.parent, None);
+    assert_eq!(root.num_conflicts, 1);
+    assert_eq!(root.expansion_order, Some(0));
+    let conflict = root
+        .conflict
+        .as_ref()
+        .expect("root was split on a conflict");
+    let mut agents = [conflict.agents.0.as_str(), conflict.agents.1.as_str()];
+    agents.sort();
+    assert_eq!(agents, ["a", "b"]);
+    assert_eq!(conflict.locations, vec![(1, 1)]);
+
+    let solutions = tree
+        .nodes
+        .iter()
+        .filter(|node| node.status == NodeStatus::Solution)
+        .collect::<Vec<_>>();
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(solutions[0].num_conflicts, 0);
+    assert_eq!(solutions[0].g, 7.0);
+    let mut ancestor = solutions[0].parent;
+    while let Some(id) = ancestor {
+        let node = &tree.nodes[id];
+        assert!(matches!(
+            node.status,
+            NodeStatus::Expanded | NodeStatus::Bypassed
+        ));
+        ancestor = node.parent;
+    }
+}
+
+#[test]
+fn test_child_records_constraint() {
+    let tree = solve_recorded(None);
+    let children = tree
+        .nodes
+        .iter()
+        .filter(|node| node.parent == Some(0))
+        .collect::<Vec<_>>();
+    assert_eq!(children.len(), 2);
+    for child in children {
+        let constraint = child
+            .constraint
+            .as_ref()
+            .expect("children add a constraint");
+        assert_eq!(constraint.time, 1);
+        assert_eq!(constraint.location, (1, 1));
+        assert_eq!(constraint.prev_location, None);
+    }
+}
+
+#[test]
+fn test_exports() {
+    let tree = solve_recorded(None);
+    let json = serde_json::from_str::<serde_json::Value>(&tree.to_json()).unwrap();
+    let nodes = json["nodes"].as_array().unwrap();
+    assert_eq!(nodes.len(), tree.nodes.len());
+    assert_eq!(nodes[0]["status"], "expanded");
+    assert_eq!(nodes[0]["conflict"]["cardinality"], "cardinal");
+
+    let dot = tree.to_dot();
+    assert!(dot.starts_with("digraph ConflictTree {"));
+    assert!(dot.contains("n0 -> n1 [label=\""));
+    assert!(dot.contains(" !(1, 1) t=1\"]"));
+    assert!(dot.contains("penwidth=2"));
+}
+
+#[test]
+fn test_dot_escapes_agent_ids() {
+    let mut cbs = CBS::new(crossing_instance_of("a\"1", "b\\2\n"), None).record_conflict_tree();
+    cbs.solve().expect("crossing agents are solvable");
+    let dot = cbs.conflict_tree().expect("recording was enabled").to_dot();
+    assert!(dot.contains(r#"b\\2\n-a\"1 t=1"#), "{}", dot);
+    assert!(dot.contains(r#"[label="a\"1 !(1, 1) t=1"]"#), "{}", dot);
+    // a raw line break in an id would split a statement over two lines
+    assert!(
+        dot.lines()
+            .all(|line| line.ends_with('{') || line.ends_with("];") || line == "}"),
+        "{}",
+        dot
+    );
+    assert_eq!(escape_label("say \"hi\"\\\r\n"), r#"say \"hi\"\\\n"#);
+}
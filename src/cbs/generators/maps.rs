@@ -0,0 +1,345 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    str::FromStr,
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::cbs::low_level::{Grid, LocationTime};
+
+/// Free space left on either side of the shelves of a warehouse, as in the Moving AI warehouse maps.
+const DEFAULT_WAREHOUSE_MARGIN: i32 = 25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapKind {
+    /// Obstacles placed uniformly at random on `density` of the cells,
+    /// keeping all free cells connected.
+    Random {
+        width: i32,
+        height: i32,
+        density: f64,
+    },
+    Warehouse(WarehouseLayout),
+    /// A perfect maze of `corridor_width` wide corridors separated by
+    /// walls one cell thick. Cells that do not fit a whole corridor
+    /// on the right and bottom are walls.
+    Maze {
+        width: i32,
+        height: i32,
+        corridor_width: i32,
+    },
+}
+
+/// Rows and columns of shelves separated by aisles, enclosed by a wall.
+/// Parsed from the Moving AI naming scheme, e.g. `10-20-10-2-1` for
+/// shelves 10 cells long, 20 rows, 10 columns, shelves 2 cells wide and aisles 1 cell wide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarehouseLayout {
+    pub shelf_length: i32,
+    pub rows: i32,
+    pub columns: i32,
+    pub shelf_width: i32,
+    pub aisle_width: i32,
+    /// Free columns between the wall and the shelves on the left and right.
+    pub margin: i32,
+}
+
+impl FromStr for WarehouseLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s
+            .split('-')
+            .map(|n| {
+                n.trim()
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or(format!("not a positive number: '{}'", n))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let [shelf_length, rows, columns, shelf_width, aisle_width] = numbers[..] else {
+            return Err(format!(
+                "expected <shelf length>-<rows>-<columns>-<shelf width>-<aisle width>, got '{}'",
+                s
+            ));
+        };
+        Ok(WarehouseLayout {
+            shelf_length,
+            rows,
+            columns,
+            shelf_width,
+            aisle_width,
+            margin: DEFAULT_WAREHOUSE_MARGIN,
+        })
+    }
+}
+
+impl WarehouseLayout {
+    /// Returns the width and height of the map including its walls.
+    pub fn dimensions(&self) -> (i32, i32) {
+        (
+            2 + 2 * self.margin
+                + self.columns * self.shelf_length
+                + (self.columns - 1) * self.aisle_width,
+            2 + self.rows * self.shelf_width + (self.rows + 1) * self.aisle_width,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapConfig {
+    pub kind: MapKind,
+    pub seed: u64,
+}
+
+/// Generates a map with static obstacles only. Warehouses do not depend on the seed.
+pub fn generate_map(config: &MapConfig) -> Result<Grid, String> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let (width, height, obstacles) = match &config.kind {
+        MapKind::Random {
+            width,
+            height,
+            density,
+        } => (
+            *width,
+            *height,
+            random_obstacles(*width, *height, *density, &mut rng)?,
+        ),
+        MapKind::Warehouse(layout) => {
+            let (width, height) = layout.dimensions();
+            (width, height, warehouse_obstacles(layout)?)
+        }
+        MapKind::Maze {
+            width,
+            height,
+            corridor_width,
+        } => (
+            *width,
+            *height,
+            maze_obstacles(*width, *height, *corridor_width, &mut rng)?,
+        ),
+    };
+    Ok(Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(
+            obstacles
+                .into_iter()
+                .map(|location| LocationTime::new(location, -1))
+                .collect(),
+        ),
+        (0, 0),
+    ))
+}
+
+fn check_dimensions(width: i32, height: i32) -> Result<(), String> {
+    if width <= 0 || height <= 0 {
+        return Err(format!("invalid map dimensions {}x{}", width, height));
+    }
+    Ok(())
+}
+
+/// Visits cells in random order and turns each into an obstacle if the
+/// free cells stay connected. Most cells pass a cheap local test; the
+/// rest are checked with a search over the whole map.
+fn random_obstacles(
+    width: i32,
+    height: i32,
+    density: f64,
+    rng: &mut StdRng,
+) -> Result<HashSet<(i32, i32)>, String> {
+    check_dimensions(width, height)?;
+    if !(0.0..1.0).contains(&density) {
+        return Err(format!("density must be in [0, 1), got {}", density));
+    }
+    let target = (density * (width * height) as f64).round() as usize;
+    let mut cells = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .collect::<Vec<_>>();
+    cells.shuffle(rng);
+    let mut obstacles = HashSet::<(i32, i32)>::new();
+    for cell in cells {
+        if obstacles.len() >= target {
+            break;
+        }
+        let is_free = |(x, y): (i32, i32)| {
+            x >= 0 && x < width && y >= 0 && y < height && !obstacles.contains(&(x, y))
+        };
+        if keeps_neighbours_connected(cell, is_free)
+            || keeps_map_connected(cell, width * height - obstacles.len() as i32, is_free)
+        {
+            obstacles.insert(cell);
+        }
+    }
+    if obstacles.len() < target {
+        return Err(format!(
+            "could only place {} of {} obstacles without disconnecting the map",
+            obstacles.len(),
+            target
+        ));
+    }
+    Ok(obstacles)
+}
+
+/// Returns whether the free 4-neighbours of `cell` are connected by free
+/// cells of the ring around it. Consecutive ring cells are 4-adjacent,
+/// so its connected parts are runs of free cells.
+fn keeps_neighbours_connected(cell: (i32, i32), is_free: impl Fn((i32, i32)) -> bool) -> bool {
+    const RING: [(i32, i32); 8] = [
+        (-1, -1),
+        (0, -1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+        (0, 1),
+        (-1, 1),
+        (-1, 0),
+    ];
+    let free = RING.map(|(dx, dy)| is_free((cell.0 + dx, cell.1 + dy)));
+    let Some(gap) = free.iter().position(|free| !free) else {
+        return true;
+    };
+    // walk the ring starting after a blocked cell, so that no run wraps around
+    let mut runs_with_neighbours = 0;
+    let mut run_has_neighbour = false;
+    for i in 1..=RING.len() {
+        let index = (gap + i) % RING.len();
+        let (dx, dy) = RING[index];
+        if free[index] {
+            run_has_neighbour |= dx == 0 || dy == 0;
+        } else {
+            runs_with_neighbours += run_has_neighbour as usize;
+            run_has_neighbour = false;
+        }
+    }
+    runs_with_neighbours <= 1
+}
+
+/// Returns whether all `num_free` free cells except `cell` are 4-connected.
+fn keeps_map_connected(
+    cell: (i32, i32),
+    num_free: i32,
+    is_free: impl Fn((i32, i32)) -> bool,
+) -> bool {
+    let Some(source) = [(0, 1), (1, 0), (0, -1), (-1, 0)]
+        .iter()
+        .map(|(dx, dy)| (cell.0 + dx, cell.1 + dy))
+        .find(|neighbour| is_free(*neighbour))
+    else {
+        return true;
+    };
+    let mut reached = HashSet::from([cell, source]);
+    let mut queue = VecDeque::from([source]);
+    while let Some((x, y)) = queue.pop_front() {
+        for (dx, dy) in [(0, 1), (1, 0), (0, -1), (-1, 0)] {
+            let neighbour = (x + dx, y + dy);
+            if is_free(neighbour) && reached.insert(neighbour) {
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    reached.len() as i32 == num_free
+}
+
+fn warehouse_obstacles(layout: &WarehouseLayout) -> Result<HashSet<(i32, i32)>, String> {
+    let WarehouseLayout {
+        shelf_length,
+        rows,
+        columns,
+        shelf_width,
+        aisle_width,
+        margin,
+    } = *layout;
+    if [shelf_length, rows, columns, shelf_width, aisle_width]
+        .iter()
+        .any(|n| *n <= 0)
+        || margin < 0
+    {
+        return Err(format!("invalid warehouse layout {:?}", layout));
+    }
+    let (width, height) = layout.dimensions();
+    let mut obstacles = walls(width, height);
+    for row in 0..rows {
+        let top = 1 + aisle_width + row * (shelf_width + aisle_width);
+        for column in 0..columns {
+            let left = 1 + margin + column * (shelf_length + aisle_width);
+            for y in top..top + shelf_width {
+                for x in left..left + shelf_length {
+                    obstacles.insert((x, y));
+                }
+            }
+        }
+    }
+    Ok(obstacles)
+}
+
+/// Carves a maze with a randomised depth-first search over corridor cells.
+fn maze_obstacles(
+    width: i32,
+    height: i32,
+    corridor_width: i32,
+    rng: &mut StdRng,
+) -> Result<HashSet<(i32, i32)>, String> {
+    check_dimensions(width, height)?;
+    let step = corridor_width + 1;
+    let (columns, rows) = ((width - 1) / step, (height - 1) / step);
+    if corridor_width <= 0 || columns == 0 || rows == 0 {
+        return Err(format!(
+            "a {}x{} map does not fit corridors {} cells wide",
+            width, height, corridor_width
+        ));
+    }
+    let mut free = HashSet::<(i32, i32)>::new();
+    let mut carve = |from: (i32, i32), to: (i32, i32)| {
+        for y in from.1..to.1 {
+            for x in from.0..to.0 {
+                free.insert((x, y));
+            }
+        }
+    };
+    let corner = |(column, row): (i32, i32)| (1 + column * step, 1 + row * step);
+    let mut visited = HashSet::from([(0, 0)]);
+    let mut stack = vec![(0, 0)];
+    carve(corner((0, 0)), (1 + corridor_width, 1 + corridor_width));
+    while let Some(&current) = stack.last() {
+        let mut unvisited = [(0, 1), (1, 0), (0, -1), (-1, 0)]
+            .iter()
+            .map(|(dx, dy)| (current.0 + dx, current.1 + dy))
+            .filter(|(column, row)| {
+                (0..columns).contains(column)
+                    && (0..rows).contains(row)
+                    && !visited.contains(&(*column, *row))
+            })
+            .collect::<Vec<_>>();
+        unvisited.shuffle(rng);
+        let Some(&next) = unvisited.first() else {
+            stack.pop();
+            continue;
+        };
+        // carve the next cell along with the wall between the two cells
+        let (from, to) = (corner(current), corner(next));
+        let top_left = (from.0.min(to.0), from.1.min(to.1));
+        let bottom_right = (
+            from.0.max(to.0) + corridor_width,
+            from.1.max(to.1) + corridor_width,
+        );
+        carve(top_left, bottom_right);
+        visited.insert(next);
+        stack.push(next);
+    }
+    Ok((0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|cell| !free.contains(cell))
+        .collect())
+}
+
+fn walls(width: i32, height: i32) -> HashSet<(i32, i32)> {
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|(x, y)| *x == 0 || *y == 0 || *x == width - 1 || *y == height - 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
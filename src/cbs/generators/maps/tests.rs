@@ -0,0 +1,110 @@
+use super::*;
+use crate::cbs::{io::parse_map, terrain::TerrainTable};
+use proptest::prelude::*;
+use rstest::rstest;
+
+fn static_obstacles(map: &Grid) -> HashSet<(i32, i32)> {
+    (0..map.width)
+        .flat_map(|x| (0..map.height).map(move |y| (x, y)))
+        .filter(|cell| map.static_obstacles.contains(*cell))
+        .collect()
+}
+
+fn num_components(map: &Grid) -> usize {
+    map.connected_components()
+        .values()
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+#[test]
+fn test_warehouse_matches_benchmark_map() {
+    let expected = parse_map(
+        &std::fs::read_to_string("tests/testdata/maps/warehouse-10-20-10-2-1.map").unwrap(),
+        &TerrainTable::default(),
+    )
+    .unwrap();
+    let map = generate_map(&MapConfig {
+        kind: MapKind::Warehouse("10-20-10-2-1".parse().unwrap()),
+        seed: 0,
+    })
+    .unwrap();
+    assert_eq!((map.width, map.height), (expected.width, expected.height));
+    assert_eq!(static_obstacles(&map), static_obstacles(&expected));
+}
+
+#[rstest]
+#[case::too_few("10-20-10-2")]
+#[case::not_a_number("10-20-x-2-1")]
+#[case::zero("10-20-10-0-1")]
+fn test_parse_invalid_warehouse_layout(#[case] layout: &str) {
+    assert!(layout.parse::<WarehouseLayout>().is_err());
+}
+
+#[rstest]
+#[case::corridors_1(21, 21, 1)]
+#[case::corridors_3(32, 24, 3)]
+fn test_maze_is_perfect(#[case] width: i32, #[case] height: i32, #[case] corridor_width: i32) {
+    let map = generate_map(&MapConfig {
+        kind: MapKind::Maze {
+            width,
+            height,
+            corridor_width,
+        },
+        seed: 7,
+    })
+    .unwrap();
+    let step = corridor_width + 1;
+    let num_cells = ((width - 1) / step * ((height - 1) / step)) as usize;
+    let corridor_area = (corridor_width * corridor_width) as usize;
+    // a spanning tree of corridor cells opens one wall segment per edge
+    let expected_free = num_cells * corridor_area + (num_cells - 1) * corridor_width as usize;
+    assert_eq!(map.connected_components().len(), expected_free);
+    assert_eq!(num_components(&map), 1);
+}
+
+#[test]
+fn test_maze_too_small() {
+    let config = MapConfig {
+        kind: MapKind::Maze {
+            width: 4,
+            height: 10,
+            corridor_width: 3,
+        },
+        seed: 0,
+    };
+    assert!(generate_map(&config).is_err());
+}
+
+#[test]
+fn test_random_too_dense() {
+    let config = MapConfig {
+        kind: MapKind::Random {
+            width: 8,
+            height: 8,
+            density: 0.95,
+        },
+        seed: 0,
+    };
+    assert!(generate_map(&config).is_err());
+}
+
+proptest! {
+    #[test]
+    fn test_random_map_density_and_connectivity(
+        width in 3..20i32,
+        height in 3..20i32,
+        density in 0.0..0.3f64,
+        seed in any::<u64>(),
+    ) {
+        let config = MapConfig {
+            kind: MapKind::Random { width, height, density },
+            seed,
+        };
+        let map = generate_map(&config).unwrap();
+        let expected = (density * (width * height) as f64).round() as usize;
+        prop_assert_eq!(static_obstacles(&map).len(), expected);
+        prop_assert!(num_components(&map) <= 1);
+        prop_assert_eq!(static_obstacles(&generate_map(&config).unwrap()), static_obstacles(&map));
+    }
+}
@@ -0,0 +1,282 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::cbs::{io::ScenarioEntry, low_level::Grid};
+
+/// Width of a bucket of optimal path lengths, as in the official Moving AI scenarios.
+const BUCKET_LENGTH: f64 = 4.0;
+/// Attempts at placing a single random agent before giving up.
+const MAX_PLACEMENT_ATTEMPTS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScenarioKind {
+    /// `count` uniformly random start/goal pairs.
+    Random { count: usize },
+    /// `per_bucket` start/goal pairs for every bucket of optimal path lengths.
+    Even { per_bucket: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct ScenarioConfig {
+    pub kind: ScenarioKind,
+    pub seed: u64,
+    /// Cells agents may start at. Any free cell if `None`.
+    pub starts: Option<Vec<(i32, i32)>>,
+    /// Cells agents may have as goals. Any free cell if `None`.
+    pub goals: Option<Vec<(i32, i32)>>,
+}
+
+/// Generates Moving AI scenario rows for `map`. Every start and goal is used
+/// by at most one row, and each goal lies in the same connected component as its start.
+/// Optimal lengths are 8-connected distances without corner cutting, like the official files.
+pub fn generate_scenario(
+    map: &Grid,
+    map_name: &str,
+    config: &ScenarioConfig,
+) -> Result<Vec<ScenarioEntry>, String> {
+    let components = map.connected_components();
+    let starts = allowed_cells(&components, &config.starts);
+    let goals = allowed_cells(&components, &config.goals);
+    if starts.is_empty() || goals.is_empty() {
+        return Err("no free cells to place starts or goals on".to_string());
+    }
+    let mut generator = ScenarioGenerator {
+        map,
+        map_name,
+        components,
+        goals,
+        used_starts: HashSet::new(),
+        used_goals: HashSet::new(),
+        rng: StdRng::seed_from_u64(config.seed),
+    };
+    match config.kind {
+        ScenarioKind::Random { count } => generator.random(&starts, count),
+        ScenarioKind::Even { per_bucket } => Ok(generator.even(starts, per_bucket)),
+    }
+}
+
+fn allowed_cells(
+    components: &HashMap<(i32, i32), usize>,
+    cells: &Option<Vec<(i32, i32)>>,
+) -> Vec<(i32, i32)> {
+    let mut allowed = match cells {
+        Some(cells) => cells
+            .iter()
+            .filter(|cell| components.contains_key(cell))
+            .copied()
+            .collect(),
+        None => components.keys().copied().collect::<Vec<_>>(),
+    };
+    // the order must not depend on hashing for generation to be reproducible
+    allowed.sort_by_key(|(x, y)| (*y, *x));
+    allowed.dedup();
+    allowed
+}
+
+struct ScenarioGenerator<'a> {
+    map: &'a Grid,
+    map_name: &'a str,
+    components: HashMap<(i32, i32), usize>,
+    goals: Vec<(i32, i32)>,
+    used_starts: HashSet<(i32, i32)>,
+    used_goals: HashSet<(i32, i32)>,
+    rng: StdRng,
+}
+
+impl ScenarioGenerator<'_> {
+    fn random(
+        &mut self,
+        starts: &[(i32, i32)],
+        count: usize,
+    ) -> Result<Vec<ScenarioEntry>, String> {
+        let mut entries = Vec::<ScenarioEntry>::new();
+        while entries.len() < count {
+            let entry = (0..MAX_PLACEMENT_ATTEMPTS).find_map(|_| {
+                let start = *starts.choose(&mut self.rng)?;
+                if self.used_starts.contains(&start) {
+                    return None;
+                }
+                let goal = *self.goal_candidates(start).choose(&mut self.rng)?;
+                let distances = octile_distances(self.map, start);
+                Some(self.entry(start, goal, distances[&goal]))
+            });
+            match entry {
+                Some(entry) => entries.push(entry),
+                None => {
+                    return Err(format!(
+                        "could only place {} of {} agents",
+                        entries.len(),
+                        count
+                    ))
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Fills buckets of optimal lengths evenly, adding one row per start cell to
+    /// the least populated bucket the start can reach. Buckets that cannot be
+    /// filled are dropped.
+    fn even(&mut self, mut starts: Vec<(i32, i32)>, per_bucket: usize) -> Vec<ScenarioEntry> {
+        starts.shuffle(&mut self.rng);
+        let mut buckets = BTreeMap::<usize, Vec<ScenarioEntry>>::new();
+        let mut starts_since_new_bucket = 0;
+        for start in starts {
+            let all_full = buckets.values().all(|entries| entries.len() >= per_bucket);
+            if all_full && starts_since_new_bucket >= 10 * per_bucket {
+                break;
+            }
+            starts_since_new_bucket += 1;
+            let distances = octile_distances(self.map, start);
+            let mut candidates = BTreeMap::<usize, Vec<(i32, i32)>>::new();
+            for goal in self.goal_candidates(start) {
+                candidates
+                    .entry(bucket(distances[&goal]))
+                    .or_default()
+                    .push(goal);
+            }
+            for bucket in candidates.keys() {
+                if !buckets.contains_key(bucket) {
+                    buckets.insert(*bucket, Vec::new());
+                    starts_since_new_bucket = 0;
+                }
+            }
+            let Some((bucket, goals)) = candidates
+                .into_iter()
+                .filter(|(bucket, _)| buckets[bucket].len() < per_bucket)
+                .min_by_key(|(bucket, _)| (buckets[bucket].len(), *bucket))
+            else {
+                continue;
+            };
+            let goal = *goals.choose(&mut self.rng).expect("buckets are non-empty");
+            let entry = self.entry(start, goal, distances[&goal]);
+            buckets
+                .get_mut(&bucket)
+                .expect("bucket was added")
+                .push(entry);
+        }
+        let mut entries = Vec::<ScenarioEntry>::new();
+        for (bucket, bucket_entries) in buckets {
+            if bucket_entries.len() < per_bucket {
+                log::warn!(
+                    "dropping bucket {}: only {} of {} start/goal pairs found",
+                    bucket,
+                    bucket_entries.len(),
+                    per_bucket
+                );
+                continue;
+            }
+            entries.extend(bucket_entries);
+        }
+        entries.shuffle(&mut self.rng);
+        entries
+    }
+
+    fn goal_candidates(&self, start: (i32, i32)) -> Vec<(i32, i32)> {
+        self.goals
+            .iter()
+            .filter(|goal| {
+                **goal != start
+                    && !self.used_goals.contains(goal)
+                    && self.components.get(goal) == self.components.get(&start)
+            })
+            .copied()
+            .collect()
+    }
+
+    fn entry(&mut self, start: (i32, i32), goal: (i32, i32), length: f64) -> ScenarioEntry {
+        self.used_starts.insert(start);
+        self.used_goals.insert(goal);
+        ScenarioEntry {
+            bucket: bucket(length),
+            map_name: self.map_name.to_string(),
+            map_width: self.map.width,
+            map_height: self.map.height,
+            start,
+            goal,
+            optimal_length: length,
+        }
+    }
+}
+
+fn bucket(length: f64) -> usize {
+    (length / BUCKET_LENGTH) as usize
+}
+
+#[derive(PartialEq)]
+struct DijkstraEntry {
+    distance: f64,
+    location: (i32, i32),
+}
+
+impl Eq for DijkstraEntry {}
+
+impl PartialOrd for DijkstraEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DijkstraEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, so that the max-heap pops the closest location first
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then_with(|| other.location.cmp(&self.location))
+    }
+}
+
+/// Computes 8-connected distances from `source` to every reachable cell.
+/// Diagonal moves cost sqrt(2) and may not cut corners of obstacles.
+pub(crate) fn octile_distances(map: &Grid, source: (i32, i32)) -> HashMap<(i32, i32), f64> {
+    let mut distances = HashMap::<(i32, i32), f64>::new();
+    let mut frontier = BinaryHeap::<DijkstraEntry>::new();
+    distances.insert(source, 0.0);
+    frontier.push(DijkstraEntry {
+        distance: 0.0,
+        location: source,
+    });
+    while let Some(DijkstraEntry { distance, location }) = frontier.pop() {
+        if distance > distances[&location] {
+            continue;
+        }
+        let (x, y) = location;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let neighbour = (x + dx, y + dy);
+                if (dx, dy) == (0, 0) || !map.is_valid_location(&neighbour, &location) {
+                    continue;
+                }
+                let is_diagonal = dx != 0 && dy != 0;
+                if is_diagonal
+                    && !(map.is_valid_location(&(x + dx, y), &location)
+                        && map.is_valid_location(&(x, y + dy), &location))
+                {
+                    continue;
+                }
+                let neighbour_distance = distance
+                    + if is_diagonal {
+                        std::f64::consts::SQRT_2
+                    } else {
+                        1.0
+                    };
+                if neighbour_distance < *distances.get(&neighbour).unwrap_or(&f64::INFINITY) {
+                    distances.insert(neighbour, neighbour_distance);
+                    frontier.push(DijkstraEntry {
+                        distance: neighbour_distance,
+                        location: neighbour,
+                    });
+                }
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests;
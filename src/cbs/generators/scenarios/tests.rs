@@ -0,0 +1,113 @@
+use super::*;
+use crate::cbs::{io::parse_map, low_level::LocationTime, terrain::TerrainTable};
+use rstest::rstest;
+
+fn two_rooms() -> Grid {
+    parse_map(
+        "type octile\nheight 4\nwidth 9\nmap\n....@....\n....@....\n....@....\nG...@...G\n",
+        &TerrainTable::default(),
+    )
+    .unwrap()
+}
+
+#[rstest]
+#[case::straight((0, 0), (3, 0), 3.0)]
+#[case::diagonal((0, 0), (3, 3), 3.0 * std::f64::consts::SQRT_2)]
+#[case::mixed((0, 0), (3, 1), 2.0 + std::f64::consts::SQRT_2)]
+fn test_octile_distances_empty(
+    #[case] source: (i32, i32),
+    #[case] target: (i32, i32),
+    #[case] expected: f64,
+) {
+    let map = Grid::new(4, 4, HashMap::new(), (0, 0));
+    let distances = octile_distances(&map, source);
+    assert!((distances[&target] - expected).abs() < 1e-9);
+}
+
+#[test]
+fn test_octile_distances_no_corner_cutting() {
+    let map = Grid::new(
+        2,
+        2,
+        Grid::to_conditional_obstacles(vec![LocationTime::new((1, 0), -1)]),
+        (0, 0),
+    );
+    let distances = octile_distances(&map, (0, 0));
+    assert_eq!(distances[&(1, 1)], 2.0);
+    assert!(!distances.contains_key(&(1, 0)));
+}
+
+#[test]
+fn test_random_scenario() {
+    let map = two_rooms();
+    let config = ScenarioConfig {
+        kind: ScenarioKind::Random { count: 10 },
+        seed: 7,
+        starts: None,
+        goals: None,
+    };
+    let entries = generate_scenario(&map, "two_rooms.map", &config).unwrap();
+    assert_eq!(entries.len(), 10);
+    let components = map.connected_components();
+    for entry in entries.iter() {
+        assert_eq!(components[&entry.start], components[&entry.goal]);
+        assert_eq!(entry.bucket, bucket(entry.optimal_length));
+        assert_eq!((entry.map_width, entry.map_height), (9, 4));
+    }
+    let starts = entries.iter().map(|e| e.start).collect::<HashSet<_>>();
+    let goals = entries.iter().map(|e| e.goal).collect::<HashSet<_>>();
+    assert_eq!(starts.len(), entries.len());
+    assert_eq!(goals.len(), entries.len());
+    assert_eq!(
+        entries,
+        generate_scenario(&map, "two_rooms.map", &config).unwrap(),
+        "generation should be reproducible"
+    );
+}
+
+#[test]
+fn test_random_scenario_too_many_agents() {
+    let config = ScenarioConfig {
+        kind: ScenarioKind::Random { count: 40 },
+        seed: 0,
+        starts: None,
+        goals: None,
+    };
+    assert!(generate_scenario(&two_rooms(), "two_rooms.map", &config).is_err());
+}
+
+#[test]
+fn test_even_scenario() {
+    let map = Grid::new(16, 16, HashMap::new(), (0, 0));
+    let config = ScenarioConfig {
+        kind: ScenarioKind::Even { per_bucket: 3 },
+        seed: 1,
+        starts: None,
+        goals: None,
+    };
+    let entries = generate_scenario(&map, "empty-16-16.map", &config).unwrap();
+    let mut bucket_sizes = BTreeMap::<usize, usize>::new();
+    for entry in entries.iter() {
+        assert_eq!(entry.bucket, bucket(entry.optimal_length));
+        *bucket_sizes.entry(entry.bucket).or_default() += 1;
+    }
+    assert!(bucket_sizes.len() > 1);
+    assert!(bucket_sizes.values().all(|size| *size == 3));
+}
+
+#[test]
+fn test_restricted_endpoints() {
+    let map = two_rooms();
+    let stations = map.terrain.as_ref().unwrap().locations_of(&['G']);
+    assert_eq!(stations, vec![(0, 3), (8, 3)]);
+    let config = ScenarioConfig {
+        kind: ScenarioKind::Random { count: 2 },
+        seed: 3,
+        starts: None,
+        goals: Some(stations.clone()),
+    };
+    let entries = generate_scenario(&map, "two_rooms.map", &config).unwrap();
+    let mut goals = entries.iter().map(|e| e.goal).collect::<Vec<_>>();
+    goals.sort();
+    assert_eq!(goals, stations);
+}
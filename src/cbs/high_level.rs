@@ -1,16 +1,27 @@
 use log::debug;
 
-use self::heuristic::Heuristic;
+use self::{
+    heuristic::Heuristic,
+    occupancy::OccupancyIndex,
+    persistent::{ConstraintList, PathMap},
+};
 
 use super::{
-    low_level::{AStarLowLevelSolver, Grid, LocationTime, LowLevelSolver},
+    conflict_tree::NodeRecording,
+    low_level::{
+        obstacles::ConstraintTable, AStarLowLevelSolver, Grid, LocationTime, LowLevelSolver,
+    },
+    mdd::MDDCache,
+    parallel::parallel_map,
     search::AStarNode,
 };
 use std::{
-    cell::Cell,
     collections::{HashMap, HashSet},
     hash::Hash,
-    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
 };
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
@@ -36,6 +47,22 @@ pub enum Conflict<'a> {
     Edge(EdgeConflict<'a>),
 }
 
+impl<'a> Conflict<'a> {
+    pub fn agents(&self) -> (&'a Agent, &'a Agent) {
+        match self {
+            Conflict::Vertex(conflict) => (conflict.agent1, conflict.agent2),
+            Conflict::Edge(conflict) => (conflict.agent1, conflict.agent2),
+        }
+    }
+
+    pub fn time(&self) -> i32 {
+        match self {
+            Conflict::Vertex(conflict) => conflict.time,
+            Conflict::Edge(conflict) => conflict.time,
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 pub struct Constraint<'a> {
     agent: &'a Agent,
@@ -56,6 +83,10 @@ impl<'a> Constraint<'a> {
     pub fn location(&self) -> (i32, i32) {
         self.location
     }
+
+    pub fn prev_location(&self) -> Option<(i32, i32)> {
+        self.prev_location
+    }
 }
 
 pub type Path = Vec<(i32, i32)>;
@@ -67,22 +98,38 @@ pub struct Agent {
     pub goal: (i32, i32),
 }
 
+/// Counters shared by all nodes of a conflict tree.
+#[derive(Debug, Default)]
+pub struct HighLevelStatistics {
+    pub(crate) infeasible_pruned: AtomicUsize,
+}
+
 #[derive(Clone)]
 pub struct ConflictTreeNode<'a> {
-    pub(crate) constraints: Vec<Box<Constraint<'a>>>,
-    pub(crate) agents: Vec<&'a Agent>,
-    pub(crate) paths: HashMap<&'a Agent, Path>,
+    pub(crate) constraints: ConstraintList<'a>,
+    pub(crate) agents: Arc<[&'a Agent]>,
+    pub(crate) paths: PathMap<'a>,
     pub(crate) conflicts: Vec<Box<Conflict<'a>>>,
+    /// The paths the conflicts were found for, shared with the parent until
+    /// a path of the node changes.
+    occupancy: Arc<OccupancyIndex<'a>>,
     pub(crate) scenario: &'a Grid,
     conflict_picker:
-        fn(&Grid, &HashMap<&Agent, Path>, &Vec<Box<Conflict<'a>>>) -> Option<Box<Conflict<'a>>>,
+        fn(&Grid, &PathMap, &MDDCache, &Vec<Box<Conflict<'a>>>) -> Option<Box<Conflict<'a>>>,
     post_expanded_callback: fn(&Self, &Conflict<'a>, Vec<Box<Self>>) -> Option<Vec<Box<Self>>>,
-    node_preprocessor: Rc<dyn CTNodePreprocessor>,
+    node_preprocessor: Arc<dyn CTNodePreprocessor>,
     use_conflict_avoidance_table: bool,
     low_level_generated: usize,
     low_level_solver: &'a AStarLowLevelSolver,
-    heuristic: Rc<dyn Heuristic>,
-    h_value: Cell<Option<f64>>,
+    heuristic: Arc<dyn Heuristic>,
+    h_value: OnceLock<f64>,
+    feasible: bool,
+    /// Threads used to plan paths, generate children and compute the heuristic.
+    pub(crate) threads: usize,
+    pub(crate) statistics: Arc<HighLevelStatistics>,
+    /// The MDDs of the agents, shared by all nodes of a conflict tree.
+    pub(crate) mdds: Arc<MDDCache>,
+    pub(crate) recording: Option<NodeRecording>,
 }
 
 impl<'a> std::fmt::Debug for ConflictTreeNode<'a> {
@@ -120,21 +167,23 @@ impl Hash for ConflictTreeNode<'_> {
 }
 
 impl<'a> ConflictTreeNode<'a> {
+    /// Creates a node and initialises it, which the solver defers to [`Self::init`].
+    #[cfg(test)]
     pub fn new(
         agents: Vec<&'a Agent>,
         constraints: Vec<Box<Constraint<'a>>>,
         precomputed_paths: HashMap<&'a Agent, Vec<(i32, i32)>>,
         scenario: &'a Grid,
         conflict_picker: Option<
-            fn(&Grid, &HashMap<&Agent, Path>, &Vec<Box<Conflict<'a>>>) -> Option<Box<Conflict<'a>>>,
+            fn(&Grid, &PathMap, &MDDCache, &Vec<Box<Conflict<'a>>>) -> Option<Box<Conflict<'a>>>,
         >,
         post_expanded_callback: Option<
             fn(&Self, &Conflict<'a>, Vec<Box<Self>>) -> Option<Vec<Box<Self>>>,
         >,
-        node_preprocessor: Option<Rc<dyn CTNodePreprocessor>>,
+        node_preprocessor: Option<Arc<dyn CTNodePreprocessor>>,
         use_conflict_avoidance_table: bool,
         low_level_solver: &'a AStarLowLevelSolver,
-        heuristic: Rc<dyn Heuristic>,
+        heuristic: Arc<dyn Heuristic>,
     ) -> ConflictTreeNode<'a> {
         let mut ctn = ConflictTreeNode::new_without_init(
             agents,
@@ -148,30 +197,41 @@ impl<'a> ConflictTreeNode<'a> {
             low_level_solver,
             heuristic,
         );
+        ctn.init();
+        ctn
+    }
+
+    /// Preprocesses the node, then plans the missing paths and finds the
+    /// conflicts of the paths that differ from those of the occupancy index.
+    pub(crate) fn init(&mut self) {
         let t0 = std::time::Instant::now();
-        Rc::clone(&ctn.node_preprocessor).preprocess(&mut ctn);
+        Arc::clone(&self.node_preprocessor).preprocess(self);
         log::debug!("Time to preprocess high level node: {:?}", t0.elapsed());
         log::debug!(
             "Agents left to plan after preprocessing: {}/{}",
-            ctn.agents.len() - ctn.paths.len(),
-            ctn.agents.len()
+            self.agents.len() - self.paths.len(),
+            self.agents.len()
         );
         log::debug!(
             "Agents left to plan for: {:?}",
-            ctn.agents
+            self.agents
                 .iter()
-                .filter(|a| !ctn.paths.contains_key(*a))
+                .filter(|a| !self.paths.contains_key(a))
                 .collect::<Vec<_>>()
         );
         let t0 = std::time::Instant::now();
-        ctn.compute_paths();
+        self.compute_paths();
         log::debug!("Time to compute paths: {:?}", t0.elapsed());
+        if !self.feasible {
+            log::debug!("Node is infeasible under its constraints");
+            self.conflicts.clear();
+            return;
+        }
         let t0 = std::time::Instant::now();
-        ctn.compute_conflicts();
+        self.update_conflicts();
         log::debug!("Time to compute conflicts: {:?}", t0.elapsed());
-        log::debug!("Number of conflicts: {}", ctn.conflicts.len());
-        log::debug!("Number of constraints: {}", ctn.constraints.len());
-        ctn
+        log::debug!("Number of conflicts: {}", self.conflicts.len());
+        log::debug!("Number of constraints: {}", self.constraints.len());
     }
 
     pub fn new_without_init(
@@ -180,30 +240,36 @@ impl<'a> ConflictTreeNode<'a> {
         scenario: &'a Grid,
         precomputed_paths: HashMap<&'a Agent, Vec<(i32, i32)>>,
         conflict_picker: Option<
-            fn(&Grid, &HashMap<&Agent, Path>, &Vec<Box<Conflict<'a>>>) -> Option<Box<Conflict<'a>>>,
+            fn(&Grid, &PathMap, &MDDCache, &Vec<Box<Conflict<'a>>>) -> Option<Box<Conflict<'a>>>,
         >,
         post_expanded_callback: Option<
             fn(&Self, &Conflict<'a>, Vec<Box<Self>>) -> Option<Vec<Box<Self>>>,
         >,
-        node_preprocessor: Option<Rc<dyn CTNodePreprocessor>>,
+        node_preprocessor: Option<Arc<dyn CTNodePreprocessor>>,
         use_conflict_avoidance_table: bool,
         low_level_solver: &'a AStarLowLevelSolver,
-        heuristic: Rc<dyn Heuristic>,
+        heuristic: Arc<dyn Heuristic>,
     ) -> ConflictTreeNode<'a> {
         let mut ctn = ConflictTreeNode {
-            constraints,
-            agents,
-            paths: precomputed_paths,
+            constraints: constraints.into(),
+            paths: PathMap::new(&agents, precomputed_paths),
+            agents: agents.into(),
             conflicts: Vec::<Box<Conflict>>::new(),
+            occupancy: Arc::default(),
             scenario,
-            conflict_picker: |_, _, conflicts| Some(conflicts[0].clone()),
+            conflict_picker: |_, _, _, conflicts| Some(conflicts[0].clone()),
             post_expanded_callback: |_, _, expanded| Some(expanded), // TODO: replace with optimization
-            node_preprocessor: Rc::new(IdentityPreprocessor::new()),
+            node_preprocessor: Arc::new(IdentityPreprocessor::new()),
             low_level_generated: 0,
             use_conflict_avoidance_table,
             low_level_solver,
             heuristic,
-            h_value: Cell::new(None),
+            h_value: OnceLock::new(),
+            feasible: true,
+            threads: 1,
+            statistics: Arc::new(HighLevelStatistics::default()),
+            mdds: Arc::new(MDDCache::new()),
+            recording: None,
         };
         if let Some(pick_conflict) = conflict_picker {
             ctn.conflict_picker = pick_conflict;
@@ -217,75 +283,50 @@ impl<'a> ConflictTreeNode<'a> {
         ctn
     }
 
-    pub fn compute_conflicts(&mut self) {
-        let mut conflicts = Vec::<Box<Conflict>>::new();
-        let mut agent_locations = HashMap::<(i32, i32), Vec<&Agent>>::new();
-        for time_step in 0..self.paths.values().map(|p| p.len()).max().unwrap() {
-            for agent in self.agents.iter() {
-                if time_step >= self.paths[agent].len() {
-                    continue;
-                }
-                let location = self.paths[agent][time_step];
-                let current_location_agents = agent_locations
-                    .entry(location)
-                    .or_insert(Vec::<&Agent>::new());
-                if !current_location_agents.contains(&agent) {
-                    current_location_agents.push(agent);
-                }
-                if time_step > 0 {
-                    let prev_location = self.paths[agent][time_step - 1];
-                    if prev_location != location {
-                        agent_locations
-                            .entry(prev_location)
-                            .or_default()
-                            .retain(|a| a != agent);
-                    }
-                }
-            }
-            for (location, agents) in agent_locations.iter() {
-                for (j, agent1) in agents.iter().enumerate() {
-                    for agent2 in agents[..j].iter() {
-                        if agents.len() > 1 {
-                            conflicts.push(Box::new(Conflict::Vertex(VertexConflict {
-                                agent1,
-                                agent2,
-                                time: time_step as i32,
-                                location: location.clone(),
-                            })));
-                        }
-                    }
-                    if time_step == 0 || time_step >= self.paths[agent1].len() {
-                        continue;
-                    }
-                    for agent2 in agent_locations
-                        .get(&self.paths[agent1][time_step - 1])
-                        .unwrap()
-                    {
-                        if time_step >= self.paths[agent2].len()
-                            || agent1 == agent2
-                            || &self.paths[agent1][time_step - 1] > location
-                        {
-                            continue;
-                        }
-                        if &self.paths[agent2][time_step - 1] == location {
-                            conflicts.push(Box::new(Conflict::Edge(EdgeConflict {
-                                agent1,
-                                agent2,
-                                time: time_step as i32,
-                                location1: location.clone(),
-                                location2: self.paths[agent1][time_step - 1],
-                            })));
-                        }
-                    }
-                }
-            }
+    /// Brings the conflicts up to date with the paths, only looking for the
+    /// conflicts of the agents whose path differs from the indexed one.
+    fn update_conflicts(&mut self) {
+        let changed = self
+            .agents
+            .iter()
+            .copied()
+            .filter(|agent| self.occupancy.path(agent) != Some(&self.paths[agent]))
+            .collect::<Vec<_>>();
+        self.update_conflicts_of(&changed);
+    }
+
+    /// Brings the conflicts up to date with the new paths of `changed`.
+    fn update_conflicts_of(&mut self, changed: &[&'a Agent]) {
+        if changed.is_empty() {
+            return;
+        }
+        let occupancy = Arc::make_mut(&mut self.occupancy);
+        for agent in changed.iter() {
+            occupancy.remove(agent);
         }
-        self.conflicts = conflicts;
+        self.conflicts.retain(|conflict| {
+            let (agent1, agent2) = conflict.agents();
+            !changed.contains(&agent1) && !changed.contains(&agent2)
+        });
+        for &agent in changed {
+            let path = &self.paths[agent];
+            let conflicts = occupancy.conflicts(agent, path, &self.agents);
+            self.conflicts.extend(conflicts.into_iter().map(Box::new));
+            occupancy.insert(agent, path.clone());
+        }
+        // the earliest conflicts first, like a scan of the paths over time
+        self.conflicts.sort_by_key(|conflict| conflict.time());
+    }
+
+    /// Replaces the path of `agent` and updates the conflicts.
+    pub(crate) fn replace_path(&mut self, agent: &'a Agent, path: Path) {
+        self.paths.insert(agent, path);
+        self.update_conflicts_of(&[agent]);
     }
 
     fn build_conflict_avoidance_table(&self) -> HashSet<LocationTime> {
         let mut conflict_avoidance_table = HashSet::<LocationTime>::new();
-        for (_, path) in self.paths.iter() {
+        for path in self.paths.values() {
             Self::update_conflict_avoidance_table(&mut conflict_avoidance_table, path);
         }
         conflict_avoidance_table
@@ -307,6 +348,9 @@ impl<'a> ConflictTreeNode<'a> {
     }
 
     fn compute_paths(&mut self) {
+        if !self.use_conflict_avoidance_table && self.threads > 1 {
+            return self.compute_independent_paths();
+        }
         let mut conflict_avoidance_table = if self.use_conflict_avoidance_table {
             self.build_conflict_avoidance_table()
         } else {
@@ -316,38 +360,16 @@ impl<'a> ConflictTreeNode<'a> {
             if self.paths.contains_key(agent) {
                 continue;
             }
-            let mut obstacles = self.constraints_to_obstacles(agent);
-            self.scenario.obstacles.iter().for_each(|(loc, prevs)| {
-                obstacles
-                    .entry(*loc)
-                    .or_insert(vec![])
-                    .extend(prevs.clone());
-            });
-            let path = self.low_level_solver.find_shortest_path(
-                agent.id.clone(),
-                Grid::new(
-                    self.scenario.width,
-                    self.scenario.height,
-                    obstacles.into_iter().collect(),
-                    agent.goal,
-                ),
-                LocationTime {
-                    location: agent.start,
-                    time: 0,
-                },
-                &conflict_avoidance_table,
-            );
-            self.paths.insert(
-                agent,
-                path.map(|(path, nodes_generated)| {
-                    self.low_level_generated += nodes_generated;
-                    path
-                })
-                .unwrap()
-                .iter()
-                .map(|n| n.location)
-                .collect(),
-            );
+            let Some((path, nodes_generated)) =
+                self.find_shortest_path(agent, &conflict_avoidance_table)
+            else {
+                log::debug!("No path for agent {} under constraints", agent.id);
+                self.feasible = false;
+                return;
+            };
+            self.low_level_generated += nodes_generated;
+            self.paths
+                .insert(agent, path.iter().map(|n| n.location).collect());
             if self.use_conflict_avoidance_table {
                 Self::update_conflict_avoidance_table(
                     &mut conflict_avoidance_table,
@@ -357,30 +379,107 @@ impl<'a> ConflictTreeNode<'a> {
         }
     }
 
-    pub(crate) fn constraints_to_obstacles(
-        &self,
-        agent: &&Agent,
-    ) -> HashMap<LocationTime, Vec<(i32, i32)>> {
-        self.constraints
+    /// Without a CAT the paths of the agents do not depend on each other,
+    /// so they are planned in parallel.
+    fn compute_independent_paths(&mut self) {
+        let agents = self
+            .agents
             .iter()
-            .filter(|c| c.agent == *agent)
-            .map(|c| {
-                (
-                    LocationTime {
-                        location: c.location,
-                        time: c.time,
-                    },
-                    match c.prev_location {
-                        Some(prev_location) => vec![prev_location],
-                        None => vec![],
-                    },
-                )
-            })
-            .collect()
-    }
-
-    pub(crate) fn invalidate_cached_h_values(&self) {
-        self.h_value.set(None);
+            .copied()
+            .filter(|agent| !self.paths.contains_key(agent))
+            .collect::<Vec<_>>();
+        let empty_cat = HashSet::new();
+        let paths = parallel_map(&agents, self.threads, |agent| {
+            self.find_shortest_path(agent, &empty_cat)
+        });
+        for (agent, path) in agents.into_iter().zip(paths) {
+            let Some((path, nodes_generated)) = path else {
+                log::debug!("No path for agent {} under constraints", agent.id);
+                self.feasible = false;
+                return;
+            };
+            self.low_level_generated += nodes_generated;
+            self.paths
+                .insert(agent, path.iter().map(|n| n.location).collect());
+        }
+    }
+
+    fn find_shortest_path(
+        &self,
+        agent: &Agent,
+        conflict_avoidance_table: &HashSet<LocationTime>,
+    ) -> Option<(Vec<LocationTime>, usize)> {
+        let mut grid = self.scenario.with_goal(agent.goal);
+        grid.add_constraints(&self.constraint_table(agent));
+        self.low_level_solver.find_shortest_path(
+            agent.id.clone(),
+            grid,
+            LocationTime {
+                location: agent.start,
+                time: 0,
+            },
+            conflict_avoidance_table,
+        )
+    }
+
+    /// The constraints on `agent`, as the obstacles its path must avoid.
+    pub(crate) fn constraint_table(&self, agent: &Agent) -> ConstraintTable {
+        Self::table_of(self.constraints.iter(), agent)
+    }
+
+    /// The constraints on `agent` in the parent node, if the node's newest
+    /// constraint is on `agent`.
+    pub(crate) fn parent_constraint_table(&self, agent: &Agent) -> Option<ConstraintTable> {
+        if self.constraints.last()?.agent != agent {
+            return None;
+        }
+        Some(Self::table_of(self.constraints.iter().skip(1), agent))
+    }
+
+    fn table_of<'c>(
+        constraints: impl Iterator<Item = &'c Constraint<'a>>,
+        agent: &Agent,
+    ) -> ConstraintTable
+    where
+        'a: 'c,
+    {
+        let mut table = ConstraintTable::default();
+        for constraint in constraints.filter(|c| c.agent == agent) {
+            table.insert(
+                LocationTime::new(constraint.location, constraint.time),
+                constraint.prev_location,
+            );
+        }
+        table
+    }
+
+    pub(crate) fn invalidate_cached_h_values(&mut self) {
+        self.h_value = OnceLock::new();
+    }
+
+    /// Whether every agent has a path under the node's constraints.
+    pub(crate) fn is_feasible(&self) -> bool {
+        self.feasible
+    }
+
+    /// g plus h if h has already been computed, which can be expensive.
+    pub(crate) fn known_f(&self) -> f64 {
+        self.g() + self.h_value.get().copied().unwrap_or(0.0)
+    }
+
+    /// Shares everything but the new constraint and the replanned path with
+    /// the parent.
+    fn child(&self, constraint: Constraint<'a>) -> Box<Self> {
+        let agent = constraint.agent;
+        let mut child = self.clone();
+        log::debug!("Current constraints: {:?}", self.constraints);
+        child.constraints.push(constraint);
+        child.paths.remove(agent);
+        child.low_level_generated = 0;
+        child.h_value = OnceLock::new();
+        child.recording = None;
+        child.init();
+        Box::new(child)
     }
 }
 
@@ -390,11 +489,13 @@ impl AStarNode<'_> for ConflictTreeNode<'_> {
     }
 
     fn h(&self) -> f64 {
-        self.h_value.get().unwrap_or_else(|| {
+        *self.h_value.get_or_init(|| {
             let t0 = std::time::Instant::now();
             let h_value = self.heuristic.h(self);
             debug!("Calculating high-level heuristic took {:?}", t0.elapsed());
-            self.h_value.set(Some(h_value));
+            if let Some(recording) = &self.recording {
+                recording.set_h(h_value);
+            }
             h_value
         })
     }
@@ -417,9 +518,11 @@ impl AStarNode<'_> for ConflictTreeNode<'_> {
             return Some(expanded);
         }
         let t0 = std::time::Instant::now();
-        let conflict = (self.conflict_picker)(self.scenario, &self.paths, &self.conflicts)?;
+        let conflict =
+            (self.conflict_picker)(self.scenario, &self.paths, &self.mdds, &self.conflicts)?;
         debug!("Time to pick conflict {:?}", t0.elapsed());
         log::debug!("Expanding conflict: {:?}", conflict);
+        let mut new_constraints = Vec::<Constraint>::new();
         match *conflict.clone() {
             Conflict::Vertex(vc) => {
                 for agent in vec![vc.agent1, vc.agent2] {
@@ -429,27 +532,10 @@ impl AStarNode<'_> for ConflictTreeNode<'_> {
                         location: vc.location,
                         prev_location: None,
                     };
-                    if self.constraints.contains(&Box::new(constraint.clone())) {
+                    if self.constraints.contains(&constraint) {
                         continue;
                     }
-                    let mut new_constraints = self.constraints.clone();
-                    new_constraints.push(Box::new(constraint));
-                    log::debug!("Current constraints: {:?}", self.constraints);
-                    log::debug!("New constraints: {:?}", new_constraints);
-                    let mut new_paths = self.paths.clone();
-                    new_paths.remove(agent);
-                    expanded.push(Box::new(ConflictTreeNode::new(
-                        self.agents.clone(),
-                        new_constraints,
-                        new_paths,
-                        self.scenario,
-                        Some(self.conflict_picker),
-                        Some(self.post_expanded_callback),
-                        Some(Rc::clone(&self.node_preprocessor)),
-                        self.use_conflict_avoidance_table,
-                        self.low_level_solver,
-                        Rc::clone(&self.heuristic),
-                    )));
+                    new_constraints.push(constraint);
                 }
             }
             Conflict::Edge(ec) => {
@@ -462,35 +548,47 @@ impl AStarNode<'_> for ConflictTreeNode<'_> {
                     };
                     let mut vertex_equiv_constraint = constraint.clone();
                     vertex_equiv_constraint.prev_location = None;
-                    if self
-                        .constraints
-                        .contains(&Box::new(vertex_equiv_constraint))
-                        || self.constraints.contains(&Box::new(constraint.clone()))
+                    if self.constraints.contains(&vertex_equiv_constraint)
+                        || self.constraints.contains(&constraint)
                     {
                         continue;
                     }
-                    let mut new_constraints = self.constraints.clone();
-                    new_constraints.push(Box::new(constraint));
-                    log::debug!("Current constraints: {:?}", self.constraints);
-                    log::debug!("New constraints: {:?}", new_constraints);
-                    let mut new_paths = self.paths.clone();
-                    new_paths.remove(agent);
-                    expanded.push(Box::new(ConflictTreeNode::new(
-                        self.agents.clone(),
-                        new_constraints,
-                        new_paths,
-                        self.scenario,
-                        Some(self.conflict_picker),
-                        Some(self.post_expanded_callback),
-                        Some(Rc::clone(&self.node_preprocessor)),
-                        self.use_conflict_avoidance_table,
-                        self.low_level_solver,
-                        Rc::clone(&self.heuristic),
-                    )));
+                    new_constraints.push(constraint);
                 }
             }
         }
-        (self.post_expanded_callback)(self, &conflict, expanded)
+        expanded.extend(parallel_map(&new_constraints, self.threads, |constraint| {
+            self.child(constraint.clone())
+        }));
+        // recorded in order, so the ids do not depend on thread scheduling
+        if let Some(recording) = &self.recording {
+            for child in expanded.iter_mut() {
+                let child_recording = recording.child(child.constraints.last(), child);
+                child.recording = Some(child_recording);
+            }
+        }
+        let num_expanded = expanded.len();
+        expanded.retain(|child| child.is_feasible());
+        let num_pruned = num_expanded - expanded.len();
+        if num_pruned > 0 {
+            log::debug!("Pruned {} infeasible children", num_pruned);
+            self.statistics
+                .infeasible_pruned
+                .fetch_add(num_pruned, Ordering::Relaxed);
+        }
+        let generated = expanded
+            .iter()
+            .filter_map(|child| child.recording.as_ref().map(|r| r.id()))
+            .collect::<Vec<_>>();
+        let mut children = (self.post_expanded_callback)(self, &conflict, expanded);
+        if let Some(recording) = &self.recording {
+            recording.expanded(self, &conflict, &generated, children.as_mut());
+        }
+        if let Some(children) = &children {
+            // evaluated as a batch instead of one by one when pushed to the open list
+            parallel_map(children, self.threads, |child| child.h());
+        }
+        children
     }
 
     fn id(&self) -> String {
@@ -501,7 +599,7 @@ impl AStarNode<'_> for ConflictTreeNode<'_> {
     }
 }
 
-pub trait CTNodePreprocessor {
+pub trait CTNodePreprocessor: Send + Sync {
     fn preprocess(&self, node: &mut ConflictTreeNode);
 }
 
@@ -518,5 +616,7 @@ impl CTNodePreprocessor for IdentityPreprocessor {
 }
 
 pub(crate) mod heuristic;
+mod occupancy;
+pub(crate) mod persistent;
 #[cfg(test)]
 mod tests;
@@ -1,18 +1,19 @@
 use std::{
-    cell::RefCell,
     collections::{HashMap, HashSet},
     rc::Rc,
+    sync::{Arc, RwLock},
 };
 
 use crate::cbs::{
-    low_level::LocationTime,
-    mdd::{mdd, merge_mdds},
+    low_level::obstacles::ConstraintTable,
+    mdd::{joint_mdd_paths, LevelSets},
+    parallel::parallel_map,
     vertex_cover::{MVCGraph, min_vertex_cover},
 };
 
-use super::{Agent, ConflictTreeNode, Path};
+use super::{Agent, ConflictTreeNode};
 
-pub trait Heuristic {
+pub trait Heuristic: Send + Sync {
     fn h(&self, node: &ConflictTreeNode<'_>) -> f64;
 }
 
@@ -45,11 +46,11 @@ impl<'a> DependencyEdge<'a> {
 }
 
 type DependencyGraph<'a> = HashSet<DependencyEdge<'a>>;
-type AgentWithConstraints = (String, Vec<(LocationTime, Vec<(i32, i32)>)>);
-type AgentWithConstraintsPair = (Rc<AgentWithConstraints>, Rc<AgentWithConstraints>);
+type AgentWithConstraints = (String, ConstraintTable);
+type AgentWithConstraintsPair = (Arc<AgentWithConstraints>, Arc<AgentWithConstraints>);
 
 pub(crate) struct DGHeuristic {
-    dependency_edges: RefCell<HashMap<AgentWithConstraintsPair, f64>>,
+    dependency_edges: RwLock<HashMap<AgentWithConstraintsPair, f64>>,
 }
 
 impl Heuristic for DGHeuristic {
@@ -61,81 +62,78 @@ impl Heuristic for DGHeuristic {
 impl DGHeuristic {
     pub(crate) fn new() -> Self {
         Self {
-            dependency_edges: RefCell::new(HashMap::new()),
+            dependency_edges: RwLock::new(HashMap::new()),
         }
     }
 
     fn get_dependency_weight(
         &self,
-        agent: Rc<AgentWithConstraints>,
-        other_agent: Rc<AgentWithConstraints>,
+        agent: Arc<AgentWithConstraints>,
+        other_agent: Arc<AgentWithConstraints>,
     ) -> Option<f64> {
         self.dependency_edges
-            .borrow()
+            .read()
+            .expect("lock is not poisoned")
             .get(&(agent, other_agent))
             .copied()
     }
 
+    /// The pairwise MDD checks are independent, so they are spread
+    /// over the node's threads, as are the MDDs they need.
     pub(crate) fn compute(&self, node: &ConflictTreeNode<'_>) -> f64 {
         let mut graph = DependencyGraph::new();
-        let mut mdds: HashMap<&Agent, Vec<Vec<(i32, i32)>>> = HashMap::new();
-        let mut agents_with_constraints: HashMap<&&Agent, Rc<AgentWithConstraints>> =
-            HashMap::new();
+        let agents_with_constraints = node
+            .agents
+            .iter()
+            .map(|agent| (*agent, self.to_agent_with_constraints(agent, node)))
+            .collect::<HashMap<&Agent, Arc<AgentWithConstraints>>>();
+        let mut unknown_pairs = Vec::<(&Agent, &Agent)>::new();
         for (i, agent) in node.agents.iter().enumerate() {
-            for j in (i + 1)..node.agents.len() {
-                let other_agent = &node.agents[j];
-                if !agents_with_constraints.contains_key(agent) {
-                    agents_with_constraints
-                        .insert(agent, self.to_agent_with_constraints(agent, node));
-                }
-                if !agents_with_constraints.contains_key(other_agent) {
-                    agents_with_constraints.insert(
-                        other_agent,
-                        self.to_agent_with_constraints(other_agent, node),
-                    );
-                }
-                if let Some(dep_weight) = self.get_dependency_weight(
-                    Rc::clone(&agents_with_constraints[agent]),
-                    Rc::clone(&agents_with_constraints[other_agent]),
+            for other_agent in node.agents[(i + 1)..].iter() {
+                match self.get_dependency_weight(
+                    Arc::clone(&agents_with_constraints[agent]),
+                    Arc::clone(&agents_with_constraints[other_agent]),
                 ) {
-                    if dep_weight > 0.0 {
+                    Some(dep_weight) if dep_weight > 0.0 => {
                         graph.insert(DependencyEdge::new(agent, other_agent, dep_weight));
                     }
-                    continue;
-                }
-                let c = (node
-                    .paths
-                    .get(agent)
-                    .unwrap()
-                    .len()
-                    .max(node.paths.get(other_agent).unwrap().len())
-                    as i32)
-                    - 1;
-                if !mdds.contains_key(agent) {
-                    mdds.insert(agent, compute_agent_mdd(node, agent));
+                    Some(_) => {}
+                    None => unknown_pairs.push((agent, other_agent)),
                 }
-                if !mdds.contains_key(other_agent) {
-                    mdds.insert(other_agent, compute_agent_mdd(node, other_agent));
-                }
-                let mdd1 = mdds.get(agent).expect("should have been computed");
-                let mdd2 = mdds.get(other_agent).expect("should have been computed");
-                let joint_mdd = merge_mdds(&mdd1, &mdd2, c);
-                let mut dep_weight = 0.0;
-                if is_joint_mdd_empty(&joint_mdd, agent, other_agent) {
-                    log::debug!(
-                        "agent {:?} and agent {:?} have a dependency",
-                        agent,
-                        other_agent
-                    );
-                    graph.insert(DependencyEdge::new(agent, other_agent, 1.0));
-                    dep_weight = 1.0;
-                }
-                self.cache_dependency_weight(
-                    Rc::clone(&agents_with_constraints[agent]),
-                    Rc::clone(&agents_with_constraints[other_agent]),
-                    dep_weight,
+            }
+        }
+        let mut seen = HashSet::new();
+        let agents_to_expand = unknown_pairs
+            .iter()
+            .flat_map(|(agent, other_agent)| [*agent, *other_agent])
+            .filter(|agent| seen.insert(*agent))
+            .collect::<Vec<_>>();
+        let mdds = agents_to_expand
+            .iter()
+            .copied()
+            .zip(parallel_map(&agents_to_expand, node.threads, |agent| {
+                compute_agent_mdd(node, agent, &agents_with_constraints[agent].1)
+            }))
+            .collect::<HashMap<&Agent, Arc<LevelSets>>>();
+        let dependent = parallel_map(&unknown_pairs, node.threads, |(agent, other_agent)| {
+            joint_mdd_paths(&[&mdds[agent], &mdds[other_agent]], node.scenario).is_none()
+        });
+        for ((agent, other_agent), dependent) in unknown_pairs.into_iter().zip(dependent) {
+            let mut dep_weight = 0.0;
+            if dependent {
+                log::debug!(
+                    "agent {:?} and agent {:?} have a dependency",
+                    agent,
+                    other_agent
                 );
+                graph.insert(DependencyEdge::new(agent, other_agent, 1.0));
+                dep_weight = 1.0;
             }
+            self.cache_dependency_weight(
+                Arc::clone(&agents_with_constraints[agent]),
+                Arc::clone(&agents_with_constraints[other_agent]),
+                dep_weight,
+            );
         }
         let mvc = find_mvc(&graph);
         let h = mvc.len() as f64;
@@ -144,53 +142,41 @@ impl DGHeuristic {
 
     fn cache_dependency_weight(
         &self,
-        agent: Rc<AgentWithConstraints>,
-        other_agent: Rc<AgentWithConstraints>,
+        agent: Arc<AgentWithConstraints>,
+        other_agent: Arc<AgentWithConstraints>,
         weight: f64,
     ) {
         self.dependency_edges
-            .borrow_mut()
+            .write()
+            .expect("lock is not poisoned")
             .insert((agent, other_agent), weight);
     }
     fn to_agent_with_constraints(
         &self,
         agent: &&Agent,
         node: &ConflictTreeNode<'_>,
-    ) -> Rc<AgentWithConstraints> {
-        let mut agent_with_constraints = (
-            agent.id.clone(),
-            node.constraints_to_obstacles(agent)
-                .iter()
-                .map(|(loc, coming_from)| (loc.clone(), coming_from.clone()))
-                .collect::<Vec<_>>(),
-        );
-        agent_with_constraints
-            .1
-            .sort_by_key(|(loc, _)| (loc.time, loc.location));
-        Rc::new(agent_with_constraints)
+    ) -> Arc<AgentWithConstraints> {
+        Arc::new((agent.id.clone(), node.constraint_table(agent)))
     }
 }
 
-fn compute_agent_mdd(node: &ConflictTreeNode<'_>, agent: &&Agent) -> Vec<Vec<(i32, i32)>> {
-    let c = (node.paths.get(agent).unwrap().len() as i32) - 1;
-    let mut scenario = node.scenario.clone();
-    scenario
-        .obstacles
-        .extend(node.constraints_to_obstacles(agent));
-    let agent_mdd = mdd(&agent, &scenario, c).unwrap();
-    agent_mdd
-}
-
-fn is_joint_mdd_empty(
-    joint_mdd: &Vec<Vec<((i32, i32), (i32, i32))>>,
+/// Built from the parent node's MDD of `agent` if the node constrained it.
+fn compute_agent_mdd(
+    node: &ConflictTreeNode<'_>,
     agent: &&Agent,
-    other_agent: &&Agent,
-) -> bool {
-    !joint_mdd
-        .last()
+    constraints: &ConstraintTable,
+) -> Arc<LevelSets> {
+    let c = (node.paths.get(agent).unwrap().len() as i32) - 1;
+    let parent_constraints = node.parent_constraint_table(agent);
+    node.mdds
+        .get(
+            agent,
+            node.scenario,
+            constraints,
+            parent_constraints.as_ref(),
+            c,
+        )
         .unwrap()
-        .contains(&(agent.goal, other_agent.goal))
-        || joint_mdd.iter().any(|layer| layer.is_empty())
 }
 
 fn find_mvc<'a>(graph: &'a HashSet<DependencyEdge<'a>>) -> Vec<Rc<&'a Agent>> {
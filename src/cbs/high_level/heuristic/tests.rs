@@ -1,4 +1,5 @@
 use crate::cbs::{
+    high_level::Path,
     low_level::{AStarLowLevelSolver, Grid, LocationTime},
 };
 
@@ -32,7 +33,8 @@ use rstest::rstest;
     vec![],
     0.0
 )]
-#[case::cardinal_edge_conflict(
+// agent 1 can follow agent 0 through (0, 0) and (0, 1) at the same cost
+#[case::non_cardinal_edge_conflict(
     Grid::new(
         2, 
         3,
@@ -54,7 +56,7 @@ use rstest::rstest;
         ],
     ],
     vec![],
-    1.0
+    0.0
 )]
 #[case::cardinal_rectangle_conflict(
     Grid::new(
@@ -126,7 +128,7 @@ fn test_dg_heuristic(
         .iter()
         .map(|agent| (agent, paths[agent.id.parse::<usize>().unwrap()].clone()))
         .collect();
-    let heuristic: Rc<dyn Heuristic> = Rc::new(DGHeuristic::new());
+    let heuristic: Arc<dyn Heuristic> = Arc::new(DGHeuristic::new());
     let solver = AStarLowLevelSolver::new();
     let node = ConflictTreeNode::new(
         agents.iter().collect(),
@@ -138,7 +140,7 @@ fn test_dg_heuristic(
         None,
         true,
         &solver,
-        Rc::clone(&heuristic),
+        Arc::clone(&heuristic),
     );
     let h = heuristic.h(&node);
     assert_eq!(h, expected_h);
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crate::cbs::low_level::LocationTime;
+
+use super::{Agent, Conflict, EdgeConflict, Path, VertexConflict};
+
+/// Where the indexed agents are at every timestep, so that the conflicts
+/// of a single agent's path are found without scanning the other paths.
+/// Agents stay at their goal once their path ends.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OccupancyIndex<'a> {
+    paths: HashMap<&'a Agent, Path>,
+    /// The agents at each location and time, while following their path.
+    occupants: HashMap<LocationTime, Vec<&'a Agent>>,
+    /// The agents staying at each location once their path ended, with the time they arrived.
+    parked: HashMap<(i32, i32), Vec<(&'a Agent, usize)>>,
+}
+
+impl<'a> OccupancyIndex<'a> {
+    /// The indexed path of `agent`, if any.
+    pub(crate) fn path(&self, agent: &Agent) -> Option<&Path> {
+        self.paths.get(agent)
+    }
+
+    pub(crate) fn insert(&mut self, agent: &'a Agent, path: Path) {
+        self.remove(agent);
+        for (time, location) in path.iter().enumerate() {
+            self.occupants
+                .entry(LocationTime::new(*location, time as i32))
+                .or_default()
+                .push(agent);
+        }
+        let goal = *path.last().expect("paths are not empty");
+        self.parked
+            .entry(goal)
+            .or_default()
+            .push((agent, path.len() - 1));
+        self.paths.insert(agent, path);
+    }
+
+    pub(crate) fn remove(&mut self, agent: &Agent) {
+        let Some(path) = self.paths.remove(agent) else {
+            return;
+        };
+        for (time, location) in path.iter().enumerate() {
+            let key = LocationTime::new(*location, time as i32);
+            if let Some(occupants) = self.occupants.get_mut(&key) {
+                occupants.retain(|occupant| *occupant != agent);
+                if occupants.is_empty() {
+                    self.occupants.remove(&key);
+                }
+            }
+        }
+        let goal = path.last().expect("paths are not empty");
+        if let Some(parked) = self.parked.get_mut(goal) {
+            parked.retain(|(occupant, _)| *occupant != agent);
+            if parked.is_empty() {
+                self.parked.remove(goal);
+            }
+        }
+    }
+
+    /// The conflicts of `agent` following `path` with the indexed agents.
+    /// In a vertex conflict, `agent1` is the agent that arrived last at the
+    /// location, or the later one in `order` if both arrived together. In an
+    /// edge conflict, it is the agent moving away from the lower location.
+    pub(crate) fn conflicts(
+        &self,
+        agent: &'a Agent,
+        path: &Path,
+        order: &[&Agent],
+    ) -> Vec<Conflict<'a>> {
+        let mut conflicts = Vec::<Conflict<'a>>::new();
+        let horizon = self
+            .paths
+            .values()
+            .map(|path| path.len())
+            .chain([path.len()])
+            .max()
+            .expect("not empty");
+        for time in 0..horizon {
+            let location = path[time.min(path.len() - 1)];
+            let moving = self
+                .occupants
+                .get(&LocationTime::new(location, time as i32))
+                .into_iter()
+                .flatten()
+                .copied();
+            // two agents cannot be parked at the same location, as their goals differ
+            let parked = self
+                .parked
+                .get(&location)
+                .filter(|_| time < path.len())
+                .into_iter()
+                .flatten()
+                .filter(|(_, arrival)| *arrival < time)
+                .map(|(other, _)| *other);
+            for other in moving.chain(parked) {
+                let (agent1, agent2) = self.later_arrival(agent, path, other, time, order);
+                conflicts.push(Conflict::Vertex(VertexConflict {
+                    agent1,
+                    agent2,
+                    time: time as i32,
+                    location,
+                }));
+            }
+            if time == 0 || time >= path.len() || path[time - 1] == location {
+                continue;
+            }
+            let prev_location = path[time - 1];
+            let swapping = self
+                .occupants
+                .get(&LocationTime::new(prev_location, time as i32))
+                .into_iter()
+                .flatten()
+                .filter(|other| self.paths[*other][time - 1] == location);
+            for other in swapping {
+                let (agent1, agent2) = if prev_location < location {
+                    (agent, *other)
+                } else {
+                    (*other, agent)
+                };
+                conflicts.push(Conflict::Edge(EdgeConflict {
+                    agent1,
+                    agent2,
+                    time: time as i32,
+                    location1: location.max(prev_location),
+                    location2: location.min(prev_location),
+                }));
+            }
+        }
+        conflicts
+    }
+
+    /// Orders `agent` and the indexed `other`, both at the same location
+    /// at `time`, by the time they arrived there.
+    fn later_arrival(
+        &self,
+        agent: &'a Agent,
+        path: &Path,
+        other: &'a Agent,
+        time: usize,
+        order: &[&Agent],
+    ) -> (&'a Agent, &'a Agent) {
+        let agent_arrival = arrival(path, time);
+        let other_arrival = arrival(&self.paths[other], time);
+        let position = |agent: &Agent| order.iter().position(|a| *a == agent);
+        if (agent_arrival, position(agent)) > (other_arrival, position(other)) {
+            (agent, other)
+        } else {
+            (other, agent)
+        }
+    }
+}
+
+/// The first timestep of the stay at the location of `path` at `time`.
+fn arrival(path: &Path, time: usize) -> usize {
+    let mut arrival = time.min(path.len() - 1);
+    while arrival > 0 && path[arrival - 1] == path[arrival] {
+        arrival -= 1;
+    }
+    arrival
+}
+
+#[cfg(test)]
+mod tests;
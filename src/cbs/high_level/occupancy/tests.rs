@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+
+use super::*;
+use proptest::{collection::vec, prelude::*};
+
+/// A conflict without the order of its agents, as (agents, time, locations).
+type Unordered = (Vec<String>, i32, Vec<(i32, i32)>);
+
+fn unordered(conflict: &Conflict) -> Unordered {
+    let (agent1, agent2) = conflict.agents();
+    let mut agents = vec![agent1.id.clone(), agent2.id.clone()];
+    agents.sort();
+    let mut locations = match conflict {
+        Conflict::Vertex(c) => vec![c.location],
+        Conflict::Edge(c) => vec![c.location1, c.location2],
+    };
+    locations.sort();
+    (agents, conflict.time(), locations)
+}
+
+/// Compares every pair of paths at every timestep.
+fn scan(agents: &[Agent], paths: &[Path]) -> HashSet<Unordered> {
+    let horizon = paths.iter().map(|path| path.len()).max().unwrap_or(0);
+    let at = |path: &Path, time: usize| path[time.min(path.len() - 1)];
+    let mut conflicts = HashSet::new();
+    for i in 0..agents.len() {
+        for j in (i + 1)..agents.len() {
+            let (a, b) = (&paths[i], &paths[j]);
+            let mut ids = vec![agents[i].id.clone(), agents[j].id.clone()];
+            ids.sort();
+            for time in 0..horizon {
+                if at(a, time) == at(b, time) {
+                    conflicts.insert((ids.clone(), time as i32, vec![at(a, time)]));
+                }
+                let swapped = time > 0
+                    && time < a.len()
+                    && time < b.len()
+                    && a[time] != a[time - 1]
+                    && a[time - 1] == b[time]
+                    && a[time] == b[time - 1];
+                if swapped {
+                    let mut locations = vec![a[time], b[time]];
+                    locations.sort();
+                    conflicts.insert((ids.clone(), time as i32, locations));
+                }
+            }
+        }
+    }
+    conflicts
+}
+
+fn index_conflicts<'a>(
+    agents: &'a [Agent],
+    paths: &[Path],
+) -> (OccupancyIndex<'a>, Vec<Conflict<'a>>) {
+    let order = agents.iter().collect::<Vec<_>>();
+    let mut index = OccupancyIndex::default();
+    let mut conflicts = Vec::new();
+    for (agent, path) in agents.iter().zip(paths) {
+        conflicts.extend(index.conflicts(agent, path, &order));
+        index.insert(agent, path.clone());
+    }
+    (index, conflicts)
+}
+
+fn agent(id: &str, path: &Path) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start: path[0],
+        goal: *path.last().unwrap(),
+    }
+}
+
+#[test]
+fn test_later_arrival_is_first_agent() {
+    let parked = vec![(1, 0)];
+    let arriving = vec![(0, 0), (1, 0), (2, 0)];
+    let agents = vec![agent("parked", &parked), agent("arriving", &arriving)];
+    let (_, conflicts) = index_conflicts(&agents, &[parked, arriving]);
+    assert_eq!(
+        conflicts,
+        vec![Conflict::Vertex(VertexConflict {
+            agent1: &agents[1],
+            agent2: &agents[0],
+            time: 1,
+            location: (1, 0),
+        })]
+    );
+}
+
+#[test]
+fn test_conflicts_with_parked_agent_after_path_ends() {
+    let short = vec![(0, 0), (1, 0)];
+    let long = vec![(2, 1), (2, 0), (2, 0), (1, 0), (0, 0)];
+    let agents = vec![agent("short", &short), agent("long", &long)];
+    let (_, conflicts) = index_conflicts(&agents, &[short, long]);
+    assert_eq!(
+        conflicts.iter().map(|c| unordered(c)).collect::<Vec<_>>(),
+        vec![(
+            vec!["long".to_string(), "short".to_string()],
+            3,
+            vec![(1, 0)]
+        )]
+    );
+}
+
+fn walk() -> impl Strategy<Value = Path> {
+    let moves = [(0, 0), (0, 1), (1, 0), (0, -1), (-1, 0)];
+    ((0..3i32, 0..3i32), vec(0..moves.len(), 0..6)).prop_map(move |(start, steps)| {
+        let mut path = vec![start];
+        for step in steps {
+            let (x, y) = *path.last().unwrap();
+            let (dx, dy) = moves[step];
+            path.push(((x + dx).clamp(0, 2), (y + dy).clamp(0, 2)));
+        }
+        path
+    })
+}
+
+proptest! {
+    #[test]
+    fn test_matches_scan_after_replanning(
+        paths in vec(walk(), 2..5),
+        replanned in any::<prop::sample::Index>(),
+        new_path in walk(),
+    ) {
+        let mut goals = paths.iter().map(|path| *path.last().unwrap()).collect::<Vec<_>>();
+        goals.sort();
+        goals.dedup();
+        prop_assume!(goals.len() == paths.len());
+        let agents = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| agent(&i.to_string(), path))
+            .collect::<Vec<_>>();
+        let (mut index, mut conflicts) = index_conflicts(&agents, &paths);
+        prop_assert_eq!(
+            conflicts.iter().map(|c| unordered(c)).collect::<HashSet<_>>(),
+            scan(&agents, &paths)
+        );
+        prop_assert_eq!(conflicts.len(), scan(&agents, &paths).len());
+
+        let replanned = replanned.index(agents.len());
+        let mut new_paths = paths.clone();
+        new_paths[replanned] = new_path;
+        let goal = *new_paths[replanned].last().unwrap();
+        prop_assume!(new_paths
+            .iter()
+            .enumerate()
+            .all(|(i, path)| i == replanned || *path.last().unwrap() != goal));
+        let agent = &agents[replanned];
+        index.remove(agent);
+        conflicts.retain(|c| {
+            let (agent1, agent2) = c.agents();
+            agent1 != agent && agent2 != agent
+        });
+        let order = agents.iter().collect::<Vec<_>>();
+        conflicts.extend(index.conflicts(agent, &new_paths[replanned], &order));
+        prop_assert_eq!(
+            conflicts.iter().map(|c| unordered(c)).collect::<HashSet<_>>(),
+            scan(&agents, &new_paths)
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Collections that a conflict tree node shares with its parent, so that
+//! a node only owns what it changed: its new constraint and the paths it
+//! replanned.
+
+use std::{collections::HashMap, fmt, hash::Hash, ops::Index, sync::Arc};
+
+use super::{Agent, Constraint, Path};
+
+struct ConstraintLink<'a> {
+    constraint: Constraint<'a>,
+    parent: Option<Arc<ConstraintLink<'a>>>,
+}
+
+/// A persistent list of constraints, newest first. Pushing a constraint
+/// shares all the older ones with the list it was pushed to.
+#[derive(Clone, Default)]
+pub struct ConstraintList<'a> {
+    head: Option<Arc<ConstraintLink<'a>>>,
+    len: usize,
+}
+
+impl<'a> ConstraintList<'a> {
+    pub(crate) fn push(&mut self, constraint: Constraint<'a>) {
+        self.head = Some(Arc::new(ConstraintLink {
+            constraint,
+            parent: self.head.take(),
+        }));
+        self.len += 1;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The most recently added constraint.
+    pub(crate) fn last(&self) -> Option<&Constraint<'a>> {
+        self.head.as_ref().map(|link| &link.constraint)
+    }
+
+    pub(crate) fn contains(&self, constraint: &Constraint<'a>) -> bool {
+        self.iter().any(|c| c == constraint)
+    }
+
+    /// The constraints, newest first.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Constraint<'a>> {
+        std::iter::successors(self.head.as_deref(), |link| link.parent.as_deref())
+            .map(|link| &link.constraint)
+    }
+}
+
+impl<'a> From<Vec<Box<Constraint<'a>>>> for ConstraintList<'a> {
+    fn from(constraints: Vec<Box<Constraint<'a>>>) -> Self {
+        let mut list = ConstraintList::default();
+        for constraint in constraints {
+            list.push(*constraint);
+        }
+        list
+    }
+}
+
+/// Indexed in the order the constraints were added.
+impl<'a> Index<usize> for ConstraintList<'a> {
+    type Output = Constraint<'a>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(index < self.len, "constraint index out of bounds");
+        self.iter()
+            .nth(self.len - 1 - index)
+            .expect("the list has len constraints")
+    }
+}
+
+impl PartialEq for ConstraintList<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for ConstraintList<'_> {}
+
+impl Hash for ConstraintList<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.iter().for_each(|constraint| constraint.hash(state));
+    }
+}
+
+impl fmt::Debug for ConstraintList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Drop for ConstraintList<'_> {
+    /// Unlinks iteratively, as dropping a long list recursively could overflow the stack.
+    fn drop(&mut self) {
+        let mut head = self.head.take();
+        while let Some(link) = head {
+            match Arc::try_unwrap(link) {
+                Ok(mut link) => head = link.parent.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+/// Paths are stored in chunks of this many agents, and a node that
+/// replans an agent copies only that agent's chunk.
+const CHUNK_SIZE: usize = 16;
+
+type Chunk = Arc<Vec<Option<Arc<Path>>>>;
+
+/// The position of every agent of a conflict tree, shared by all its nodes.
+#[derive(Debug)]
+struct AgentIndex<'a> {
+    agents: Vec<&'a Agent>,
+    positions: HashMap<&'a Agent, usize>,
+}
+
+/// A persistent map from the agents of a conflict tree to their paths.
+/// Clones share the paths and the chunks they did not change.
+#[derive(Clone)]
+pub struct PathMap<'a> {
+    index: Arc<AgentIndex<'a>>,
+    chunks: Vec<Chunk>,
+    len: usize,
+}
+
+impl<'a> PathMap<'a> {
+    pub(crate) fn new(agents: &[&'a Agent], paths: HashMap<&'a Agent, Path>) -> Self {
+        let index = AgentIndex {
+            agents: agents.to_vec(),
+            positions: agents
+                .iter()
+                .enumerate()
+                .map(|(position, agent)| (*agent, position))
+                .collect(),
+        };
+        let chunks = agents
+            .chunks(CHUNK_SIZE)
+            .map(|chunk| Arc::new(vec![None; chunk.len()]))
+            .collect();
+        let mut map = PathMap {
+            index: Arc::new(index),
+            chunks,
+            len: 0,
+        };
+        map.extend(paths);
+        map
+    }
+
+    fn position(&self, agent: &Agent) -> Option<usize> {
+        self.index.positions.get(agent).copied()
+    }
+
+    fn slot(&self, position: usize) -> &Option<Arc<Path>> {
+        &self.chunks[position / CHUNK_SIZE][position % CHUNK_SIZE]
+    }
+
+    /// Copies the chunk holding `position` unless no other map shares it.
+    fn slot_mut(&mut self, position: usize) -> &mut Option<Arc<Path>> {
+        &mut Arc::make_mut(&mut self.chunks[position / CHUNK_SIZE])[position % CHUNK_SIZE]
+    }
+
+    pub(crate) fn get(&self, agent: &Agent) -> Option<&Path> {
+        self.position(agent)
+            .and_then(|position| self.slot(position).as_deref())
+    }
+
+    pub(crate) fn contains_key(&self, agent: &Agent) -> bool {
+        self.get(agent).is_some()
+    }
+
+    /// Panics if `agent` is not an agent of the conflict tree.
+    pub(crate) fn insert(&mut self, agent: &'a Agent, path: Path) {
+        let position = self
+            .position(agent)
+            .unwrap_or_else(|| panic!("agent {} is not in the conflict tree", agent.id));
+        let slot = self.slot_mut(position);
+        let replaced = slot.replace(Arc::new(path));
+        if replaced.is_none() {
+            self.len += 1;
+        }
+    }
+
+    pub(crate) fn remove(&mut self, agent: &Agent) {
+        let Some(position) = self.position(agent) else {
+            return;
+        };
+        if self.slot(position).is_some() {
+            *self.slot_mut(position) = None;
+            self.len -= 1;
+        }
+    }
+
+    /// The number of agents with a path.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The agents with a path and their paths, in the order of the agents.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&'a Agent, &Path)> {
+        self.index
+            .agents
+            .iter()
+            .enumerate()
+            .filter_map(|(position, agent)| {
+                self.slot(position).as_deref().map(|path| (*agent, path))
+            })
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Path> {
+        self.iter().map(|(_, path)| path)
+    }
+}
+
+impl<'a> Extend<(&'a Agent, Path)> for PathMap<'a> {
+    fn extend<T: IntoIterator<Item = (&'a Agent, Path)>>(&mut self, paths: T) {
+        for (agent, path) in paths {
+            self.insert(agent, path);
+        }
+    }
+}
+
+impl Index<&Agent> for PathMap<'_> {
+    type Output = Path;
+
+    fn index(&self, agent: &Agent) -> &Self::Output {
+        self.get(agent)
+            .unwrap_or_else(|| panic!("agent {} has no path", agent.id))
+    }
+}
+
+impl Index<&&Agent> for PathMap<'_> {
+    type Output = Path;
+
+    fn index(&self, agent: &&Agent) -> &Self::Output {
+        &self[*agent]
+    }
+}
+
+impl PartialEq for PathMap<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self
+                .iter()
+                .all(|(agent, path)| other.get(agent) == Some(path))
+    }
+}
+
+impl fmt::Debug for PathMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests;
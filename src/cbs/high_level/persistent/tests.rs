@@ -0,0 +1,120 @@
+use super::*;
+use rstest::rstest;
+
+fn agents(count: usize) -> Vec<Agent> {
+    (0..count)
+        .map(|i| Agent {
+            id: i.to_string(),
+            start: (i as i32, 0),
+            goal: (i as i32, 1),
+        })
+        .collect()
+}
+
+fn constraint(agent: &Agent, time: i32) -> Constraint<'_> {
+    Constraint {
+        agent,
+        time,
+        location: agent.start,
+        prev_location: None,
+    }
+}
+
+#[test]
+fn test_pushing_to_a_clone_keeps_the_original() {
+    let agents = agents(1);
+    let mut parent = ConstraintList::default();
+    parent.push(constraint(&agents[0], 1));
+    let mut child = parent.clone();
+    child.push(constraint(&agents[0], 2));
+
+    assert_eq!(parent.len(), 1);
+    assert_eq!(child.len(), 2);
+    assert!(!parent.contains(&constraint(&agents[0], 2)));
+    assert!(child.contains(&constraint(&agents[0], 1)));
+    assert_eq!(child.last(), Some(&constraint(&agents[0], 2)));
+}
+
+#[test]
+fn test_constraints_are_indexed_in_insertion_order() {
+    let agents = agents(1);
+    let constraints = (0..5)
+        .map(|time| Box::new(constraint(&agents[0], time)))
+        .collect::<Vec<_>>();
+    let list = ConstraintList::from(constraints.clone());
+
+    for (i, constraint) in constraints.iter().enumerate() {
+        assert_eq!(list[i], **constraint);
+    }
+    assert_eq!(list, ConstraintList::from(constraints));
+}
+
+#[test]
+fn test_dropping_a_long_list() {
+    let agents = agents(1);
+    let mut list = ConstraintList::default();
+    for time in 0..200_000 {
+        list.push(constraint(&agents[0], time));
+    }
+    let shared = list.clone();
+    drop(list);
+    assert_eq!(shared.len(), 200_000);
+}
+
+#[rstest]
+#[case(2)]
+#[case(CHUNK_SIZE)]
+#[case(3 * CHUNK_SIZE + 1)]
+fn test_inserting_into_a_clone_keeps_the_original(#[case] count: usize) {
+    let agents = agents(count);
+    let refs = agents.iter().collect::<Vec<_>>();
+    let paths = agents
+        .iter()
+        .map(|agent| (agent, vec![agent.start, agent.goal]))
+        .collect::<HashMap<_, _>>();
+    let parent = PathMap::new(&refs, paths);
+    let mut child = parent.clone();
+    let last = refs[count - 1];
+    child.insert(last, vec![last.start, last.start, last.goal]);
+    child.remove(refs[0]);
+
+    assert_eq!(parent.len(), count);
+    assert_eq!(parent[last], vec![last.start, last.goal]);
+    assert!(parent.contains_key(refs[0]));
+    assert_eq!(child.len(), count - 1);
+    assert_eq!(child.get(last).map(|path| path.len()), Some(3));
+    assert!(!child.contains_key(refs[0]));
+    assert_ne!(parent, child);
+}
+
+#[test]
+fn test_paths_iterate_in_agent_order() {
+    let agents = agents(20);
+    let refs = agents.iter().rev().collect::<Vec<_>>();
+    let paths = agents
+        .iter()
+        .step_by(2)
+        .map(|agent| (agent, vec![agent.start]))
+        .collect::<HashMap<_, _>>();
+    let map = PathMap::new(&refs, paths);
+
+    let order = map
+        .iter()
+        .map(|(agent, _)| agent.id.clone())
+        .collect::<Vec<_>>();
+    let expected = refs
+        .iter()
+        .filter(|agent| map.contains_key(agent))
+        .map(|agent| agent.id.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(order.len(), 10);
+    assert_eq!(order, expected);
+}
+
+#[test]
+#[should_panic]
+fn test_inserting_an_unknown_agent() {
+    let agents = agents(2);
+    let mut map = PathMap::new(&[&agents[0]], HashMap::new());
+    map.insert(&agents[1], vec![agents[1].start]);
+}
@@ -50,7 +50,7 @@ fn test_high_level() {
         None,
         true,
         &low_level_solver,
-        Rc::new(heuristic::ZeroHeuristic::new()),
+        Arc::new(heuristic::ZeroHeuristic::new()),
     );
     assert_eq!(ctn.conflicts.len(), 13);
     match ctn.expand() {
@@ -65,3 +65,52 @@ fn test_high_level() {
     }
     drop(ctn);
 }
+
+#[test]
+fn test_expand_prunes_infeasible_children() {
+    let agents = [
+        Agent {
+            id: "a".to_string(),
+            start: (0, 0),
+            goal: (0, 0),
+        },
+        Agent {
+            id: "b".to_string(),
+            start: (1, 0),
+            goal: (1, 0),
+        },
+    ];
+    let precomputed_paths = HashMap::from([
+        (&agents[0], vec![(0, 0), (0, 0)]),
+        (&agents[1], vec![(1, 0), (0, 0), (1, 0)]),
+    ]);
+    let grid = Grid::new(
+        2,
+        1,
+        Grid::to_conditional_obstacles(vec![low_level::LocationTime::new((1, 0), 1)]),
+        (0, 0),
+    );
+    let low_level_solver = AStarLowLevelSolver::new();
+    let ctn = ConflictTreeNode::new(
+        agents.iter().collect(),
+        vec![],
+        precomputed_paths,
+        &grid,
+        None,
+        None,
+        None,
+        false,
+        &low_level_solver,
+        Arc::new(heuristic::ZeroHeuristic::new()),
+    );
+    assert!(ctn.is_feasible());
+    assert_eq!(ctn.conflicts.len(), 1);
+    let expanded = ctn.expand().expect("should expand");
+    assert!(expanded.is_empty());
+    assert_eq!(
+        ctn.statistics
+            .infeasible_pruned
+            .load(std::sync::atomic::Ordering::Relaxed),
+        2
+    );
+}
@@ -0,0 +1,190 @@
+//! Sharon et al.'s Increasing Cost Tree Search: a breadth-first search over
+//! vectors of per-agent path costs, where a vector is a solution when the
+//! MDDs of its costs hold a joint path without conflicts.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error,
+};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    low_level::{heuristic::DistanceMap, Grid},
+    mdd::{joint_mdd_paths, mdd, LevelSets, MDDError},
+    search::SearchError,
+    CBSError, CBSInstance,
+};
+
+pub struct IncreasingCostTreeSearch {
+    instance: CBSInstance,
+    pruning_group_size: usize,
+    cancellation: Option<CancellationToken>,
+    /// Level sets of the MDD of every (agent, cost) pair built so far.
+    mdds: HashMap<(usize, i32), LevelSets>,
+    /// Whether the agents at the given costs have a joint path, by sorted (agent, cost) pairs.
+    group_feasible: HashMap<Vec<(usize, i32)>, bool>,
+    solved: bool,
+    pub ict_nodes_generated: usize,
+    pub ict_nodes_expanded: usize,
+    pub ict_nodes_pruned: usize,
+}
+
+impl IncreasingCostTreeSearch {
+    pub fn new(instance: CBSInstance) -> Self {
+        Self {
+            instance,
+            pruning_group_size: 2,
+            cancellation: None,
+            mdds: HashMap::new(),
+            group_feasible: HashMap::new(),
+            solved: false,
+            ict_nodes_generated: 0,
+            ict_nodes_expanded: 0,
+            ict_nodes_pruned: 0,
+        }
+    }
+
+    /// Before searching all agents' MDDs together, rejects cost vectors where
+    /// some `group_size` agents have no joint path. 2 prunes pairwise, values
+    /// below 2 disable pruning.
+    pub fn with_pruning(mut self, group_size: usize) -> Self {
+        self.pruning_group_size = group_size;
+        self
+    }
+
+    /// Makes [`Self::solve`] fail with [`SearchError::Cancelled`] once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Gives up with [`SearchError::CostLimitReached`] once the sum of costs
+    /// exceeds that of the shortest paths by more than the number of free
+    /// cells. Without this bound, an instance that is valid but unsolvable
+    /// would be searched forever; solutions needing larger delays are far
+    /// beyond what the search can reach anyway.
+    pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
+        if self.solved {
+            return Err(Box::new(CBSError::AlreadySolved));
+        }
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        self.instance.require_unit_costs("ICTS")?;
+        self.solved = true;
+        let root = self
+            .instance
+            .agents
+            .iter()
+            .map(|agent| DistanceMap::new(&self.instance.map, agent.goal).distance(agent.start))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(SearchError::NotFound)?;
+        let max_cost = root.iter().sum::<i32>() + free_cells(&self.instance.map);
+        let mut open = VecDeque::from([root.clone()]);
+        let mut seen = HashSet::from([root]);
+        self.ict_nodes_generated = 1;
+        while let Some(costs) = open.pop_front() {
+            if self
+                .cancellation
+                .as_ref()
+                .is_some_and(|cancellation| cancellation.is_cancelled())
+            {
+                return Err(Box::new(SearchError::Cancelled));
+            }
+            self.ict_nodes_expanded += 1;
+            if let Some(paths) = self.goal_test(&costs)? {
+                return Ok(self.instance.agents.iter().zip(paths).collect());
+            }
+            if costs.iter().sum::<i32>() >= max_cost {
+                continue;
+            }
+            for i in 0..costs.len() {
+                let mut child = costs.clone();
+                child[i] += 1;
+                if seen.insert(child.clone()) {
+                    self.ict_nodes_generated += 1;
+                    open.push_back(child);
+                }
+            }
+        }
+        // every cost vector below the bound has children, so the search only
+        // runs out of them at the bound
+        Err(Box::new(SearchError::CostLimitReached(max_cost)))
+    }
+
+    /// Returns a path for every agent with exactly the given costs, if their
+    /// MDDs hold a joint path without conflicts.
+    fn goal_test(&mut self, costs: &[i32]) -> Result<Option<Vec<Path>>, Box<dyn Error>> {
+        for (agent, &cost) in costs.iter().enumerate() {
+            self.mdd(agent, cost)?;
+        }
+        if (2..costs.len()).contains(&self.pruning_group_size) {
+            for group in combinations(costs.len(), self.pruning_group_size) {
+                let key = group.iter().map(|&i| (i, costs[i])).collect::<Vec<_>>();
+                if !self.group_feasible(key) {
+                    self.ict_nodes_pruned += 1;
+                    return Ok(None);
+                }
+            }
+        }
+        let mdds = (0..costs.len())
+            .map(|i| &self.mdds[&(i, costs[i])])
+            .collect::<Vec<_>>();
+        let Some(mut paths) = joint_mdd_paths(&mdds, &self.instance.map) else {
+            return Ok(None);
+        };
+        for path in &mut paths {
+            while path.len() > 1 && path[path.len() - 2] == path[path.len() - 1] {
+                path.pop();
+            }
+        }
+        Ok(Some(paths))
+    }
+
+    fn group_feasible(&mut self, key: Vec<(usize, i32)>) -> bool {
+        if let Some(&feasible) = self.group_feasible.get(&key) {
+            return feasible;
+        }
+        let mdds = key.iter().map(|k| &self.mdds[k]).collect::<Vec<_>>();
+        let feasible = joint_mdd_paths(&mdds, &self.instance.map).is_some();
+        self.group_feasible.insert(key, feasible);
+        feasible
+    }
+
+    fn mdd(&mut self, agent: usize, cost: i32) -> Result<(), SearchError> {
+        if self.mdds.contains_key(&(agent, cost)) {
+            return Ok(());
+        }
+        let mdd = mdd(&self.instance.agents[agent], &self.instance.map, cost)
+            .map_err(|MDDError::GoalUnreachable| SearchError::NotFound)?;
+        self.mdds.insert((agent, cost), mdd);
+        Ok(())
+    }
+}
+
+fn free_cells(map: &Grid) -> i32 {
+    (0..map.width)
+        .flat_map(|x| (0..map.height).map(move |y| (x, y)))
+        .filter(|cell| map.is_valid_location(cell, cell))
+        .count() as i32
+}
+
+/// All `k`-element subsets of `0..n`, in lexicographic order.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut subsets = Vec::new();
+    let mut subset = (0..k).collect::<Vec<_>>();
+    loop {
+        subsets.push(subset.clone());
+        let Some(i) = (0..k).rev().find(|&i| subset[i] < n - k + i) else {
+            return subsets;
+        };
+        subset[i] += 1;
+        for j in i + 1..k {
+            subset[j] = subset[j - 1] + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
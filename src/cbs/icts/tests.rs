@@ -0,0 +1,149 @@
+use proptest::{prelude::*, sample::subsequence};
+use rstest::rstest;
+
+use super::*;
+use crate::cbs::operator_decomposition::OperatorDecomposition;
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+fn empty_grid(width: i32, height: i32) -> Grid {
+    Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(Vec::new()),
+        (0, 0),
+    )
+}
+
+fn sum_of_costs(paths: &HashMap<&Agent, Path>) -> usize {
+    paths.values().map(|path| path.len() - 1).sum()
+}
+
+#[rstest]
+#[case::independent(vec![agent("a", (0, 0), (0, 3)), agent("b", (3, 3), (3, 0))], 6)]
+#[case::crossing(vec![agent("a", (0, 1), (2, 1)), agent("b", (1, 0), (1, 2))], 5)]
+#[case::swapping(
+    vec![agent("a", (0, 0), (3, 0)), agent("b", (3, 0), (0, 0))],
+    8
+)]
+#[case::already_at_goal(vec![agent("a", (1, 1), (1, 1)), agent("b", (0, 1), (2, 1))], 4)]
+fn test_optimal_cost(#[case] agents: Vec<Agent>, #[case] expected: usize) {
+    let mut icts = IncreasingCostTreeSearch::new(CBSInstance::new(empty_grid(4, 4), agents));
+    let paths = icts.solve().unwrap();
+    assert_eq!(sum_of_costs(&paths), expected);
+    assert!(icts.ict_nodes_expanded <= icts.ict_nodes_generated);
+}
+
+#[test]
+fn test_gives_up_on_unsolvable_instance() {
+    // swapping in a corridor is valid, but has no solution at any cost
+    let agents = vec![agent("a", (0, 0), (4, 0)), agent("b", (4, 0), (0, 0))];
+    let mut icts = IncreasingCostTreeSearch::new(CBSInstance::new(empty_grid(5, 1), agents));
+    let err = icts.solve().expect_err("the agents cannot pass each other");
+    assert!(matches!(
+        err.downcast_ref::<SearchError>(),
+        Some(SearchError::CostLimitReached(13))
+    ));
+    // the 1 + 2 + ... + 6 cost vectors delayed by up to the 5 free cells
+    assert_eq!(icts.ict_nodes_expanded, 21);
+}
+
+#[test]
+fn test_solves_once() {
+    let mut icts = IncreasingCostTreeSearch::new(CBSInstance::new(
+        empty_grid(4, 4),
+        vec![agent("a", (0, 0), (3, 3))],
+    ));
+    assert!(icts.solve().is_ok());
+    let error = icts.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<CBSError>(),
+        Some(CBSError::AlreadySolved)
+    ));
+}
+
+#[test]
+fn test_pairwise_pruning_skips_joint_search() {
+    let agents = vec![
+        agent("a", (0, 1), (2, 1)),
+        agent("b", (1, 0), (1, 2)),
+        agent("far", (3, 3), (3, 3)),
+    ];
+    let mut pruned =
+        IncreasingCostTreeSearch::new(CBSInstance::new(empty_grid(4, 4), agents.clone()));
+    let mut unpruned =
+        IncreasingCostTreeSearch::new(CBSInstance::new(empty_grid(4, 4), agents)).with_pruning(0);
+    assert_eq!(
+        sum_of_costs(&pruned.solve().unwrap()),
+        sum_of_costs(&unpruned.solve().unwrap())
+    );
+    assert!(pruned.ict_nodes_pruned > 0);
+    assert_eq!(unpruned.ict_nodes_pruned, 0);
+}
+
+#[test]
+fn test_cancelled() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let mut icts = IncreasingCostTreeSearch::new(CBSInstance::new(
+        empty_grid(4, 4),
+        vec![agent("a", (0, 0), (3, 3))],
+    ))
+    .with_cancellation(cancellation);
+    let error = icts.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::Cancelled)
+    ));
+}
+
+#[rstest]
+#[case(3, 2, vec![vec![0, 1], vec![0, 2], vec![1, 2]])]
+#[case(4, 3, vec![vec![0, 1, 2], vec![0, 1, 3], vec![0, 2, 3], vec![1, 2, 3]])]
+#[case(2, 2, vec![vec![0, 1]])]
+fn test_combinations(#[case] n: usize, #[case] k: usize, #[case] expected: Vec<Vec<usize>>) {
+    assert_eq!(combinations(n, k), expected);
+}
+
+fn open_grid_instance() -> impl Strategy<Value = Vec<Agent>> {
+    let cells = (0..4)
+        .flat_map(|x| (0..4).map(move |y| (x, y)))
+        .collect::<Vec<_>>();
+    (2..5usize)
+        .prop_flat_map(move |n| {
+            (
+                subsequence(cells.clone(), n).prop_shuffle(),
+                subsequence(cells.clone(), n).prop_shuffle(),
+            )
+        })
+        .prop_map(|(starts, goals)| {
+            starts
+                .into_iter()
+                .zip(goals)
+                .enumerate()
+                .map(|(i, (start, goal))| agent(&i.to_string(), start, goal))
+                .collect()
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+    #[test]
+    fn test_same_cost_as_joint_search(agents in open_grid_instance(), pruning in 0..4usize) {
+        let mut oracle = OperatorDecomposition::new(CBSInstance::new(empty_grid(4, 4), agents.clone()));
+        let expected = sum_of_costs(&oracle.solve().unwrap());
+        let mut icts = IncreasingCostTreeSearch::new(CBSInstance::new(empty_grid(4, 4), agents)).with_pruning(pruning);
+        let paths = icts.solve().unwrap();
+        prop_assert_eq!(sum_of_costs(&paths), expected);
+        for (agent, path) in &paths {
+            prop_assert_eq!(path.first(), Some(&agent.start));
+            prop_assert_eq!(path.last(), Some(&agent.goal));
+        }
+    }
+}
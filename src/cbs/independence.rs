@@ -0,0 +1,170 @@
+//! Standley's Independence Detection: agents are planned alone, and groups
+//! whose paths conflict are merged and replanned together with CBS until
+//! no two groups conflict.
+
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    low_level::AStarLowLevelSolver,
+    CBSError, CBSInstance, CBSOptimisationConfig, CBS,
+};
+
+pub struct IndependenceDetection {
+    instance: CBSInstance,
+    optimisation_config: Option<CBSOptimisationConfig>,
+    low_level_solver: Arc<AStarLowLevelSolver>,
+    cancellation: Option<CancellationToken>,
+    /// Agent indices of every group, in instance order.
+    groups: Vec<Vec<usize>>,
+    solved: bool,
+    pub merges: usize,
+    pub high_level_generated: usize,
+    pub high_level_pruned: usize,
+}
+
+impl IndependenceDetection {
+    pub fn new(instance: CBSInstance, optimisation_config: Option<CBSOptimisationConfig>) -> Self {
+        let groups = (0..instance.agents.len()).map(|i| vec![i]).collect();
+        Self {
+            instance,
+            optimisation_config,
+            low_level_solver: Arc::new(AStarLowLevelSolver::new()),
+            cancellation: None,
+            groups,
+            solved: false,
+            merges: 0,
+            high_level_generated: 0,
+            high_level_pruned: 0,
+        }
+    }
+
+    /// Passes `cancellation` on to the CBS search of every group.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Sizes of the independent groups, largest first. Every agent is its own group before solving.
+    pub fn group_sizes(&self) -> Vec<usize> {
+        let mut sizes = self.groups.iter().map(|g| g.len()).collect::<Vec<_>>();
+        sizes.sort_by(|a, b| b.cmp(a));
+        sizes
+    }
+
+    /// The agent ids of every group.
+    pub fn groups(&self) -> Vec<Vec<&str>> {
+        self.groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|i| self.instance.agents[*i].id.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
+        if self.solved {
+            return Err(Box::new(CBSError::AlreadySolved));
+        }
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        // the groups are merged in place, so a second search would start from them
+        self.solved = true;
+        let mut paths = vec![Path::new(); self.instance.agents.len()];
+        for group in self.groups.clone() {
+            self.solve_group(&group, &mut paths)?;
+        }
+        while let Some((group1, group2)) = self.find_conflicting_groups(&paths) {
+            let mut merged = self.groups[group1].clone();
+            merged.extend(self.groups[group2].iter());
+            merged.sort();
+            log::debug!(
+                "Merging groups of {} and {} agents",
+                self.groups[group1].len(),
+                self.groups[group2].len()
+            );
+            self.groups.remove(group1.max(group2));
+            self.groups[group1.min(group2)] = merged.clone();
+            self.merges += 1;
+            self.solve_group(&merged, &mut paths)?;
+        }
+        Ok(self.instance.agents.iter().zip(paths).collect())
+    }
+
+    fn solve_group(&mut self, group: &[usize], paths: &mut [Path]) -> Result<(), Box<dyn Error>> {
+        let agents = group
+            .iter()
+            .map(|i| self.instance.agents[*i].clone())
+            .collect::<Vec<_>>();
+        let mut cbs = CBS::new(
+            CBSInstance::new(self.instance.map.clone(), agents),
+            self.optimisation_config.clone(),
+        )
+        .with_low_level_solver(Arc::clone(&self.low_level_solver));
+        if let Some(cancellation) = &self.cancellation {
+            cbs = cbs.with_cancellation(cancellation.clone());
+        }
+        let group_paths = cbs.solve().map(|solution| {
+            group
+                .iter()
+                .map(|i| solution[&self.instance.agents[*i]].clone())
+                .collect::<Vec<_>>()
+        });
+        self.high_level_generated += cbs.high_level_generated;
+        self.high_level_pruned += cbs.high_level_pruned;
+        for (i, path) in group.iter().zip(group_paths?) {
+            paths[*i] = path;
+        }
+        Ok(())
+    }
+
+    /// Returns the indices of the groups of the earliest conflict between
+    /// agents of different groups. Agents wait at their goal after arriving.
+    fn find_conflicting_groups(&self, paths: &[Path]) -> Option<(usize, usize)> {
+        let mut group_of = vec![0; paths.len()];
+        for (g, group) in self.groups.iter().enumerate() {
+            for i in group {
+                group_of[*i] = g;
+            }
+        }
+        let location = |i: usize, t: usize| paths[i][t.min(paths[i].len() - 1)];
+        let makespan = paths.iter().map(|p| p.len()).max().unwrap_or(0);
+        for t in 0..makespan {
+            let mut occupants = HashMap::<(i32, i32), usize>::with_capacity(paths.len());
+            for i in 0..paths.len() {
+                if let Some(j) = occupants.insert(location(i, t), i) {
+                    if group_of[i] != group_of[j] {
+                        return Some((group_of[j], group_of[i]));
+                    }
+                }
+            }
+            if t == 0 {
+                continue;
+            }
+            let previous = (0..paths.len())
+                .map(|i| (location(i, t - 1), i))
+                .collect::<HashMap<_, _>>();
+            for i in 0..paths.len() {
+                let (from, to) = (location(i, t - 1), location(i, t));
+                if from == to {
+                    continue;
+                }
+                let Some(&j) = previous.get(&to) else {
+                    continue;
+                };
+                if location(j, t) == from && group_of[i] != group_of[j] {
+                    return Some((group_of[j], group_of[i]));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,113 @@
+use proptest::{prelude::*, sample::subsequence};
+
+use super::*;
+use crate::cbs::low_level::Grid;
+use crate::cbs::operator_decomposition::OperatorDecomposition;
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+fn empty_grid(width: i32, height: i32) -> Grid {
+    Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(Vec::new()),
+        (0, 0),
+    )
+}
+
+fn sum_of_costs(paths: &HashMap<&Agent, Path>) -> usize {
+    paths.values().map(|path| path.len() - 1).sum()
+}
+
+#[test]
+fn test_independent_agents() {
+    let instance = CBSInstance::new(
+        empty_grid(8, 8),
+        vec![agent("a", (0, 0), (0, 3)), agent("b", (7, 7), (7, 4))],
+    );
+    let mut solver = IndependenceDetection::new(instance, None);
+    let paths = solver.solve().unwrap();
+    assert_eq!(sum_of_costs(&paths), 6);
+    assert_eq!(solver.merges, 0);
+    assert_eq!(solver.group_sizes(), vec![1, 1]);
+}
+
+#[test]
+fn test_merges_conflicting_agents() {
+    let instance = CBSInstance::new(
+        empty_grid(8, 8),
+        vec![
+            agent("a", (0, 1), (2, 1)),
+            agent("far", (7, 7), (7, 4)),
+            agent("b", (1, 0), (1, 2)),
+        ],
+    );
+    let mut solver = IndependenceDetection::new(instance, None);
+    let paths = solver.solve().unwrap();
+    assert_eq!(sum_of_costs(&paths), 8);
+    assert_eq!(solver.merges, 1);
+    assert_eq!(solver.group_sizes(), vec![2, 1]);
+    assert_eq!(solver.groups(), vec![vec!["a", "b"], vec!["far"]]);
+}
+
+#[test]
+fn test_solves_once() {
+    let instance = CBSInstance::new(
+        empty_grid(8, 8),
+        vec![agent("a", (0, 1), (2, 1)), agent("b", (1, 0), (1, 2))],
+    );
+    let mut solver = IndependenceDetection::new(instance, None);
+    assert!(solver.solve().is_ok());
+    let err = solver.solve().expect_err("a second solve should be rejected");
+    assert_eq!(err.to_string(), "CBS instance already solved");
+    assert_eq!(solver.merges, 1);
+}
+
+#[test]
+fn test_invalid_instance() {
+    let instance = CBSInstance::new(
+        empty_grid(4, 4),
+        vec![agent("a", (0, 0), (3, 3)), agent("b", (1, 1), (3, 3))],
+    );
+    assert!(IndependenceDetection::new(instance, None).solve().is_err());
+}
+
+fn open_grid_instance() -> impl Strategy<Value = Vec<Agent>> {
+    let cells = (0..5)
+        .flat_map(|x| (0..5).map(move |y| (x, y)))
+        .collect::<Vec<_>>();
+    (2..5usize)
+        .prop_flat_map(move |n| {
+            (
+                subsequence(cells.clone(), n).prop_shuffle(),
+                subsequence(cells.clone(), n).prop_shuffle(),
+            )
+        })
+        .prop_map(|(starts, goals)| {
+            starts
+                .into_iter()
+                .zip(goals)
+                .enumerate()
+                .map(|(i, (start, goal))| agent(&i.to_string(), start, goal))
+                .collect()
+        })
+}
+
+proptest! {
+    #[test]
+    fn test_same_cost_as_joint_search(agents in open_grid_instance()) {
+        let mut oracle = OperatorDecomposition::new(CBSInstance::new(empty_grid(5, 5), agents.clone()));
+        let expected = sum_of_costs(&oracle.solve().unwrap());
+        let mut solver = IndependenceDetection::new(CBSInstance::new(empty_grid(5, 5), agents.clone()), None);
+        let paths = solver.solve().unwrap();
+        prop_assert_eq!(sum_of_costs(&paths), expected);
+        prop_assert_eq!(solver.group_sizes().iter().sum::<usize>(), agents.len());
+        prop_assert_eq!(solver.merges, agents.len() - solver.group_sizes().len());
+    }
+}
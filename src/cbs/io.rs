@@ -1,51 +1,207 @@
 use regex::Regex;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
     fs::{self, File},
     io::Read,
+    ops::RangeInclusive,
+    sync::Arc,
 };
 
+pub mod yaml;
+
 use super::{
     high_level::{Agent, Path},
-    low_level::{Grid, LocationTime},
+    low_level::{
+        obstacles::{CostMap, ObstacleMap},
+        AStarLowLevelSolver, Grid, LocationTime,
+    },
+    terrain::{Terrain, TerrainClass, TerrainTable},
     CBSInstance,
 };
 
-impl TryFrom<String> for Grid {
-    fn try_from(value: String) -> Result<Self, Self::Error> {
-        let map_regex = Regex::new(
-            r"type octile\r?\nheight (\d+)\r?\nwidth (\d+)\r?\nmap\r?\n((?:[.G@OTSW]*\r?\n)*)",
+/// An error in a map file, located by 1-based line and column numbers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl MapParseError {
+    fn new(line: usize, column: usize, message: String) -> Self {
+        Self {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for MapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
         )
-        .unwrap();
-        let map_match = map_regex.captures(&value).ok_or("Invalid map file")?;
-
-        let height = map_match[1]
-            .parse::<i32>()
-            .or(Result::Err("height is not a number"))?;
-        let width = map_match[2]
-            .parse::<i32>()
-            .or(Result::Err("width is not a number"))?;
-        let mut obstacles = Vec::new();
-        for (i, line) in map_match[3].trim().split('\n').enumerate() {
-            let line_vec: Vec<char> = line.trim().chars().collect();
-            for (j, c) in line_vec.iter().enumerate() {
-                if !matches!(c, '.' | 'G' | '@' | 'O' | 'T' | 'S' | 'W') {
-                    return Err(format!("Invalid character: {}", c));
+    }
+}
+
+impl Error for MapParseError {}
+
+/// Parses a Moving AI map, classifying its terrain with `terrain_table`.
+/// Blocked cells become static obstacles; the original terrain is kept on the grid.
+pub fn parse_map(content: &str, terrain_table: &TerrainTable) -> Result<Grid, MapParseError> {
+    let mut lines = content.lines().enumerate().map(|(i, line)| (i + 1, line));
+    let (line_no, line) =
+        lines
+            .next()
+            .ok_or(MapParseError::new(1, 1, "empty map file".to_string()))?;
+    if line.trim() != "type octile" {
+        return Err(MapParseError::new(
+            line_no,
+            1,
+            format!("expected 'type octile', got '{}'", line.trim()),
+        ));
+    }
+    let mut height = None;
+    let mut width = None;
+    let mut last_line_no = line_no;
+    loop {
+        let (line_no, line) = lines.next().ok_or(MapParseError::new(
+            last_line_no + 1,
+            1,
+            "expected 'map' header".to_string(),
+        ))?;
+        last_line_no = line_no;
+        let mut words = line.split_whitespace();
+        let (key, dimension) = match (words.next(), words.next(), words.next()) {
+            (Some("map"), None, None) => break,
+            (Some(key @ ("height" | "width")), Some(value), None) => {
+                let value_column = line.find(value).expect("value is part of the line") + 1;
+                let value =
+                    value
+                        .parse::<i32>()
+                        .ok()
+                        .filter(|v| *v > 0)
+                        .ok_or(MapParseError::new(
+                            line_no,
+                            value_column,
+                            format!("{} is not a positive number: '{}'", key, value),
+                        ))?;
+                (key, value)
+            }
+            _ => {
+                return Err(MapParseError::new(
+                    line_no,
+                    1,
+                    format!("unexpected header line: '{}'", line.trim()),
+                ))
+            }
+        };
+        match key {
+            "height" => height = Some(dimension),
+            _ => width = Some(dimension),
+        }
+    }
+    let height = height.ok_or(MapParseError::new(
+        last_line_no,
+        1,
+        "missing height header".to_string(),
+    ))?;
+    let width = width.ok_or(MapParseError::new(
+        last_line_no,
+        1,
+        "missing width header".to_string(),
+    ))?;
+    let mut cells = Vec::<char>::with_capacity((width * height) as usize);
+    let mut obstacles = Vec::new();
+    let mut enclosed = Vec::new();
+    let mut costs = Vec::new();
+    for y in 0..height {
+        let (line_no, line) = lines.next().ok_or(MapParseError::new(
+            last_line_no + 1,
+            1,
+            format!("expected {} map rows, found {}", height, y),
+        ))?;
+        last_line_no = line_no;
+        let mut row_width = 0;
+        for (x, c) in line.trim_end().chars().enumerate() {
+            if x as i32 >= width {
+                return Err(MapParseError::new(
+                    line_no,
+                    x + 1,
+                    format!("row is longer than the map width {}", width),
+                ));
+            }
+            match terrain_table.classify(c) {
+                Some(TerrainClass::Blocked) => obstacles.push(LocationTime {
+                    location: (x as i32, y),
+                    time: -1,
+                }),
+                Some(TerrainClass::Costly(cost)) => costs.push(((x as i32, y), cost)),
+                Some(TerrainClass::Enclosed(cost)) => {
+                    enclosed.push((x as i32, y));
+                    costs.push(((x as i32, y), cost));
                 }
-                if matches!(c, '@' | 'O' | 'T' | 'S' | 'W') {
-                    obstacles.push(LocationTime {
-                        location: (j as i32, i as i32),
-                        time: -1,
-                    });
+                Some(TerrainClass::Traversable) => {}
+                None => {
+                    return Err(MapParseError::new(
+                        line_no,
+                        x + 1,
+                        format!("unknown terrain: '{}'", c),
+                    ))
                 }
             }
+            cells.push(c);
+            row_width += 1;
         }
-        Ok(Grid::new(
-            width,
-            height,
-            Grid::to_conditional_obstacles(obstacles),
-            (0, 0),
-        ))
+        if row_width < width {
+            return Err(MapParseError::new(
+                line_no,
+                row_width as usize + 1,
+                format!("row is shorter than the map width {}", width),
+            ));
+        }
+    }
+    if let Some((line_no, line)) = lines.find(|(_, line)| !line.trim().is_empty()) {
+        return Err(MapParseError::new(
+            line_no,
+            1,
+            format!("unexpected content after the map: '{}'", line.trim()),
+        ));
+    }
+    let mut grid = Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(obstacles),
+        (0, 0),
+    );
+    grid.enclosed_cells = Arc::new(ObstacleMap::new(width, height, enclosed));
+    grid.costs = Arc::new(CostMap::new(width, height, costs));
+    grid.terrain = Some(Arc::new(Terrain::new(
+        width,
+        height,
+        cells,
+        terrain_table.clone(),
+    )));
+    Ok(grid)
+}
+
+/// Reads a Moving AI map from any source, e.g. stdin.
+pub fn read_map(mut reader: impl Read, terrain_table: &TerrainTable) -> Result<Grid, String> {
+    let mut content = String::new();
+    reader
+        .read_to_string(&mut content)
+        .map_err(|e| e.to_string())?;
+    parse_map(&content, terrain_table).map_err(|e| e.to_string())
+}
+
+impl TryFrom<String> for Grid {
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_map(&value, &TerrainTable::default()).map_err(|e| e.to_string())
     }
 
     type Error = String;
@@ -56,10 +212,9 @@ impl TryInto<String> for Grid {
         let mut map = String::new();
         for i in 0..self.height {
             for j in 0..self.width {
-                if self.obstacles.contains_key(&LocationTime {
-                    location: (j, i),
-                    time: -1,
-                }) {
+                if let Some(terrain) = self.terrain.as_ref().and_then(|t| t.get((j, i))) {
+                    map.push(terrain);
+                } else if self.is_static_obstacle((j, i)) {
                     map.push('@');
                 } else {
                     map.push('.');
@@ -76,27 +231,230 @@ impl TryInto<String> for Grid {
     type Error = String;
 }
 
+/// A row of a Moving AI scenario file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioEntry {
+    pub bucket: usize,
+    pub map_name: String,
+    pub map_width: i32,
+    pub map_height: i32,
+    pub start: (i32, i32),
+    pub goal: (i32, i32),
+    /// The optimal single-agent path length on the 8-connected map.
+    pub optimal_length: f64,
+}
+
+/// Which rows of a scenario file become agents. Filters apply in order:
+/// bucket range, then index range (by row, starting at 0), then the agent limit.
+#[derive(Debug, Clone, Default)]
+pub struct AgentSelection {
+    pub buckets: Option<RangeInclusive<usize>>,
+    pub indices: Option<RangeInclusive<usize>>,
+    pub max_agents: Option<usize>,
+}
+
+impl AgentSelection {
+    fn select(&self, entries: Vec<ScenarioEntry>) -> Vec<(usize, ScenarioEntry)> {
+        let mut selected = entries
+            .into_iter()
+            .enumerate()
+            .filter(|(_, entry)| match &self.buckets {
+                Some(buckets) => buckets.contains(&entry.bucket),
+                None => true,
+            })
+            .filter(|(i, _)| match &self.indices {
+                Some(indices) => indices.contains(i),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        if let Some(max_agents) = self.max_agents {
+            selected.truncate(max_agents);
+        }
+        selected
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub agents: AgentSelection,
+    pub terrain: TerrainTable,
+}
+
+/// An agent whose single-agent optimal cost on the 4-connected grid cannot be
+/// reconciled with the optimal length its scenario file states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimalLengthMismatch {
+    pub agent: String,
+    pub expected: f64,
+    pub found: Option<usize>,
+}
+
+impl fmt::Display for OptimalLengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.found {
+            Some(found) => write!(
+                f,
+                "agent {}: optimal cost {} is inconsistent with the scenario's optimal length {}",
+                self.agent, found, self.expected
+            ),
+            None => write!(
+                f,
+                "agent {}: no path found, but the scenario's optimal length is {}",
+                self.agent, self.expected
+            ),
+        }
+    }
+}
+
 impl CBSInstance {
-    pub fn from_files(
+    /// Loads the first `num_agents` agents with the default options.
+    #[cfg(test)]
+    pub(crate) fn from_files(
         map_file: &str,
         scen_file: &str,
         num_agents: Option<usize>,
+    ) -> Result<Self, String> {
+        Self::from_files_with_options(
+            map_file,
+            scen_file,
+            &LoadOptions {
+                agents: AgentSelection {
+                    max_agents: num_agents,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn from_files_with_options(
+        map_file: &str,
+        scen_file: &str,
+        options: &LoadOptions,
     ) -> Result<Self, String> {
         let map_file_content = fs::read_to_string(map_file).map_err(|e| e.to_string())?;
-        let map = Grid::try_from(map_file_content)?;
-        let mut agents = load_scenario_file(scen_file)?;
-        if let Some(num_agents) = num_agents {
-            agents.truncate(num_agents);
+        let map = parse_map(&map_file_content, &options.terrain)
+            .map_err(|e| format!("{}: {}", map_file, e))?;
+        let entries = load_scenario(scen_file)?;
+        for warning in scenario_map_mismatches(map_file, &map, &entries) {
+            log::warn!("{}: {}", scen_file, warning);
+        }
+        Ok(Self::from_scenario(map, entries, &options.agents))
+    }
+
+    /// Reads a Moving AI map and scenario from any source, e.g. stdin.
+    /// Unlike [`Self::from_files_with_options`], the scenario's map name cannot be checked.
+    pub fn from_readers(
+        map_reader: impl Read,
+        scen_reader: impl Read,
+        options: &LoadOptions,
+    ) -> Result<Self, String> {
+        let map = read_map(map_reader, &options.terrain)?;
+        let entries = read_scenario(scen_reader)?;
+        Ok(Self::from_scenario(map, entries, &options.agents))
+    }
+
+    fn from_scenario(map: Grid, entries: Vec<ScenarioEntry>, selection: &AgentSelection) -> Self {
+        let mut agents = Vec::<Agent>::new();
+        let mut scenario = HashMap::<String, ScenarioEntry>::new();
+        for (i, entry) in selection.select(entries) {
+            agents.push(Agent {
+                id: i.to_string(),
+                start: entry.start,
+                goal: entry.goal,
+            });
+            scenario.insert(i.to_string(), entry);
         }
-        Ok(CBSInstance { map, agents })
+        let mut instance = CBSInstance::new(map, agents);
+        instance.scenario = scenario;
+        instance
+    }
+
+    /// Checks the cost of every agent on its own against the optimal length
+    /// in the scenario file. The file's lengths are for 8-connected moves,
+    /// so a 4-connected cost must lie between the length and sqrt(2) times it.
+    /// The costs are looked up in the distance tables of `solver`, which a
+    /// search on this instance can then reuse.
+    pub fn check_optimal_lengths(
+        &self,
+        solver: &AStarLowLevelSolver,
+    ) -> Vec<OptimalLengthMismatch> {
+        const TOLERANCE: f64 = 1e-6;
+        let mut mismatches = Vec::new();
+        for agent in self.agents.iter() {
+            let Some(entry) = self.scenario.get(&agent.id) else {
+                continue;
+            };
+            let found = solver
+                .distances
+                .get(&self.map, agent.goal)
+                .distance(agent.start)
+                .map(|cost| cost as usize);
+            let consistent = match found {
+                Some(cost) => {
+                    let cost = cost as f64;
+                    cost >= entry.optimal_length - TOLERANCE
+                        && cost <= entry.optimal_length * std::f64::consts::SQRT_2 + TOLERANCE
+                }
+                None => false,
+            };
+            if !consistent {
+                mismatches.push(OptimalLengthMismatch {
+                    agent: agent.id.clone(),
+                    expected: entry.optimal_length,
+                    found,
+                });
+            }
+        }
+        mismatches
     }
 }
 
-fn load_scenario_file(scen_file: &str) -> Result<Vec<Agent>, String> {
+/// Describes the ways in which the scenario's map name and size disagree
+/// with the loaded map.
+pub(crate) fn scenario_map_mismatches(
+    map_file: &str,
+    map: &Grid,
+    entries: &[ScenarioEntry],
+) -> Vec<String> {
+    let map_file_name = file_name(map_file);
+    let mut warnings = Vec::<String>::new();
+    let mut reported = HashSet::<String>::new();
+    for entry in entries.iter() {
+        if file_name(&entry.map_name) != map_file_name {
+            let warning = format!(
+                "scenario is for map '{}', but '{}' was loaded",
+                entry.map_name, map_file_name
+            );
+            if reported.insert(warning.clone()) {
+                warnings.push(warning);
+            }
+        }
+        if (entry.map_width, entry.map_height) != (map.width, map.height) {
+            let warning = format!(
+                "scenario is for a {}x{} map, but the loaded map is {}x{}",
+                entry.map_width, entry.map_height, map.width, map.height
+            );
+            if reported.insert(warning.clone()) {
+                warnings.push(warning);
+            }
+        }
+    }
+    warnings
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+pub fn load_scenario(scen_file: &str) -> Result<Vec<ScenarioEntry>, String> {
+    read_scenario(File::open(scen_file).map_err(|e| e.to_string())?)
+}
+
+pub fn read_scenario(mut reader: impl Read) -> Result<Vec<ScenarioEntry>, String> {
     let agents_regex = Regex::new(r"version \d+(?:\.\d+)?\r?\n((?:\d+\t(?:.+)\t\d+\t\d+\t\d+\t\d+\t\d+\t\d+\t[\d.]+(?:\r?\n)?)*)").unwrap();
-    let mut scen_file = File::open(scen_file).map_err(|e| e.to_string())?;
     let mut scen_content = String::new();
-    scen_file
+    reader
         .read_to_string(&mut scen_content)
         .map_err(|e| e.to_string())?;
     let scen_match = agents_regex
@@ -104,19 +462,49 @@ fn load_scenario_file(scen_file: &str) -> Result<Vec<Agent>, String> {
         .ok_or("Invalid scenario file")?;
     let agents_regex =
         Regex::new(r"(\d+)\t(.+)\t(\d+)\t(\d+)\t(\d+)\t(\d+)\t(\d+)\t(\d+)\t([\d.]+)").unwrap();
-    let mut agents: Vec<Agent> = Vec::new();
-    for (i, caps) in agents_regex.captures_iter(&scen_match[1]).enumerate() {
+    let mut entries: Vec<ScenarioEntry> = Vec::new();
+    for caps in agents_regex.captures_iter(&scen_match[1]) {
+        let bucket = caps[1].parse::<usize>().or(Err("bucket not a number"))?;
+        let map_width = caps[3].parse::<i32>().or(Err("map width not a number"))?;
+        let map_height = caps[4].parse::<i32>().or(Err("map height not a number"))?;
         let x_start = caps[5].parse::<i32>().or(Err("start x not a number"))?;
         let y_start = caps[6].parse::<i32>().or(Err("start y not a number"))?;
         let x_goal = caps[7].parse::<i32>().or(Err("goal x not a number"))?;
         let y_goal = caps[8].parse::<i32>().or(Err("goal y not a number"))?;
-        agents.push(Agent {
-            id: i.to_string(),
+        let optimal_length = caps[9]
+            .parse::<f64>()
+            .or(Err("optimal length not a number"))?;
+        entries.push(ScenarioEntry {
+            bucket,
+            map_name: caps[2].to_string(),
+            map_width,
+            map_height,
             start: (x_start, y_start),
             goal: (x_goal, y_goal),
+            optimal_length,
         });
     }
-    Ok(agents)
+    Ok(entries)
+}
+
+/// Writes scenario rows in the Moving AI scenario format.
+pub fn scenario_to_string(entries: &[ScenarioEntry]) -> String {
+    let mut scen = String::from("version 1\n");
+    for entry in entries.iter() {
+        scen.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.8}\n",
+            entry.bucket,
+            entry.map_name,
+            entry.map_width,
+            entry.map_height,
+            entry.start.0,
+            entry.start.1,
+            entry.goal.0,
+            entry.goal.1,
+            entry.optimal_length
+        ));
+    }
+    scen
 }
 
 pub fn path_to_string(agent_id: &str, path: &Path) -> String {
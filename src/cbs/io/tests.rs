@@ -1,18 +1,17 @@
 use super::*;
+use crate::cbs::low_level::obstacles::ObstacleMap;
 use rstest::rstest;
 use std::io::Read;
 
 #[rstest]
 #[case::clean_24x24("tests/testdata/maps/clean_24x24.map", 24, 24, vec![])]
 #[case::clean_24x24_crlf("tests/testdata/maps/clean_24x24_crlf.map", 24, 24, vec![])]
-#[case::clean_24x24_crlf("tests/testdata/maps/5x3_with_conflict.map", 5, 3, vec![
-    LocationTime { location: (1, 3), time: -1 },
-])]
+#[case::clean_24x24_crlf("tests/testdata/maps/5x3_with_conflict.map", 5, 3, vec![(1, 3)])]
 fn test_map_loading_saving(
     #[case] map_file_path: &str,
     #[case] height: i32,
     #[case] width: i32,
-    #[case] obstacles: Vec<LocationTime>,
+    #[case] obstacles: Vec<(i32, i32)>,
 ) {
     let mut map_file = File::open(map_file_path).unwrap();
     let mut map_file_content = String::new();
@@ -21,18 +20,20 @@ fn test_map_loading_saving(
     assert_eq!(map.height, height);
     assert_eq!(map.width, width);
     assert_eq!(
-        map.obstacles,
-        Grid::to_conditional_obstacles(obstacles.clone().into_iter().collect())
+        *map.static_obstacles,
+        ObstacleMap::new(width, height, obstacles.clone())
     );
+    assert!(map.timed_obstacles.is_empty());
 
     let map_file_content: String = map.try_into().unwrap();
     let map = Grid::try_from(map_file_content).unwrap();
     assert_eq!(map.height, height);
     assert_eq!(map.width, width);
     assert_eq!(
-        map.obstacles,
-        Grid::to_conditional_obstacles(obstacles.into_iter().collect())
+        *map.static_obstacles,
+        ObstacleMap::new(width, height, obstacles)
     );
+    assert!(map.timed_obstacles.is_empty());
 }
 
 #[rstest]
@@ -50,7 +51,195 @@ fn test_map_loading_saving(
     Agent { id: "10".to_string(), start: (2, 9), goal: (23, 20) },
     Agent { id: "11".to_string(), start: (1, 10), goal: (23, 21) },
 ])]
-fn test_load_scenario_file(#[case] scen_file_path: &str, #[case] exp_agents: Vec<Agent>) {
-    let agents = load_scenario_file(scen_file_path).unwrap();
+fn test_load_scenario_agents(#[case] scen_file_path: &str, #[case] exp_agents: Vec<Agent>) {
+    let entries = load_scenario(scen_file_path).unwrap();
+    let agents = entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| Agent {
+            id: i.to_string(),
+            start: entry.start,
+            goal: entry.goal,
+        })
+        .collect::<Vec<_>>();
     assert_eq!(agents, exp_agents);
 }
+
+#[test]
+fn test_map_terrain() {
+    let map =
+        Grid::try_from("type octile\nheight 2\nwidth 3\nmap\n.SW\nT@G\n".to_string()).unwrap();
+    assert_eq!(
+        *map.static_obstacles,
+        ObstacleMap::new(3, 2, [(0, 1), (1, 1)])
+    );
+    assert_eq!(map.terrain_cost((0, 0)), 1);
+    assert_eq!(map.terrain_cost((1, 0)), 2);
+    assert_eq!(map.terrain_cost((2, 0)), 2);
+    assert!(map.has_terrain_costs());
+    assert_eq!(map.terrain.as_ref().unwrap().get((2, 0)), Some('W'));
+    assert_eq!(*map.enclosed_cells, ObstacleMap::new(3, 2, [(2, 0)]));
+    // water is only entered from water, but may be left and waited in
+    assert!(!map.is_valid_location(&(2, 0), &(1, 0)));
+    assert!(!map.is_valid_location(&(2, 0), &(2, 1)));
+    assert!(map.is_valid_location(&(1, 0), &(2, 0)));
+    assert!(map.is_valid_location(&(2, 0), &(2, 0)));
+
+    let map_file_content: String = map.try_into().unwrap();
+    assert_eq!(
+        map_file_content,
+        "type octile\nheight 2\nwidth 3\nmap\n.SW\nT@G\n"
+    );
+}
+
+#[test]
+fn test_map_custom_terrain_table() {
+    let table = "S=blocked".parse::<TerrainTable>().unwrap();
+    let map = parse_map("type octile\nheight 1\nwidth 2\nmap\n.S\n", &table).unwrap();
+    assert_eq!(*map.static_obstacles, ObstacleMap::new(2, 1, [(1, 0)]));
+}
+
+#[rstest]
+#[case::empty("", 1, 1)]
+#[case::wrong_type("type tile\nheight 1\nwidth 1\nmap\n.\n", 1, 1)]
+#[case::bad_height("type octile\nheight x\nwidth 1\nmap\n.\n", 2, 8)]
+#[case::missing_width("type octile\nheight 1\nmap\n.\n", 3, 1)]
+#[case::unknown_terrain("type octile\nheight 2\nwidth 3\nmap\n...\n.x.\n", 6, 2)]
+#[case::short_row("type octile\nheight 2\nwidth 3\nmap\n...\n..\n", 6, 3)]
+#[case::long_row("type octile\nheight 2\nwidth 3\nmap\n....\n...\n", 5, 4)]
+#[case::missing_rows("type octile\nheight 3\nwidth 3\nmap\n...\n...\n", 7, 1)]
+#[case::trailing_content("type octile\nheight 1\nwidth 1\nmap\n.\n\n.\n", 7, 1)]
+fn test_map_parse_errors(#[case] content: &str, #[case] line: usize, #[case] column: usize) {
+    let err = parse_map(content, &TerrainTable::default()).unwrap_err();
+    assert_eq!((err.line, err.column), (line, column), "error: {}", err);
+}
+
+#[test]
+fn test_load_scenario() {
+    let entries = load_scenario("tests/testdata/scenarios/empty-16-16-even-1.scen").unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(
+        entries[1],
+        ScenarioEntry {
+            bucket: 3,
+            map_name: "empty-16-16.map".to_string(),
+            map_width: 16,
+            map_height: 16,
+            start: (9, 11),
+            goal: (0, 1),
+            optimal_length: 13.72792206,
+        }
+    );
+}
+
+#[rstest]
+#[case::all(AgentSelection::default(), vec!["0", "1", "2", "3", "4"])]
+#[case::prefix(AgentSelection { max_agents: Some(2), ..Default::default() }, vec!["0", "1"])]
+#[case::buckets(AgentSelection { buckets: Some(1..=3), ..Default::default() }, vec!["1", "2", "4"])]
+#[case::indices(AgentSelection { indices: Some(1..=2), ..Default::default() }, vec!["1", "2"])]
+#[case::combined(
+    AgentSelection { buckets: Some(0..=3), indices: Some(1..=3), max_agents: Some(1) },
+    vec!["1"],
+)]
+fn test_agent_selection(#[case] selection: AgentSelection, #[case] exp_ids: Vec<&str>) {
+    let instance = CBSInstance::from_files_with_options(
+        "tests/testdata/maps/empty-16-16.map",
+        "tests/testdata/scenarios/empty-16-16-even-1.scen",
+        &LoadOptions {
+            agents: selection,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        instance
+            .agents
+            .iter()
+            .map(|a| a.id.as_str())
+            .collect::<Vec<_>>(),
+        exp_ids
+    );
+    assert!(instance
+        .agents
+        .iter()
+        .all(|a| instance.scenario[&a.id].start == a.start));
+}
+
+#[test]
+fn test_scenario_map_mismatches() {
+    let map = Grid::new(16, 16, HashMap::new(), (0, 0));
+    let entries = load_scenario("tests/testdata/scenarios/empty-16-16-even-1.scen").unwrap();
+    assert!(scenario_map_mismatches("some/dir/empty-16-16.map", &map, &entries).is_empty());
+    assert_eq!(
+        scenario_map_mismatches(
+            "other.map",
+            &Grid::new(8, 16, HashMap::new(), (0, 0)),
+            &entries
+        ),
+        vec![
+            "scenario is for map 'empty-16-16.map', but 'other.map' was loaded".to_string(),
+            "scenario is for a 16x16 map, but the loaded map is 8x16".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_check_optimal_lengths() {
+    let mut instance = CBSInstance::from_files(
+        "tests/testdata/maps/empty-16-16.map",
+        "tests/testdata/scenarios/empty-16-16-even-1.scen",
+        None,
+    )
+    .unwrap();
+    assert!(instance
+        .check_optimal_lengths(&AStarLowLevelSolver::new())
+        .is_empty());
+
+    instance.scenario.get_mut("2").unwrap().optimal_length = 2.0;
+    assert_eq!(
+        instance.check_optimal_lengths(&AStarLowLevelSolver::new()),
+        vec![OptimalLengthMismatch {
+            agent: "2".to_string(),
+            expected: 2.0,
+            found: Some(6),
+        }]
+    );
+}
+
+#[test]
+fn test_scenario_round_trip() {
+    let entries = load_scenario("tests/testdata/scenarios/empty-16-16-even-1.scen").unwrap();
+    let scen_file = std::env::temp_dir().join("cbs_rs_test_scenario_round_trip.scen");
+    fs::write(&scen_file, scenario_to_string(&entries)).unwrap();
+    assert_eq!(load_scenario(scen_file.to_str().unwrap()).unwrap(), entries);
+}
+
+#[test]
+fn test_from_readers() {
+    let map = "type octile\nheight 2\nwidth 3\nmap\n..@\n...\n";
+    let scen = "version 1\n0\tm.map\t3\t2\t0\t0\t2\t1\t3\n0\tm.map\t3\t2\t0\t1\t1\t0\t2\n";
+    let instance = CBSInstance::from_readers(
+        map.as_bytes(),
+        scen.as_bytes(),
+        &LoadOptions {
+            agents: AgentSelection {
+                max_agents: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        *instance.map.static_obstacles,
+        ObstacleMap::new(3, 2, [(2, 0)])
+    );
+    assert_eq!(
+        instance.agents,
+        vec![Agent {
+            id: "0".to_string(),
+            start: (0, 0),
+            goal: (2, 1)
+        }]
+    );
+}
@@ -0,0 +1,156 @@
+//! Instances and solutions in the YAML format of libMultiRobotPlanning.
+
+use std::{collections::HashMap, fs::File, io::Read};
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use crate::cbs::{
+    high_level::{Agent, Path},
+    low_level::{Grid, LocationTime},
+    CBSInstance,
+};
+
+#[derive(Debug, Deserialize)]
+struct YamlInstance {
+    map: YamlMap,
+    agents: Vec<YamlAgent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct YamlMap {
+    /// Width and height.
+    dimensions: [i32; 2],
+    #[serde(default)]
+    obstacles: Vec<YamlLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct YamlAgent {
+    name: String,
+    start: YamlLocation,
+    goal: YamlLocation,
+}
+
+/// An `[x, y]` list, or a `python/tuple` of the two. serde_yaml already
+/// reads the `!!python/tuple` PyYAML writes as a list, but keeps the tag of
+/// the local `!python/tuple` spelling.
+#[derive(Debug)]
+struct YamlLocation(i32, i32);
+
+impl<'de> Deserialize<'de> for YamlLocation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = match serde_yaml::Value::deserialize(deserializer)? {
+            serde_yaml::Value::Tagged(tagged) if tagged.tag == "python/tuple" => tagged.value,
+            serde_yaml::Value::Tagged(tagged) => {
+                return Err(de::Error::custom(format!(
+                    "unexpected tag {} on a location",
+                    tagged.tag
+                )))
+            }
+            value => value,
+        };
+        let [x, y] = serde_yaml::from_value::<[i32; 2]>(value).map_err(de::Error::custom)?;
+        Ok(YamlLocation(x, y))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct YamlSolution {
+    statistics: YamlStatistics,
+    schedule: serde_yaml::Mapping,
+}
+
+#[derive(Debug, Serialize)]
+struct YamlStatistics {
+    cost: usize,
+    makespan: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct YamlState {
+    x: i32,
+    y: i32,
+    t: usize,
+}
+
+impl CBSInstance {
+    /// Parses a libMultiRobotPlanning instance. Agents keep their names as ids,
+    /// and locations may be plain lists or `!!python/tuple`s.
+    pub fn from_yaml(content: &str) -> Result<Self, String> {
+        let instance: YamlInstance = serde_yaml::from_str(content).map_err(|e| e.to_string())?;
+        let [width, height] = instance.map.dimensions;
+        if width <= 0 || height <= 0 {
+            return Err(format!("invalid map dimensions {}x{}", width, height));
+        }
+        let map = Grid::new(
+            width,
+            height,
+            Grid::to_conditional_obstacles(
+                instance
+                    .map
+                    .obstacles
+                    .into_iter()
+                    .map(|YamlLocation(x, y)| LocationTime::new((x, y), -1))
+                    .collect(),
+            ),
+            (0, 0),
+        );
+        let agents = instance
+            .agents
+            .into_iter()
+            .map(|agent| Agent {
+                id: agent.name,
+                start: (agent.start.0, agent.start.1),
+                goal: (agent.goal.0, agent.goal.1),
+            })
+            .collect();
+        Ok(CBSInstance::new(map, agents))
+    }
+
+    pub fn from_yaml_reader(mut reader: impl Read) -> Result<Self, String> {
+        let mut content = String::new();
+        reader
+            .read_to_string(&mut content)
+            .map_err(|e| e.to_string())?;
+        Self::from_yaml(&content)
+    }
+
+    pub fn from_yaml_file(instance_file: &str) -> Result<Self, String> {
+        let file = File::open(instance_file).map_err(|e| e.to_string())?;
+        Self::from_yaml_reader(file).map_err(|e| format!("{}: {}", instance_file, e))
+    }
+}
+
+/// Writes a solution in the `schedule` format of libMultiRobotPlanning,
+/// listing agents in the order of `agents`. The cost of an agent is the time
+/// it reaches its goal.
+pub fn schedule_to_yaml(agents: &[Agent], paths: &HashMap<&Agent, Path>) -> String {
+    let mut schedule = serde_yaml::Mapping::new();
+    let mut cost = 0;
+    let mut makespan = 0;
+    for agent in agents.iter() {
+        let Some(path) = paths.get(agent) else {
+            continue;
+        };
+        let arrival = path.len().saturating_sub(1);
+        cost += arrival;
+        makespan = makespan.max(arrival);
+        let states = path
+            .iter()
+            .enumerate()
+            .map(|(t, (x, y))| YamlState { x: *x, y: *y, t })
+            .collect::<Vec<_>>();
+        schedule.insert(
+            agent.id.clone().into(),
+            serde_yaml::to_value(states).expect("states are serialisable"),
+        );
+    }
+    serde_yaml::to_string(&YamlSolution {
+        statistics: YamlStatistics { cost, makespan },
+        schedule,
+    })
+    .expect("solutions are serialisable")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,88 @@
+use super::*;
+use crate::cbs::low_level::obstacles::ObstacleMap;
+use rstest::rstest;
+
+const INSTANCE: &str = "
+map:
+  dimensions: [3, 2]
+  obstacles:
+  - !!python/tuple [1, 0]
+agents:
+- start: !python/tuple [0, 0]
+  goal: [2, 0]
+  name: agent0
+- name: robot-b
+  start: [2, 1]
+  goal: [0, 1]
+";
+
+#[test]
+fn test_from_yaml() {
+    let instance = CBSInstance::from_yaml(INSTANCE).unwrap();
+    assert_eq!((instance.map.width, instance.map.height), (3, 2));
+    assert_eq!(
+        *instance.map.static_obstacles,
+        ObstacleMap::new(3, 2, [(1, 0)])
+    );
+    assert_eq!(
+        instance.agents,
+        vec![
+            Agent {
+                id: "agent0".to_string(),
+                start: (0, 0),
+                goal: (2, 0)
+            },
+            Agent {
+                id: "robot-b".to_string(),
+                start: (2, 1),
+                goal: (0, 1)
+            },
+        ]
+    );
+}
+
+#[rstest]
+#[case::missing_agents("map:\n  dimensions: [3, 3]\n")]
+#[case::missing_name("map:\n  dimensions: [3, 3]\nagents:\n- start: [0, 0]\n  goal: [1, 1]\n")]
+#[case::bad_dimensions("map:\n  dimensions: [0, 3]\nagents: []\n")]
+#[case::unknown_tag(
+    "map:\n  dimensions: [3, 3]\nagents:\n- {name: a, start: !point [0, 0], goal: [1, 1]}\n"
+)]
+fn test_from_yaml_invalid(#[case] content: &str) {
+    assert!(CBSInstance::from_yaml(content).is_err());
+}
+
+#[test]
+fn test_solve_yaml_instance() {
+    let instance = CBSInstance::from_yaml(
+        "
+map:
+  dimensions: [3, 3]
+  obstacles: [[1, 1]]
+agents:
+- {name: agent0, start: [0, 0], goal: [2, 0]}
+- {name: robot-b, start: [0, 2], goal: [2, 1]}
+",
+    )
+    .unwrap();
+    let agents = instance.agents.clone();
+    let mut cbs = crate::cbs::CBS::new(instance, None);
+    let paths = cbs.solve().unwrap();
+    let schedule: serde_yaml::Value =
+        serde_yaml::from_str(&schedule_to_yaml(&agents, &paths)).unwrap();
+    let names = schedule["schedule"]
+        .as_mapping()
+        .unwrap()
+        .keys()
+        .map(|k| k.as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["agent0", "robot-b"]);
+    let first = &schedule["schedule"]["agent0"];
+    assert_eq!(first[0]["x"], 0);
+    assert_eq!(first[0]["t"], 0);
+    assert_eq!(first[2]["x"], 2);
+    assert_eq!(first[2]["y"], 0);
+    assert_eq!(first[2]["t"], 2);
+    assert_eq!(schedule["statistics"]["cost"], 5);
+    assert_eq!(schedule["statistics"]["makespan"], 3);
+}
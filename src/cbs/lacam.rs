@@ -0,0 +1,158 @@
+//! Okumura's Lazy Constraints Addition search: a depth-first search over
+//! configurations whose successors are generated one at a time by PIBT.
+//! Every configuration lazily grows a tree of constraints fixing the next
+//! location of its agents one by one, so that revisiting it makes PIBT
+//! produce a different successor. Complete, but not optimal, and without
+//! the refinements of LaCAM* it can take long to escape a PIBT livelock
+//! involving many agents.
+//!
+//! Only the static obstacles of the map are considered.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    pibt::{
+        configurations_to_paths, initial_priorities, priority_order, update_priorities,
+        Configuration, ConfigurationGenerator, DistanceTables,
+    },
+    search::SearchError,
+    CBSError, CBSInstance,
+};
+
+/// A configuration reached by the search.
+struct HighLevelNode {
+    configuration: Configuration,
+    parent: Option<usize>,
+    priorities: Vec<f64>,
+    order: Vec<usize>,
+    /// Constraint sets not yet tried, in breadth-first order.
+    constraints: VecDeque<Vec<(usize, (i32, i32))>>,
+}
+
+pub struct LaCAM {
+    instance: CBSInstance,
+    seed: u64,
+    cancellation: Option<CancellationToken>,
+    pub high_level_generated: usize,
+    pub low_level_generated: usize,
+}
+
+impl LaCAM {
+    pub fn new(instance: CBSInstance) -> Self {
+        Self {
+            instance,
+            seed: 0,
+            cancellation: None,
+            high_level_generated: 0,
+            low_level_generated: 0,
+        }
+    }
+
+    /// Seeds PIBT's tie-breaking and the order in which constraints are tried.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Makes [`Self::solve`] fail with [`SearchError::Cancelled`] once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        self.instance.require_unit_costs("LaCAM")?;
+        self.instance.require_no_timed_obstacles("LaCAM")?;
+        let map = &self.instance.map;
+        let agents = &self.instance.agents;
+        let distances = DistanceTables::new(map, agents);
+        let goals = agents.iter().map(|agent| agent.goal).collect::<Vec<_>>();
+        let starts = agents.iter().map(|agent| agent.start).collect::<Vec<_>>();
+        if (0..agents.len()).any(|i| distances.distance(i, starts[i]).is_infinite()) {
+            return Err(Box::new(SearchError::NotFound));
+        }
+        let mut generator = ConfigurationGenerator::new(map, &distances, self.seed);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let priorities = initial_priorities(&distances, &starts);
+        let mut nodes = vec![HighLevelNode {
+            configuration: starts.clone(),
+            parent: None,
+            order: priority_order(&priorities),
+            priorities,
+            constraints: VecDeque::from([Vec::new()]),
+        }];
+        let mut explored = HashMap::from([(starts, 0)]);
+        let mut open = vec![0];
+        self.high_level_generated = 1;
+        while let Some(&current) = open.last() {
+            if self
+                .cancellation
+                .as_ref()
+                .is_some_and(|cancellation| cancellation.is_cancelled())
+            {
+                return Err(Box::new(SearchError::Cancelled));
+            }
+            let node = &mut nodes[current];
+            if node.configuration == goals {
+                let mut configurations = vec![node.configuration.clone()];
+                let mut parent = node.parent;
+                while let Some(index) = parent {
+                    configurations.push(nodes[index].configuration.clone());
+                    parent = nodes[index].parent;
+                }
+                configurations.reverse();
+                let paths = configurations_to_paths(agents, &configurations);
+                return Ok(paths);
+            }
+            let Some(constraints) = node.constraints.pop_front() else {
+                open.pop();
+                continue;
+            };
+            if constraints.len() < agents.len() {
+                let agent = node.order[constraints.len()];
+                let mut moves = generator.moves(node.configuration[agent]);
+                moves.shuffle(&mut rng);
+                for location in moves {
+                    let mut child = constraints.clone();
+                    child.push((agent, location));
+                    node.constraints.push_back(child);
+                    self.low_level_generated += 1;
+                }
+            }
+            let Some(next) =
+                generator.next_configuration(&node.configuration, &node.order, &constraints)
+            else {
+                continue;
+            };
+            if explored.contains_key(&next) {
+                continue;
+            }
+            let mut priorities = node.priorities.clone();
+            update_priorities(&mut priorities, &next, &goals);
+            explored.insert(next.clone(), nodes.len());
+            open.push(nodes.len());
+            nodes.push(HighLevelNode {
+                configuration: next,
+                parent: Some(current),
+                order: priority_order(&priorities),
+                priorities,
+                constraints: VecDeque::from([Vec::new()]),
+            });
+            self.high_level_generated += 1;
+        }
+        Err(Box::new(SearchError::NotFound))
+    }
+}
+
+#[cfg(test)]
+mod tests;
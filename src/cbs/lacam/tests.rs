@@ -0,0 +1,184 @@
+use proptest::{prelude::*, sample::subsequence};
+use rstest::rstest;
+
+use super::*;
+use crate::cbs::{
+    low_level::{Grid, LocationTime},
+    operator_decomposition::OperatorDecomposition,
+};
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+fn empty_grid(width: i32, height: i32) -> Grid {
+    Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(Vec::new()),
+        (0, 0),
+    )
+}
+
+fn sum_of_costs(paths: &HashMap<&Agent, Path>) -> usize {
+    paths.values().map(|path| path.len() - 1).sum()
+}
+
+fn assert_valid(map: &Grid, paths: &HashMap<&Agent, Path>) {
+    let horizon = paths.values().map(|path| path.len()).max().unwrap_or(0);
+    let at = |path: &Path, t: usize| path[t.min(path.len() - 1)];
+    for (agent, path) in paths {
+        assert_eq!(path.first(), Some(&agent.start));
+        assert_eq!(path.last(), Some(&agent.goal));
+        for step in path.windows(2) {
+            assert!(map.is_valid_location(&step[1], &step[0]));
+        }
+    }
+    let paths = paths.values().collect::<Vec<_>>();
+    for t in 0..horizon {
+        for (i, a) in paths.iter().enumerate() {
+            for b in &paths[i + 1..] {
+                assert_ne!(at(a, t), at(b, t), "vertex conflict at {}", t);
+                assert!(
+                    at(a, t) != at(b, t + 1) || at(a, t + 1) != at(b, t),
+                    "swapping conflict at {}",
+                    t
+                );
+            }
+        }
+    }
+}
+
+#[rstest]
+#[case::independent(vec![agent("a", (0, 0), (0, 3)), agent("b", (3, 3), (3, 0))])]
+#[case::crossing(vec![agent("a", (0, 1), (2, 1)), agent("b", (1, 0), (1, 2))])]
+#[case::swapping(vec![agent("a", (0, 0), (3, 0)), agent("b", (3, 0), (0, 0))])]
+#[case::pushed_off_goal(vec![agent("a", (1, 1), (1, 1)), agent("b", (0, 1), (2, 1))])]
+fn test_valid_paths(#[case] agents: Vec<Agent>) {
+    let grid = empty_grid(4, 4);
+    let mut lacam = LaCAM::new(CBSInstance::new(grid.clone(), agents));
+    let paths = lacam.solve().unwrap();
+    assert_valid(&grid, &paths);
+}
+
+// PIBT alone cannot solve this: the agent in the dead end has to let the
+// other one past although it is already at its goal
+#[test]
+fn test_solves_swap_in_dead_end() {
+    let grid = Grid::new(
+        3,
+        2,
+        Grid::to_conditional_obstacles(vec![
+            LocationTime::new((0, 1), -1),
+            LocationTime::new((2, 1), -1),
+        ]),
+        (0, 0),
+    );
+    let agents = vec![agent("a", (0, 0), (2, 0)), agent("b", (2, 0), (0, 0))];
+    let mut lacam = LaCAM::new(CBSInstance::new(grid.clone(), agents));
+    let paths = lacam.solve().unwrap();
+    assert_valid(&grid, &paths);
+}
+
+#[test]
+fn test_not_found_when_exhausted() {
+    let mut lacam = LaCAM::new(CBSInstance::new(
+        empty_grid(3, 1),
+        vec![agent("a", (0, 0), (2, 0)), agent("b", (2, 0), (0, 0))],
+    ));
+    let error = lacam.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::NotFound)
+    ));
+}
+
+#[test]
+fn test_cancelled() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let mut lacam = LaCAM::new(CBSInstance::new(
+        empty_grid(4, 4),
+        vec![agent("a", (0, 0), (3, 3))],
+    ))
+    .with_cancellation(cancellation);
+    let error = lacam.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::Cancelled)
+    ));
+}
+
+#[test]
+fn test_rejects_timed_obstacles() {
+    let map = Grid::new(
+        4,
+        4,
+        Grid::to_conditional_obstacles(vec![crate::cbs::low_level::LocationTime::new((1, 1), 2)]),
+        (0, 0),
+    );
+    let mut lacam = LaCAM::new(CBSInstance::new(map, vec![agent("a", (0, 0), (3, 3))]));
+    let error = lacam.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::InvalidArguments(_))
+    ));
+}
+
+// PIBT alone livelocks here, see the PIBT warehouse test
+#[test]
+fn test_warehouse() {
+    let instance = CBSInstance::from_files(
+        "tests/testdata/maps/warehouse-10-20-10-2-1.map",
+        "tests/testdata/scenarios/warehouse-10-20-10-2-1-even-1.scen",
+        Some(100),
+    )
+    .expect("should be valid scenario files");
+    let map = instance.map.clone();
+    let mut lacam = LaCAM::new(instance);
+    let paths = lacam.solve().unwrap();
+    assert_eq!(paths.len(), 100);
+    assert_valid(&map, &paths);
+}
+
+fn open_grid_instance() -> impl Strategy<Value = Vec<Agent>> {
+    let cells = (0..3)
+        .flat_map(|x| (0..3).map(move |y| (x, y)))
+        .collect::<Vec<_>>();
+    (1..5usize)
+        .prop_flat_map(move |n| {
+            (
+                subsequence(cells.clone(), n).prop_shuffle(),
+                subsequence(cells.clone(), n).prop_shuffle(),
+            )
+        })
+        .prop_map(|(starts, goals)| {
+            starts
+                .into_iter()
+                .zip(goals)
+                .enumerate()
+                .map(|(i, (start, goal))| agent(&i.to_string(), start, goal))
+                .collect()
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+    #[test]
+    fn test_solves_whenever_joint_search_does(
+        agents in open_grid_instance(),
+        seed in any::<u64>(),
+    ) {
+        let grid = empty_grid(3, 3);
+        let mut oracle = OperatorDecomposition::new(CBSInstance::new(grid.clone(), agents.clone()));
+        let expected = sum_of_costs(&oracle.solve().unwrap());
+        let mut lacam = LaCAM::new(CBSInstance::new(grid.clone(), agents)).with_seed(seed);
+        let paths = lacam.solve().unwrap();
+        assert_valid(&grid, &paths);
+        prop_assert!(sum_of_costs(&paths) >= expected);
+    }
+}
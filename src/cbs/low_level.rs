@@ -1,13 +1,18 @@
 use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     hash::Hash,
-    rc::Rc,
+    path::PathBuf,
+    sync::Arc,
 };
 
+use distance_cache::DistanceCache;
 use heuristic::Heuristic;
+use obstacles::{ConstraintTable, CostMap, ObstacleMap};
 
-use super::search::{a_star, AStarNode};
+use super::{
+    search::{a_star, AStarNode},
+    terrain::Terrain,
+};
 
 #[derive(Debug, Eq, Clone, Copy)]
 pub struct LocationTime {
@@ -44,24 +49,34 @@ struct Obstacle {
 pub struct Grid {
     pub width: i32,
     pub height: i32,
-    pub obstacles: HashMap<LocationTime, Vec<(i32, i32)>>,
+    /// Shared by the grids of the same map, which only differ in their
+    /// goal and timed obstacles.
+    pub(crate) static_obstacles: Arc<ObstacleMap>,
+    pub(crate) timed_obstacles: ConstraintTable,
+    /// Cells only entered from another enclosed cell, see [`TerrainClass::Enclosed`].
+    pub(crate) enclosed_cells: Arc<ObstacleMap>,
+    /// The timesteps it takes to enter each cell, see [`Self::arrival_time`].
+    pub(crate) costs: Arc<CostMap>,
     pub goal: (i32, i32),
     latest_goal_obstacle_time: i32,
+    pub(crate) terrain: Option<Arc<Terrain>>,
 }
 
 impl Hash for Grid {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.width.hash(state);
         self.height.hash(state);
-        self.obstacles.iter().for_each(|(k, v)| {
-            k.hash(state);
-            v.hash(state);
-        });
+        self.static_obstacles.hash(state);
+        self.timed_obstacles.hash(state);
+        self.enclosed_cells.hash(state);
+        self.costs.hash(state);
         self.goal.hash(state);
     }
 }
 
 impl Grid {
+    /// Obstacles at a negative time are static, and block their location
+    /// whichever way it is entered.
     pub fn new(
         width: i32,
         height: i32,
@@ -71,11 +86,15 @@ impl Grid {
         let mut grid = Grid {
             width,
             height,
-            obstacles,
+            static_obstacles: Arc::new(ObstacleMap::new(width, height, [])),
+            timed_obstacles: ConstraintTable::default(),
+            enclosed_cells: Arc::new(ObstacleMap::new(width, height, [])),
+            costs: Arc::new(CostMap::new(width, height, [])),
             goal,
-            latest_goal_obstacle_time: -1,
+            latest_goal_obstacle_time: i32::MIN,
+            terrain: None,
         };
-        grid.latest_goal_obstacle_time = grid.latest_goal_obstacle_time();
+        grid.add_obstacles(obstacles);
         grid
     }
 
@@ -88,6 +107,74 @@ impl Grid {
             .collect()
     }
 
+    /// Adds obstacles in the form taken by [`Self::new`]. Adding static
+    /// obstacles copies the static obstacles if other grids share them.
+    pub fn add_obstacles(&mut self, obstacles: HashMap<LocationTime, Vec<(i32, i32)>>) {
+        for (loc_time, coming_from) in obstacles {
+            if loc_time.time < 0 {
+                Arc::make_mut(&mut self.static_obstacles).insert(loc_time.location);
+            } else if coming_from.is_empty() {
+                self.timed_obstacles.insert(loc_time, None);
+            } else {
+                for from in coming_from {
+                    self.timed_obstacles.insert(loc_time, Some(from));
+                }
+            }
+        }
+        self.latest_goal_obstacle_time = self.latest_goal_obstacle_time();
+    }
+
+    /// Adds the timed obstacles of `constraints`, keeping the static obstacles shared.
+    pub(crate) fn add_constraints(&mut self, constraints: &ConstraintTable) {
+        self.timed_obstacles.extend(constraints);
+        self.latest_goal_obstacle_time = self.latest_goal_obstacle_time();
+    }
+
+    /// The same map with another goal, sharing the static obstacles.
+    pub(crate) fn with_goal(&self, goal: (i32, i32)) -> Grid {
+        Grid {
+            goal,
+            latest_goal_obstacle_time: self
+                .timed_obstacles
+                .latest_vertex_time(goal)
+                .unwrap_or(i32::MIN),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn is_static_obstacle(&self, location: (i32, i32)) -> bool {
+        self.static_obstacles.contains(location)
+    }
+
+    /// Returns the number of timesteps it takes to enter `location`
+    /// according to its terrain. Cells without terrain information cost 1.
+    pub fn terrain_cost(&self, location: (i32, i32)) -> u32 {
+        self.costs.get(location)
+    }
+
+    /// Whether entering some cell takes more than one timestep.
+    pub fn has_terrain_costs(&self) -> bool {
+        !self.costs.is_uniform()
+    }
+
+    /// The time at which an agent at `from` that moves to `to`, or waits if
+    /// `to` is its location, may move on, or `None` if the move is not
+    /// allowed. Entering a cell takes its terrain cost in timesteps, during
+    /// which the agent stays in the cell.
+    pub(crate) fn arrival_time(&self, from: &LocationTime, to: (i32, i32)) -> Option<i32> {
+        let entered = LocationTime::new(to, from.time + 1);
+        if !self.is_valid_location_time(&entered, &from.location) {
+            return None;
+        }
+        if to == from.location {
+            return Some(entered.time);
+        }
+        let arrival = from.time + self.terrain_cost(to) as i32;
+        ((entered.time + 1)..=arrival)
+            .all(|time| self.is_valid_location_time(&LocationTime::new(to, time), &to))
+            .then_some(arrival)
+    }
+
     pub fn is_valid_location(&self, location: &(i32, i32), prev_location: &(i32, i32)) -> bool {
         self.is_valid_location_time(
             &LocationTime {
@@ -107,31 +194,62 @@ impl Grid {
             && loc_time.location.0 < self.width
             && loc_time.location.1 >= 0
             && loc_time.location.1 < self.height
-            // permament obstacles
-            && !self.is_obstacle(&LocationTime {
-                location: loc_time.location,
-                time: -1,
-            }, prev_location)
-            // dynamic obstacles
-            && !self.is_obstacle(loc_time, prev_location)
+            && !self.static_obstacles.contains(loc_time.location)
+            && !self.timed_obstacles.is_blocked(loc_time, prev_location)
+            && (loc_time.location == *prev_location
+                || !self.enclosed_cells.contains(loc_time.location)
+                || self.enclosed_cells.contains(*prev_location))
     }
 
-    fn is_obstacle(&self, loc_time: &LocationTime, prev_location: &(i32, i32)) -> bool {
-        let coming_from = self.obstacles.get(loc_time);
-        match coming_from {
-            Some(coming_from) => coming_from.is_empty() || coming_from.contains(&prev_location),
-            None => false,
+    /// A hash of what the static map lets agents do, stable between runs.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let mut fingerprint = self.static_obstacles.fingerprint();
+        if !self.enclosed_cells.is_empty() {
+            fingerprint ^= self.enclosed_cells.fingerprint().rotate_left(1);
         }
+        if self.has_terrain_costs() {
+            fingerprint ^= self.costs.fingerprint().rotate_left(2);
+        }
+        fingerprint
+    }
+
+    /// Labels every free cell of the static map with the id of the
+    /// 4-connected component it belongs to. Neighbours are only connected
+    /// if each can be entered from the other, so cells of different
+    /// components may still be reachable one way around enclosed cells.
+    /// Dynamic (timed) obstacles are ignored.
+    pub(crate) fn connected_components(&self) -> HashMap<(i32, i32), usize> {
+        let mut components = HashMap::<(i32, i32), usize>::new();
+        let mut num_components = 0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if components.contains_key(&(x, y)) || !self.is_valid_location(&(x, y), &(x, y)) {
+                    continue;
+                }
+                let mut queue = VecDeque::from([(x, y)]);
+                components.insert((x, y), num_components);
+                while let Some(cell) = queue.pop_front() {
+                    for (dx, dy) in [(0, 1), (1, 0), (0, -1), (-1, 0)] {
+                        let neighbour = (cell.0 + dx, cell.1 + dy);
+                        if components.contains_key(&neighbour)
+                            || !self.is_valid_location(&neighbour, &cell)
+                            || !self.is_valid_location(&cell, &neighbour)
+                        {
+                            continue;
+                        }
+                        components.insert(neighbour, num_components);
+                        queue.push_back(neighbour);
+                    }
+                }
+                num_components += 1;
+            }
+        }
+        components
     }
 
     pub(crate) fn latest_goal_obstacle_time(&self) -> i32 {
-        self.obstacles
-            .iter()
-            .filter(|(loc_time, coming_from)| {
-                loc_time.location == self.goal && coming_from.is_empty()
-            })
-            .map(|(loc_time, _)| loc_time.time)
-            .max()
+        self.timed_obstacles
+            .latest_vertex_time(self.goal)
             .unwrap_or(i32::MIN)
     }
 }
@@ -144,6 +262,7 @@ struct PathFindingNode<'a> {
     grid: &'a Grid,
     conflict_avoidance_table: &'a HashSet<LocationTime>,
     heuristic: &'a dyn heuristic::Heuristic<LocationTime>,
+    horizon: i32,
 }
 
 impl<'a> std::fmt::Debug for PathFindingNode<'a> {
@@ -180,6 +299,7 @@ impl<'a> PathFindingNode<'a> {
         grid: &'a Grid,
         conflict_avoidance_table: &'a HashSet<LocationTime>,
         heuristic: &'a dyn heuristic::Heuristic<LocationTime>,
+        horizon: i32,
     ) -> PathFindingNode<'a> {
         PathFindingNode {
             loc_time,
@@ -188,6 +308,7 @@ impl<'a> PathFindingNode<'a> {
             grid,
             conflict_avoidance_table,
             heuristic,
+            horizon,
         }
     }
 }
@@ -223,25 +344,25 @@ impl AStarNode<'_> for PathFindingNode<'_> {
     fn expand(&self) -> Option<Vec<Box<Self>>> {
         let expanded = vec![(0, 0), (0, 1), (1, 0), (0, -1), (-1, 0)]
             .iter()
-            .map(|(x, y)| LocationTime {
-                location: (self.loc_time.location.0 + x, self.loc_time.location.1 + y),
-                time: self.loc_time.time + 1,
-            })
-            .filter(|neighbour| {
-                self.grid
-                    .is_valid_location_time(&neighbour, &self.loc_time.location)
+            .filter_map(|(x, y)| {
+                let location = (self.loc_time.location.0 + x, self.loc_time.location.1 + y);
+                let time = self.grid.arrival_time(&self.loc_time, location)?;
+                Some(LocationTime { location, time })
             })
             .map(|neighbour| -> Box<PathFindingNode> {
                 let h = self.heuristic.h(&neighbour);
                 Box::new(PathFindingNode::new(
                     neighbour,
-                    self.g + 1.0,
+                    self.g + (neighbour.time - self.loc_time.time) as f64,
                     h as f64,
                     self.grid,
                     self.conflict_avoidance_table,
                     self.heuristic,
+                    self.horizon,
                 ))
             })
+            // nodes that cannot reach the goal by the horizon are dead ends
+            .filter(|neighbour| neighbour.g + neighbour.h <= neighbour.horizon as f64)
             .collect::<Vec<Box<Self>>>();
         Some(expanded)
     }
@@ -252,6 +373,8 @@ impl AStarNode<'_> for PathFindingNode<'_> {
 }
 
 pub(crate) trait LowLevelSolver {
+    /// Returns the shortest path to the goal of `grid` along with the
+    /// number of nodes generated, or `None` if the goal is unreachable.
     fn find_shortest_path(
         &self,
         agent_id: String,
@@ -261,46 +384,43 @@ pub(crate) trait LowLevelSolver {
     ) -> Option<(Vec<LocationTime>, usize)>;
 }
 
+/// Caches a distance table per goal. The tables only depend on the static
+/// obstacles, so a solver may be shared by instances on the same map, also
+/// across threads.
 pub struct AStarLowLevelSolver {
-    heuristic_cache: RefCell<HashMap<String, Rc<dyn Heuristic<LocationTime>>>>,
+    pub(crate) distances: DistanceCache,
 }
 
 impl AStarLowLevelSolver {
     pub fn new() -> AStarLowLevelSolver {
         AStarLowLevelSolver {
-            heuristic_cache: RefCell::new(HashMap::new()),
+            distances: DistanceCache::new(),
         }
     }
 
-    fn get_heuristic(
-        &self,
-        agent_id: String,
-        grid: &Grid,
-        start: &LocationTime,
-    ) -> Rc<dyn Heuristic<LocationTime>> {
-        let mut cache = self.heuristic_cache.borrow_mut();
-        let heuristic = cache.entry(agent_id).or_insert_with(|| {
-            Rc::new(heuristic::TrueDistance::new(
-                Rc::new(grid.clone()),
-                start.location.clone(),
-            ))
-        });
-        Rc::clone(&heuristic)
+    /// Keeps the distance tables in `directory` between runs, see
+    /// [`DistanceCache::with_directory`].
+    pub fn with_distance_cache(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.distances = DistanceCache::new().with_directory(directory);
+        self
     }
 }
 
 impl LowLevelSolver for AStarLowLevelSolver {
     fn find_shortest_path(
         &self,
-        agent_id: String,
+        _agent_id: String,
         grid: Grid,
         start: LocationTime,
         conflict_avoidance_table: &HashSet<LocationTime>,
     ) -> Option<(Vec<LocationTime>, usize)> {
-        let heuristic = self.get_heuristic(agent_id, &grid, &start);
+        let heuristic = self.distances.get(&grid, grid.goal);
         let t0 = std::time::Instant::now();
         let h = heuristic.h(&start);
         log::debug!("Calculating heuristic took {:?}", t0.elapsed());
+        if h.is_infinite() {
+            return None;
+        }
         let start_node = PathFindingNode::new(
             start,
             0.0,
@@ -308,16 +428,36 @@ impl LowLevelSolver for AStarLowLevelSolver {
             &grid,
             &conflict_avoidance_table,
             heuristic.as_ref(),
+            time_horizon(&grid),
         );
-        let solution = a_star(start_node).expect("should find path");
-        Some((
-            solution.path.iter().map(|node| node.loc_time).collect(),
-            solution.nodes_generated as usize,
-        ))
+        let solution = a_star(start_node).ok()?;
+        let mut path = Vec::<LocationTime>::new();
+        for node in solution.path.iter() {
+            // the agent stays in a costly cell until it may move on
+            while let Some(time) = path.last().map(|last| last.time + 1) {
+                if time >= node.loc_time.time {
+                    break;
+                }
+                path.push(LocationTime::new(node.loc_time.location, time));
+            }
+            path.push(node.loc_time);
+        }
+        Some((path, solution.nodes_generated as usize))
     }
 }
 
-mod heuristic;
+/// Returns a time by which a shortest path must have reached the goal,
+/// if one exists at all. Once the last timed obstacle has passed, the grid
+/// is static, and a path can visit each cell at most once after that, each
+/// visit taking at most the highest terrain cost.
+fn time_horizon(grid: &Grid) -> i32 {
+    let latest_obstacle_time = grid.timed_obstacles.latest_time().unwrap_or(0);
+    latest_obstacle_time + grid.width * grid.height * grid.costs.max() as i32
+}
+
+pub(crate) mod distance_cache;
+pub(crate) mod heuristic;
+pub(crate) mod obstacles;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,129 @@
+//! Distance maps shared by the agents with the same goal, and optionally
+//! kept on disk so that runs on the same map do not compute them again.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use super::{heuristic::DistanceMap, Grid};
+
+/// Starts every distance map file, followed by the width and height of the
+/// map and then the distances row by row, all little-endian.
+const MAGIC: &[u8; 8] = b"CBSDIST1";
+
+/// Computes the distance map of a goal once. Maps are looked up by goal
+/// only, so a cache must only be used on a single map.
+#[derive(Debug, Default)]
+pub struct DistanceCache {
+    maps: RwLock<HashMap<(i32, i32), Arc<DistanceMap>>>,
+    directory: Option<PathBuf>,
+}
+
+impl DistanceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also reads and writes the distance maps in `directory`, with file
+    /// names made of a hash of the static obstacles and the goal. Files
+    /// that cannot be read are ignored and written again.
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    /// The distance map of `goal` on `map`.
+    pub(crate) fn get(&self, map: &Grid, goal: (i32, i32)) -> Arc<DistanceMap> {
+        if let Some(distances) = self.maps.read().expect("lock is not poisoned").get(&goal) {
+            return Arc::clone(distances);
+        }
+        // computed without the lock, as other threads may want other goals
+        let distances = Arc::new(self.load_or_compute(map, goal));
+        let mut maps = self.maps.write().expect("lock is not poisoned");
+        Arc::clone(maps.entry(goal).or_insert(distances))
+    }
+
+    fn load_or_compute(&self, map: &Grid, goal: (i32, i32)) -> DistanceMap {
+        let Some(directory) = &self.directory else {
+            return DistanceMap::new(map, goal);
+        };
+        let file = directory.join(format!(
+            "{:016x}-{}-{}.dist",
+            map.fingerprint(),
+            goal.0,
+            goal.1
+        ));
+        match read_distance_map(&file, map) {
+            Ok(distances) => return distances,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Ignoring distance map {}: {}", file.display(), e),
+        }
+        let distances = DistanceMap::new(map, goal);
+        if let Err(e) = write_distance_map(&file, &distances) {
+            log::warn!("Could not write distance map {}: {}", file.display(), e);
+        }
+        distances
+    }
+}
+
+fn read_distance_map(file: &Path, map: &Grid) -> io::Result<DistanceMap> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut bytes = Vec::new();
+    fs::File::open(file)?.read_to_end(&mut bytes)?;
+    let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return Err(invalid("not a distance map"));
+    };
+    let words = rest
+        .chunks_exact(4)
+        .map(|word| word.try_into().expect("chunks have 4 bytes"))
+        .collect::<Vec<[u8; 4]>>();
+    let num_cells = (map.width.max(0) * map.height.max(0)) as usize;
+    if rest.len() % 4 != 0
+        || words.len() != 2 + num_cells
+        || i32::from_le_bytes(words[0]) != map.width
+        || i32::from_le_bytes(words[1]) != map.height
+    {
+        return Err(invalid("the size does not match the map"));
+    }
+    Ok(DistanceMap {
+        width: map.width,
+        height: map.height,
+        distances: words[2..]
+            .iter()
+            .map(|word| u32::from_le_bytes(*word))
+            .collect(),
+    })
+}
+
+/// Numbers the temporary files of this process, which threads with
+/// separate caches may write to the same directory at the same time.
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes to a temporary file of its own first, so that the processes and
+/// threads sharing the directory never read a partly written map.
+fn write_distance_map(file: &Path, distances: &DistanceMap) -> io::Result<()> {
+    if let Some(directory) = file.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(distances.width.to_le_bytes());
+    bytes.extend(distances.height.to_le_bytes());
+    bytes.extend(distances.distances.iter().flat_map(|d| d.to_le_bytes()));
+    let temporary = file.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temporary, bytes)?;
+    fs::rename(&temporary, file)
+}
+
+#[cfg(test)]
+mod tests;
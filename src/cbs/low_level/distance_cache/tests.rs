@@ -0,0 +1,128 @@
+use super::*;
+use crate::cbs::low_level::LocationTime;
+
+fn map() -> Grid {
+    let obstacles = (0..4)
+        .map(|y| LocationTime::new((2, y), -1))
+        .collect::<Vec<_>>();
+    Grid::new(5, 5, Grid::to_conditional_obstacles(obstacles), (0, 0))
+}
+
+fn cache_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!(
+        "cbs_rs_test_distance_cache_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&directory);
+    directory
+}
+
+#[test]
+fn test_agents_with_the_same_goal_share_a_map() {
+    let map = map();
+    let cache = DistanceCache::new();
+    let first = cache.get(&map, (4, 0));
+    let second = cache.get(&map, (4, 0));
+
+    assert!(Arc::ptr_eq(&first, &second));
+    assert!(!Arc::ptr_eq(&first, &cache.get(&map, (0, 0))));
+    assert_eq!(first.distance((0, 0)), Some(12));
+    assert_eq!(first.distance((2, 0)), None);
+}
+
+#[test]
+fn test_maps_are_kept_on_disk() {
+    let map = map();
+    let directory = cache_directory("round_trip");
+    let computed = DistanceCache::new()
+        .with_directory(&directory)
+        .get(&map, (4, 0));
+    let files = fs::read_dir(&directory).unwrap().count();
+    let loaded = read_distance_map(
+        &fs::read_dir(&directory)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path(),
+        &map,
+    )
+    .unwrap();
+    let reloaded = DistanceCache::new()
+        .with_directory(&directory)
+        .get(&map, (4, 0));
+
+    assert_eq!(files, 1);
+    assert_eq!(loaded, *computed);
+    assert_eq!(reloaded, computed);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_invalid_files_are_replaced() {
+    let map = map();
+    let directory = cache_directory("invalid");
+    DistanceCache::new()
+        .with_directory(&directory)
+        .get(&map, (4, 0));
+    let file = fs::read_dir(&directory)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    fs::write(&file, b"CBSDIST1 truncated").unwrap();
+
+    let distances = DistanceCache::new()
+        .with_directory(&directory)
+        .get(&map, (4, 0));
+
+    assert_eq!(*distances, DistanceMap::new(&map, (4, 0)));
+    assert_eq!(read_distance_map(&file, &map).unwrap(), *distances);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_maps_with_other_obstacles_use_other_files() {
+    let directory = cache_directory("other_maps");
+    let other_map = Grid::new(5, 5, Grid::to_conditional_obstacles(vec![]), (0, 0));
+    for map in [map(), other_map] {
+        DistanceCache::new()
+            .with_directory(&directory)
+            .get(&map, (4, 0));
+    }
+
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_caches_on_several_threads_share_a_directory() {
+    let map = map();
+    let directory = cache_directory("threads");
+    let distances = std::thread::scope(|scope| {
+        let handles = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    DistanceCache::new()
+                        .with_directory(&directory)
+                        .get(&map, (4, 0))
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let files = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(files.len(), 1);
+    assert_eq!(read_distance_map(&files[0], &map).unwrap(), *distances[0]);
+    assert!(distances.iter().all(|d| *d == distances[0]));
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -75,10 +75,21 @@ use proptest::prelude::*;
         ((3, 0), f64::INFINITY),
     ],
 )]
-fn test_true_distance(#[case] grid: Grid, #[case] queries: Vec<(Location, f64)>) {
-    let td = TrueDistance::new(Rc::new(grid), (0, 0));
+#[case::terrain_costs(
+    Grid::try_from("type octile\nheight 2\nwidth 5\nmap\n.SSS.\n.....\n".to_string())
+        .unwrap()
+        .with_goal((4, 0)),
+    vec![
+        ((0, 0), 6.0),
+        ((1, 0), 5.0),
+        ((2, 0), 3.0),
+        ((4, 1), 1.0),
+    ],
+)]
+fn test_distance_map(#[case] grid: Grid, #[case] queries: Vec<(Location, f64)>) {
+    let distances = DistanceMap::new(&grid, grid.goal);
     for (query, true_distance) in queries {
-        let h = td.h(&LocationTime {
+        let h = distances.h(&LocationTime {
             location: query,
             time: 0,
         });
@@ -86,37 +97,22 @@ fn test_true_distance(#[case] grid: Grid, #[case] queries: Vec<(Location, f64)>)
     }
 }
 
-prop_compose! {
-    fn arb_location()(x in 0..5000, y in 0..5000) -> Location {
-        (x, y)
-    }
-}
-
 proptest! {
-    #[rstest]
-    fn test_dynamic_goal_manhattan_distance(
-        first_goal in arb_location(),
-        second_goal in arb_location(),
-        query in arb_location(),
-        time in -1..5000
+    #[test]
+    fn test_distance_map_without_obstacles_is_manhattan_distance(
+        (width, height) in (1..30, 1..30),
+        goal in (0..30, 0..30),
+        query in (-1..31, -1..31),
     ) {
-        let td = DynamicGoalManhattanDistance::new(first_goal);
-        let h = td.h(&LocationTime {
-            location: query,
-            time,
-        });
-        assert_eq!(
-            h,
-            (first_goal.0 - query.0).abs() as f64 + (first_goal.1 - query.1).abs() as f64
-        );
-        td.set_goal(second_goal);
-        let h = td.h(&LocationTime {
-            location: query,
-            time,
-        });
-        assert_eq!(
-            h,
-            (second_goal.0 - query.0).abs() as f64 + (second_goal.1 - query.1).abs() as f64
-        );
+        let goal = (goal.0 % width, goal.1 % height);
+        let grid = Grid::new(width, height, Grid::to_conditional_obstacles(vec![]), goal);
+        let manhattan = ManhattanDistance::new(Arc::new(grid.clone()));
+        let distances = DistanceMap::new(&grid, goal);
+        let loc_time = LocationTime::new(query, 0);
+        if grid.is_valid_location(&query, &query) {
+            prop_assert_eq!(distances.h(&loc_time), manhattan.h(&loc_time));
+        } else {
+            prop_assert_eq!(distances.h(&loc_time), f64::INFINITY);
+        }
     }
 }
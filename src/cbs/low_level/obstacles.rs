@@ -0,0 +1,219 @@
+//! The two kinds of obstacles of a [`super::Grid`]: the static ones, which
+//! are the same for every agent on a map, and the timed ones, which are
+//! mostly the constraints of the agent being planned. Costly terrain, which
+//! slows agents down rather than stopping them, is kept in a [`CostMap`].
+
+use super::LocationTime;
+
+/// The static obstacles of a map, one bit per cell.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub(crate) struct ObstacleMap {
+    width: i32,
+    height: i32,
+    bits: Vec<u64>,
+}
+
+impl ObstacleMap {
+    /// Cells outside of the map are ignored, as they cannot be entered anyway.
+    pub(crate) fn new(
+        width: i32,
+        height: i32,
+        cells: impl IntoIterator<Item = (i32, i32)>,
+    ) -> Self {
+        let num_cells = (width.max(0) * height.max(0)) as usize;
+        let mut map = ObstacleMap {
+            width,
+            height,
+            bits: vec![0; num_cells.div_ceil(64)],
+        };
+        for cell in cells {
+            map.insert(cell);
+        }
+        map
+    }
+
+    fn index(&self, (x, y): (i32, i32)) -> Option<usize> {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return None;
+        }
+        Some((y * self.width + x) as usize)
+    }
+
+    pub(crate) fn insert(&mut self, cell: (i32, i32)) {
+        if let Some(index) = self.index(cell) {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    pub(crate) fn contains(&self, cell: (i32, i32)) -> bool {
+        self.index(cell)
+            .is_some_and(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0)
+    }
+
+    /// A hash of the map that stays the same between runs and Rust
+    /// versions, unlike [`std::hash::Hash`] with the default hasher.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        fnv1a(
+            [self.width.to_le_bytes(), self.height.to_le_bytes()]
+                .into_iter()
+                .flatten()
+                .chain(self.bits.iter().flat_map(|word| word.to_le_bytes())),
+        )
+    }
+}
+
+/// The number of timesteps it takes to enter each cell of a map, 1 unless
+/// the cell's terrain is costly.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub(crate) struct CostMap {
+    width: i32,
+    height: i32,
+    /// Row by row, or empty if every cell costs 1.
+    costs: Vec<u32>,
+}
+
+impl CostMap {
+    /// Cells outside of the map are ignored.
+    pub(crate) fn new(
+        width: i32,
+        height: i32,
+        cells: impl IntoIterator<Item = ((i32, i32), u32)>,
+    ) -> Self {
+        let mut map = CostMap {
+            width,
+            height,
+            costs: Vec::new(),
+        };
+        for ((x, y), cost) in cells {
+            if x < 0 || x >= width || y < 0 || y >= height || cost == 1 {
+                continue;
+            }
+            if map.costs.is_empty() {
+                map.costs = vec![1; (width * height) as usize];
+            }
+            map.costs[(y * width + x) as usize] = cost;
+        }
+        map
+    }
+
+    pub(crate) fn get(&self, (x, y): (i32, i32)) -> u32 {
+        if self.costs.is_empty() || x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return 1;
+        }
+        self.costs[(y * self.width + x) as usize]
+    }
+
+    /// Whether every cell costs 1.
+    pub(crate) fn is_uniform(&self) -> bool {
+        self.costs.is_empty()
+    }
+
+    /// The highest cost of a cell.
+    pub(crate) fn max(&self) -> u32 {
+        self.costs.iter().copied().max().unwrap_or(1)
+    }
+
+    /// Like [`ObstacleMap::fingerprint`].
+    pub(crate) fn fingerprint(&self) -> u64 {
+        fnv1a(
+            [self.width.to_le_bytes(), self.height.to_le_bytes()]
+                .into_iter()
+                .flatten()
+                .chain(self.costs.iter().flat_map(|cost| cost.to_le_bytes())),
+        )
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// A location, and the location that moving into it is forbidden from, or
+/// `None` if it cannot be entered at all.
+type TimedObstacle = ((i32, i32), Option<(i32, i32)>);
+
+/// Timed obstacles, looked up by time first.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Hash)]
+pub(crate) struct ConstraintTable {
+    /// The obstacles at each timestep, sorted so that equal tables compare
+    /// and hash the same whatever order the obstacles were inserted in.
+    by_time: Vec<Vec<TimedObstacle>>,
+}
+
+impl ConstraintTable {
+    /// Panics if `loc_time` is before the first timestep.
+    pub(crate) fn insert(&mut self, loc_time: LocationTime, coming_from: Option<(i32, i32)>) {
+        let time = usize::try_from(loc_time.time).expect("timed obstacles are not in the past");
+        if self.by_time.len() <= time {
+            self.by_time.resize(time + 1, Vec::new());
+        }
+        let obstacles = &mut self.by_time[time];
+        let obstacle = (loc_time.location, coming_from);
+        if let Err(position) = obstacles.binary_search(&obstacle) {
+            obstacles.insert(position, obstacle);
+        }
+    }
+
+    pub(crate) fn extend(&mut self, other: &ConstraintTable) {
+        for (loc_time, coming_from) in other.iter() {
+            self.insert(loc_time, coming_from);
+        }
+    }
+
+    /// Whether moving from `prev_location` to `loc_time` is forbidden.
+    pub(crate) fn is_blocked(&self, loc_time: &LocationTime, prev_location: &(i32, i32)) -> bool {
+        let Ok(time) = usize::try_from(loc_time.time) else {
+            return false;
+        };
+        self.by_time.get(time).is_some_and(|obstacles| {
+            obstacles.iter().any(|(location, coming_from)| {
+                *location == loc_time.location
+                    && coming_from.is_none_or(|from| from == *prev_location)
+            })
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.by_time.is_empty()
+    }
+
+    /// The time of the last obstacle.
+    pub(crate) fn latest_time(&self) -> Option<i32> {
+        self.by_time.len().checked_sub(1).map(|time| time as i32)
+    }
+
+    /// The last time `location` cannot be entered at all.
+    pub(crate) fn latest_vertex_time(&self, location: (i32, i32)) -> Option<i32> {
+        self.iter()
+            .filter(|(loc_time, coming_from)| {
+                loc_time.location == location && coming_from.is_none()
+            })
+            .map(|(loc_time, _)| loc_time.time)
+            .last()
+    }
+
+    /// The obstacles by increasing time.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (LocationTime, Option<(i32, i32)>)> + '_ {
+        self.by_time
+            .iter()
+            .enumerate()
+            .flat_map(|(time, obstacles)| {
+                obstacles.iter().map(move |(location, coming_from)| {
+                    (LocationTime::new(*location, time as i32), *coming_from)
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests;
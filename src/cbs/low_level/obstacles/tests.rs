@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use super::*;
+use proptest::{collection::vec, prelude::*};
+use rstest::rstest;
+
+#[rstest]
+#[case(1, 1)]
+#[case(8, 8)]
+#[case(13, 7)]
+fn test_obstacle_map_holds_its_cells(#[case] width: i32, #[case] height: i32) {
+    let cells = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .collect::<Vec<_>>();
+    let is_obstacle = |(x, y): (i32, i32)| (x + y) % 3 == 0;
+    let map = ObstacleMap::new(
+        width,
+        height,
+        cells.iter().copied().filter(|cell| is_obstacle(*cell)),
+    );
+
+    for cell in cells {
+        assert_eq!(map.contains(cell), is_obstacle(cell));
+    }
+    assert!(!map.contains((-1, 0)));
+    assert!(!map.contains((width, 0)));
+    assert!(!map.contains((0, height)));
+}
+
+#[test]
+fn test_obstacle_map_ignores_cells_outside() {
+    let map = ObstacleMap::new(2, 2, [(2, 0), (0, -1), (1, 1)]);
+    assert_eq!(map, ObstacleMap::new(2, 2, [(1, 1)]));
+}
+
+#[test]
+fn test_vertex_and_edge_obstacles() {
+    let mut table = ConstraintTable::default();
+    table.insert(LocationTime::new((1, 1), 2), None);
+    table.insert(LocationTime::new((2, 1), 3), Some((1, 1)));
+
+    assert!(table.is_blocked(&LocationTime::new((1, 1), 2), &(0, 1)));
+    assert!(!table.is_blocked(&LocationTime::new((1, 1), 3), &(0, 1)));
+    assert!(table.is_blocked(&LocationTime::new((2, 1), 3), &(1, 1)));
+    assert!(!table.is_blocked(&LocationTime::new((2, 1), 3), &(2, 1)));
+    assert!(!table.is_blocked(&LocationTime::new((1, 1), -1), &(1, 1)));
+    assert_eq!(table.latest_time(), Some(3));
+    assert_eq!(table.latest_vertex_time((1, 1)), Some(2));
+    assert_eq!(table.latest_vertex_time((2, 1)), None);
+}
+
+proptest! {
+    #[test]
+    fn test_tables_do_not_depend_on_insertion_order(
+        obstacles in vec(((0..4, 0..4), 0..6, proptest::option::of((0..4, 0..4))), 0..20),
+    ) {
+        let mut table = ConstraintTable::default();
+        for (location, time, coming_from) in obstacles.iter() {
+            table.insert(LocationTime::new(*location, *time), *coming_from);
+        }
+        let mut reversed = ConstraintTable::default();
+        for (location, time, coming_from) in obstacles.iter().rev() {
+            reversed.insert(LocationTime::new(*location, *time), *coming_from);
+        }
+        prop_assert_eq!(&table, &reversed);
+
+        let expected = obstacles
+            .iter()
+            .map(|(location, time, coming_from)| (LocationTime::new(*location, *time), *coming_from))
+            .collect::<HashSet<_>>();
+        prop_assert_eq!(table.iter().collect::<HashSet<_>>(), expected);
+    }
+}
@@ -58,10 +58,18 @@ fn test_path_finding() {
         location: (0, 0),
         time: 0,
     };
-    let heuristic = heuristic::ManhattanDistance::new(Rc::new(grid.clone()));
+    let heuristic = heuristic::ManhattanDistance::new(Arc::new(grid.clone()));
     let h = heuristic.h(&start);
     let empty_cat = HashSet::new();
-    let start_node = PathFindingNode::new(start, 0.0, h as f64, &grid, &empty_cat, &heuristic);
+    let start_node = PathFindingNode::new(
+        start,
+        0.0,
+        h as f64,
+        &grid,
+        &empty_cat,
+        &heuristic,
+        i32::MAX,
+    );
     let solution = a_star(start_node).expect("No path found");
     assert_eq!(
         solution.path[solution.path.len() - 1].loc_time.location,
@@ -112,13 +120,13 @@ prop_compose! {
 #[derive(Debug, Clone)]
 enum HeuristicType {
     ManhattanDistance,
-    TrueDistance,
+    DistanceMap,
 }
 
 fn heuristic_strategy() -> impl Strategy<Value = HeuristicType> {
     prop_oneof![
         Just(HeuristicType::ManhattanDistance),
-        Just(HeuristicType::TrueDistance),
+        Just(HeuristicType::DistanceMap),
     ]
 }
 
@@ -126,12 +134,12 @@ proptest! {
     #[rstest]
     fn test_path_validity((grid, start) in empty_grid(100), heuristic_type in heuristic_strategy()) {
         let heuristic = match heuristic_type {
-            HeuristicType::ManhattanDistance => Box::new(heuristic::ManhattanDistance::new(Rc::new(grid.clone()))) as Box<dyn Heuristic<LocationTime>>,
-            HeuristicType::TrueDistance => Box::new(heuristic::TrueDistance::new(Rc::new(grid.clone()), start.location)) as Box<dyn Heuristic<LocationTime>>,
+            HeuristicType::ManhattanDistance => Box::new(heuristic::ManhattanDistance::new(Arc::new(grid.clone()))) as Box<dyn Heuristic<LocationTime>>,
+            HeuristicType::DistanceMap => Box::new(heuristic::DistanceMap::new(&grid, grid.goal)) as Box<dyn Heuristic<LocationTime>>,
         };
         let h = heuristic.h(&start);
         let empty_cat = HashSet::new();
-        let start_node = PathFindingNode::new(start, 0.0, h as f64, &grid, &empty_cat, heuristic.as_ref());
+        let start_node = PathFindingNode::new(start, 0.0, h as f64, &grid, &empty_cat, heuristic.as_ref(), i32::MAX);
         let solution = a_star(start_node).expect("No path found");
         let mut prev = solution.path[0];
         for node in solution.path.iter().skip(1) {
@@ -141,3 +149,122 @@ proptest! {
         assert_eq!(solution.path[solution.path.len() - 1].loc_time.location, grid.goal);
     }
 }
+
+#[test]
+fn test_unreachable_under_constraints() {
+    let grid = Grid::new(
+        2,
+        1,
+        Grid::to_conditional_obstacles(vec![
+            LocationTime::new((0, 0), 1),
+            LocationTime::new((1, 0), 1),
+        ]),
+        (1, 0),
+    );
+    let solver = AStarLowLevelSolver::new();
+    let path = solver.find_shortest_path(
+        "a".to_string(),
+        grid,
+        LocationTime::new((0, 0), 0),
+        &HashSet::new(),
+    );
+    assert!(path.is_none());
+}
+
+#[rstest]
+#[case::stays_in_swamp(vec![], vec![(0, 0), (1, 0), (1, 0), (2, 0)])]
+#[case::waits_until_it_can_stay(
+    vec![LocationTime::new((1, 0), 2)],
+    vec![(0, 0), (0, 0), (0, 0), (1, 0), (1, 0), (2, 0)],
+)]
+fn test_path_through_costly_terrain(
+    #[case] obstacles: Vec<LocationTime>,
+    #[case] expected: Vec<(i32, i32)>,
+) {
+    let mut grid = Grid::try_from("type octile\nheight 1\nwidth 3\nmap\n.S.\n".to_string())
+        .unwrap()
+        .with_goal((2, 0));
+    grid.add_obstacles(Grid::to_conditional_obstacles(obstacles));
+    let (path, _) = AStarLowLevelSolver::new()
+        .find_shortest_path(
+            "a".to_string(),
+            grid,
+            LocationTime::new((0, 0), 0),
+            &HashSet::new(),
+        )
+        .expect("path should exist");
+    assert_eq!(
+        path,
+        expected
+            .into_iter()
+            .enumerate()
+            .map(|(time, location)| LocationTime::new(location, time as i32))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_shared_solver_across_threads() {
+    let obstacles = Grid::to_conditional_obstacles(
+        (1..9)
+            .map(|y| LocationTime::new((5, y), -1))
+            .collect::<Vec<_>>(),
+    );
+    let grid = Grid::new(10, 10, obstacles, (9, 5));
+    let starts = (0..10).map(|y| (0, y)).collect::<Vec<_>>();
+    let path_length = |solver: &AStarLowLevelSolver, start: (i32, i32)| {
+        solver
+            .find_shortest_path(
+                "a".to_string(),
+                grid.clone(),
+                LocationTime::new(start, 0),
+                &HashSet::new(),
+            )
+            .expect("path should exist")
+            .0
+            .len()
+    };
+    let sequential = starts
+        .iter()
+        .map(|&start| path_length(&AStarLowLevelSolver::new(), start))
+        .collect::<Vec<_>>();
+    // all threads resume the same distance table, each from another start
+    let solver = Arc::new(AStarLowLevelSolver::new());
+    let concurrent = std::thread::scope(|scope| {
+        let handles = starts
+            .iter()
+            .map(|&start| {
+                let solver = Arc::clone(&solver);
+                scope.spawn(move || path_length(&solver, start))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("thread should not panic"))
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(concurrent, sequential);
+}
+
+#[test]
+fn test_grids_of_a_map_share_static_obstacles() {
+    let obstacles = Grid::to_conditional_obstacles(vec![
+        LocationTime::new((1, 0), -1),
+        LocationTime::new((2, 2), 3),
+    ]);
+    let map = Grid::new(3, 3, obstacles.clone(), (0, 0));
+    let mut grid = map.with_goal((2, 2));
+    let mut constraints = ConstraintTable::default();
+    constraints.insert(LocationTime::new((2, 1), 1), Some((2, 0)));
+    grid.add_constraints(&constraints);
+
+    assert!(Arc::ptr_eq(&map.static_obstacles, &grid.static_obstacles));
+    let mut timed_obstacles = ConstraintTable::default();
+    timed_obstacles.insert(LocationTime::new((2, 2), 3), None);
+    assert_eq!(*map.static_obstacles, ObstacleMap::new(3, 3, [(1, 0)]));
+    assert_eq!(map.timed_obstacles, timed_obstacles);
+    assert!(!grid.is_valid_location(&(1, 0), &(0, 0)));
+    assert!(!grid.is_valid_location_time(&LocationTime::new((2, 1), 1), &(2, 0)));
+    assert!(grid.is_valid_location_time(&LocationTime::new((2, 1), 1), &(1, 1)));
+    assert_eq!(grid.latest_goal_obstacle_time(), 3);
+}
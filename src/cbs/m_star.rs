@@ -0,0 +1,422 @@
+//! Wagner and Choset's M*: agents follow their individual optimal policies,
+//! and only the agents in the collision set of a joint state consider all
+//! their moves. Collisions found below a state are backpropagated to its
+//! ancestors, which are then expanded again with the larger collision set.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
+    error::Error,
+    hash::Hash,
+};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    low_level::{
+        heuristic::{DistanceMap, Heuristic},
+        Grid, LocationTime,
+    },
+    search::{a_star_until, AStarNode, SearchError},
+    CBSError, CBSInstance,
+};
+
+type CollisionSet = BTreeSet<usize>;
+
+pub struct MStar {
+    instance: CBSInstance,
+    cancellation: Option<CancellationToken>,
+    pub nodes_generated: usize,
+    /// Size of the largest collision set, i.e. of the largest group of agents searched jointly.
+    pub max_collision_set: usize,
+}
+
+impl MStar {
+    pub fn new(instance: CBSInstance) -> Self {
+        Self {
+            instance,
+            cancellation: None,
+            nodes_generated: 0,
+            max_collision_set: 0,
+        }
+    }
+
+    /// Makes [`Self::solve`] fail with [`SearchError::Cancelled`] once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        self.instance.require_unit_costs("M*")?;
+        let map = &self.instance.map;
+        let agents = &self.instance.agents;
+        let policies = Policies {
+            map,
+            goals: agents.iter().map(|agent| agent.goal).collect(),
+            distances: agents
+                .iter()
+                .map(|agent| DistanceMap::new(map, agent.goal))
+                .collect(),
+            horizon: map.timed_obstacles.latest_time().unwrap_or(-1),
+        };
+        let start = Configuration {
+            locations: agents.iter().map(|agent| agent.start).collect(),
+            time: 0,
+            goal_waits: vec![0; agents.len()],
+        };
+        if policies.h(&start).is_infinite() {
+            return Err(Box::new(SearchError::NotFound));
+        }
+        let graph = RefCell::new(CollisionGraph::default());
+        graph.borrow_mut().vertex(&start).cost = 0.0;
+        let root = MStarNode::new(start, CollisionSet::new(), 0.0, &policies, &graph);
+        let cancellation = self.cancellation.clone();
+        let solution = a_star_until(root, &|| {
+            cancellation
+                .as_ref()
+                .is_some_and(|cancellation| cancellation.is_cancelled())
+        })?;
+        self.nodes_generated = solution.nodes_generated as usize;
+        let graph = graph.borrow();
+        self.max_collision_set = graph
+            .vertices
+            .values()
+            .map(|vertex| vertex.collision_set.len())
+            .max()
+            .unwrap_or(0);
+        // re-opened ancestors break the chain of the search, so follow the back pointers instead
+        let goal = &solution
+            .path
+            .last()
+            .expect("path should not be empty")
+            .configuration;
+        let mut steps = vec![goal];
+        while let Some(previous) = &graph.vertices[*steps.last().unwrap()].back_pointer {
+            steps.push(previous);
+        }
+        steps.reverse();
+        let mut paths = HashMap::<&Agent, Path>::new();
+        for (i, agent) in agents.iter().enumerate() {
+            let mut path = steps.iter().map(|step| step.locations[i]).collect::<Path>();
+            while path.len() > 1 && path[path.len() - 2] == path[path.len() - 1] {
+                path.pop();
+            }
+            paths.insert(agent, path);
+        }
+        Ok(paths)
+    }
+}
+
+/// Where every agent is, along with the waits at the goal it owes if it leaves again.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Configuration {
+    locations: Vec<(i32, i32)>,
+    time: i32,
+    goal_waits: Vec<i32>,
+}
+
+struct Vertex {
+    collision_set: CollisionSet,
+    /// The vertices that have this one as a successor.
+    back_set: HashSet<Configuration>,
+    cost: f64,
+    back_pointer: Option<Configuration>,
+}
+
+impl Default for Vertex {
+    fn default() -> Self {
+        Self {
+            collision_set: CollisionSet::new(),
+            back_set: HashSet::new(),
+            cost: f64::INFINITY,
+            back_pointer: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CollisionGraph {
+    vertices: HashMap<Configuration, Vertex>,
+}
+
+impl CollisionGraph {
+    fn vertex(&mut self, configuration: &Configuration) -> &mut Vertex {
+        self.vertices.entry(configuration.clone()).or_default()
+    }
+
+    /// Adds `collision_set` to the collision set of `configuration` and of
+    /// its ancestors, collecting every vertex whose collision set grew.
+    /// Walks the ancestors with an explicit stack, as recursing through the
+    /// back sets of a long search could overflow the call stack.
+    fn backpropagate(
+        &mut self,
+        configuration: &Configuration,
+        collision_set: &CollisionSet,
+        reopened: &mut Vec<Configuration>,
+    ) {
+        let mut stack = vec![(configuration.clone(), collision_set.clone())];
+        while let Some((configuration, collision_set)) = stack.pop() {
+            let vertex = self.vertex(&configuration);
+            if collision_set.is_subset(&vertex.collision_set) {
+                continue;
+            }
+            vertex.collision_set.extend(&collision_set);
+            let collision_set = vertex.collision_set.clone();
+            stack.extend(
+                vertex
+                    .back_set
+                    .iter()
+                    .map(|previous| (previous.clone(), collision_set.clone())),
+            );
+            reopened.push(configuration);
+        }
+    }
+}
+
+/// The individual optimal policy of every agent, given by its distance table.
+struct Policies<'a> {
+    map: &'a Grid,
+    goals: Vec<(i32, i32)>,
+    distances: Vec<DistanceMap>,
+    /// The time of the last timed obstacle, after which time can be dropped from the state.
+    horizon: i32,
+}
+
+impl Policies<'_> {
+    fn distance(&self, agent: usize, location: (i32, i32)) -> f64 {
+        self.distances[agent].h(&LocationTime::new(location, 0))
+    }
+
+    fn h(&self, configuration: &Configuration) -> f64 {
+        configuration
+            .locations
+            .iter()
+            .enumerate()
+            .map(|(i, location)| self.distance(i, *location))
+            .sum()
+    }
+
+    /// The moves of `agent` from `location` that keep its goal reachable.
+    fn moves(&self, agent: usize, location: (i32, i32)) -> Vec<(i32, i32)> {
+        let (x, y) = location;
+        [(x, y), (x, y + 1), (x + 1, y), (x, y - 1), (x - 1, y)]
+            .into_iter()
+            .filter(|cell| {
+                self.map.is_valid_location(cell, &location)
+                    && self.distance(agent, *cell).is_finite()
+            })
+            .collect()
+    }
+
+    /// The next location of `agent` on its individual optimal path.
+    fn policy(&self, agent: usize, location: (i32, i32)) -> (i32, i32) {
+        if location == self.goals[agent] {
+            return location;
+        }
+        let distance = self.distance(agent, location);
+        self.moves(agent, location)
+            .into_iter()
+            .find(|cell| self.distance(agent, *cell) < distance)
+            .expect("a location with a finite distance should have a closer neighbour")
+    }
+}
+
+/// A joint state along with the collision set it is expanded with.
+#[derive(Clone)]
+struct MStarNode<'a> {
+    configuration: Configuration,
+    collision_set: CollisionSet,
+    g: f64,
+    h: f64,
+    policies: &'a Policies<'a>,
+    graph: &'a RefCell<CollisionGraph>,
+}
+
+impl<'a> MStarNode<'a> {
+    fn new(
+        configuration: Configuration,
+        collision_set: CollisionSet,
+        g: f64,
+        policies: &'a Policies<'a>,
+        graph: &'a RefCell<CollisionGraph>,
+    ) -> Self {
+        Self {
+            h: policies.h(&configuration),
+            configuration,
+            collision_set,
+            g,
+            policies,
+            graph,
+        }
+    }
+
+    /// The locations every agent may move to: all its moves if it is in
+    /// the collision set, otherwise only its policy.
+    fn options(&self) -> Vec<Vec<(i32, i32)>> {
+        self.configuration
+            .locations
+            .iter()
+            .enumerate()
+            .map(|(i, &location)| {
+                if self.collision_set.contains(&i) {
+                    self.policies.moves(i, location)
+                } else {
+                    vec![self.policies.policy(i, location)]
+                }
+            })
+            .collect()
+    }
+
+    /// The agents that collide with each other or with a timed obstacle
+    /// when moving to `next`.
+    fn collisions(&self, next: &[(i32, i32)]) -> CollisionSet {
+        let current = &self.configuration.locations;
+        let mut collisions = CollisionSet::new();
+        for i in 0..next.len() {
+            let valid = self.policies.map.is_valid_location_time(
+                &LocationTime::new(next[i], self.configuration.time + 1),
+                &current[i],
+            );
+            if !valid {
+                collisions.insert(i);
+            }
+            for j in 0..i {
+                if next[i] == next[j] || (next[i] == current[j] && next[j] == current[i]) {
+                    collisions.insert(i);
+                    collisions.insert(j);
+                }
+            }
+        }
+        collisions
+    }
+
+    /// Applies `next` to the configuration, returning the successor and the cost of the move.
+    fn successor(&self, next: Vec<(i32, i32)>) -> (Configuration, f64) {
+        let mut successor = self.configuration.clone();
+        let mut cost = 0.0;
+        for (i, &location) in next.iter().enumerate() {
+            let goal = self.policies.goals[i];
+            if location == goal && self.configuration.locations[i] == goal {
+                successor.goal_waits[i] += 1;
+            } else {
+                cost += 1.0 + successor.goal_waits[i] as f64;
+                successor.goal_waits[i] = 0;
+            }
+        }
+        successor.locations = next;
+        successor.time = (successor.time + 1).min(self.policies.horizon + 1);
+        (successor, cost)
+    }
+}
+
+impl PartialEq for MStarNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.configuration == other.configuration && self.collision_set == other.collision_set
+    }
+}
+
+impl Eq for MStarNode<'_> {}
+
+impl Hash for MStarNode<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.configuration.hash(state);
+        self.collision_set.hash(state);
+    }
+}
+
+impl AStarNode<'_> for MStarNode<'_> {
+    fn g(&self) -> f64 {
+        self.g
+    }
+
+    fn h(&self) -> f64 {
+        self.h
+    }
+
+    fn expand(&self) -> Option<Vec<Box<Self>>> {
+        let mut graph = self.graph.borrow_mut();
+        let mut children = Vec::new();
+        let mut reopened = Vec::new();
+        for next in cartesian_product(&self.options()) {
+            let collisions = self.collisions(&next);
+            let (successor, cost) = self.successor(next);
+            let vertex = graph.vertex(&successor);
+            vertex.back_set.insert(self.configuration.clone());
+            vertex.collision_set.extend(&collisions);
+            let collision_set = vertex.collision_set.clone();
+            graph.backpropagate(&self.configuration, &collision_set, &mut reopened);
+            if !collisions.is_empty() {
+                continue;
+            }
+            let g = self.g + cost;
+            let vertex = graph.vertex(&successor);
+            if g < vertex.cost {
+                vertex.cost = g;
+                vertex.back_pointer = Some(self.configuration.clone());
+                children.push(Box::new(Self::new(
+                    successor,
+                    collision_set,
+                    g,
+                    self.policies,
+                    self.graph,
+                )));
+            }
+        }
+        for configuration in reopened {
+            let vertex = graph.vertex(&configuration);
+            children.push(Box::new(Self::new(
+                configuration,
+                vertex.collision_set.clone(),
+                vertex.cost,
+                self.policies,
+                self.graph,
+            )));
+        }
+        Some(children)
+    }
+
+    fn is_goal(&self) -> bool {
+        self.configuration.time > self.policies.horizon
+            && self.configuration.locations == self.policies.goals
+    }
+
+    fn id(&self) -> String {
+        format!("{:?} {:?}", self.configuration, self.collision_set)
+    }
+
+    fn tie_breaker(&self, other: &Self) -> std::cmp::Ordering {
+        self.collision_set
+            .len()
+            .cmp(&other.collision_set.len())
+            .then_with(|| {
+                self.configuration
+                    .locations
+                    .cmp(&other.configuration.locations)
+            })
+    }
+}
+
+/// Every combination of one element of each of `options`.
+fn cartesian_product(options: &[Vec<(i32, i32)>]) -> Vec<Vec<(i32, i32)>> {
+    options
+        .iter()
+        .fold(vec![Vec::new()], |combinations, option| {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    option.iter().map(move |&location| {
+                        let mut combination = combination.clone();
+                        combination.push(location);
+                        combination
+                    })
+                })
+                .collect()
+        })
+}
+
+#[cfg(test)]
+mod tests;
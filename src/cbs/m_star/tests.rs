@@ -0,0 +1,159 @@
+use proptest::{prelude::*, sample::subsequence};
+use rstest::rstest;
+
+use super::*;
+use crate::cbs::operator_decomposition::OperatorDecomposition;
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+fn empty_grid(width: i32, height: i32) -> Grid {
+    Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(Vec::new()),
+        (0, 0),
+    )
+}
+
+fn sum_of_costs(paths: &HashMap<&Agent, Path>) -> usize {
+    paths.values().map(|path| path.len() - 1).sum()
+}
+
+#[rstest]
+#[case::independent(vec![agent("a", (0, 0), (0, 3)), agent("b", (3, 3), (3, 0))], 6, 0)]
+#[case::crossing(vec![agent("a", (0, 1), (2, 1)), agent("b", (1, 0), (1, 2))], 5, 2)]
+#[case::swapping(
+    vec![agent("a", (0, 0), (3, 0)), agent("b", (3, 0), (0, 0))],
+    8,
+    2
+)]
+#[case::already_at_goal(vec![agent("a", (1, 1), (1, 1)), agent("b", (0, 1), (2, 1))], 4, 2)]
+fn test_optimal_cost(
+    #[case] agents: Vec<Agent>,
+    #[case] expected: usize,
+    #[case] expected_collision_set: usize,
+) {
+    let mut m_star = MStar::new(CBSInstance::new(empty_grid(4, 4), agents));
+    let paths = m_star.solve().unwrap();
+    assert_eq!(sum_of_costs(&paths), expected);
+    for (agent, path) in &paths {
+        assert_eq!(path.first(), Some(&agent.start));
+        assert_eq!(path.last(), Some(&agent.goal));
+    }
+    assert_eq!(m_star.max_collision_set, expected_collision_set);
+}
+
+#[test]
+fn test_only_couples_colliding_agents() {
+    let agents = vec![
+        agent("a", (0, 1), (2, 1)),
+        agent("b", (1, 0), (1, 2)),
+        agent("far", (5, 0), (5, 5)),
+    ];
+    let mut m_star = MStar::new(CBSInstance::new(empty_grid(6, 6), agents));
+    let paths = m_star.solve().unwrap();
+    assert_eq!(sum_of_costs(&paths), 10);
+    assert_eq!(m_star.max_collision_set, 2);
+}
+
+#[test]
+fn test_waits_for_timed_obstacle() {
+    let grid = Grid::new(
+        3,
+        1,
+        Grid::to_conditional_obstacles(vec![LocationTime::new((1, 0), 1)]),
+        (0, 0),
+    );
+    let mut m_star = MStar::new(CBSInstance::new(grid, vec![agent("a", (0, 0), (2, 0))]));
+    let paths = m_star.solve().unwrap();
+    assert_eq!(
+        paths.values().next().unwrap(),
+        &vec![(0, 0), (0, 0), (1, 0), (2, 0)]
+    );
+}
+
+#[test]
+fn test_cancelled() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let mut m_star = MStar::new(CBSInstance::new(
+        empty_grid(4, 4),
+        vec![agent("a", (0, 0), (3, 3))],
+    ))
+    .with_cancellation(cancellation);
+    let error = m_star.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::Cancelled)
+    ));
+}
+
+#[test]
+fn test_backpropagates_along_long_chain() {
+    let configuration = |time| Configuration {
+        locations: vec![(0, 0)],
+        time,
+        goal_waits: vec![0],
+    };
+    let mut graph = CollisionGraph::default();
+    for time in 1..100_000 {
+        graph
+            .vertex(&configuration(time))
+            .back_set
+            .insert(configuration(time - 1));
+    }
+    let mut reopened = Vec::new();
+    graph.backpropagate(
+        &configuration(99_999),
+        &CollisionSet::from([0]),
+        &mut reopened,
+    );
+    assert_eq!(reopened.len(), 100_000);
+    assert_eq!(
+        graph.vertex(&configuration(0)).collision_set,
+        CollisionSet::from([0])
+    );
+}
+
+fn open_grid_instance() -> impl Strategy<Value = Vec<Agent>> {
+    let cells = (0..4)
+        .flat_map(|x| (0..4).map(move |y| (x, y)))
+        .collect::<Vec<_>>();
+    (2..5usize)
+        .prop_flat_map(move |n| {
+            (
+                subsequence(cells.clone(), n).prop_shuffle(),
+                subsequence(cells.clone(), n).prop_shuffle(),
+            )
+        })
+        .prop_map(|(starts, goals)| {
+            starts
+                .into_iter()
+                .zip(goals)
+                .enumerate()
+                .map(|(i, (start, goal))| agent(&i.to_string(), start, goal))
+                .collect()
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+    #[test]
+    fn test_same_cost_as_joint_search(agents in open_grid_instance()) {
+        let mut oracle = OperatorDecomposition::new(CBSInstance::new(empty_grid(4, 4), agents.clone()));
+        let expected = sum_of_costs(&oracle.solve().unwrap());
+        let mut m_star = MStar::new(CBSInstance::new(empty_grid(4, 4), agents));
+        let paths = m_star.solve().unwrap();
+        prop_assert_eq!(sum_of_costs(&paths), expected);
+        for (agent, path) in &paths {
+            prop_assert_eq!(path.first(), Some(&agent.start));
+            prop_assert_eq!(path.last(), Some(&agent.goal));
+        }
+    }
+}
@@ -1,171 +1,385 @@
-use std::collections::HashMap;
-
-use cached::proc_macro::cached;
-use cached::{SizedCache, UnboundCache};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
 
 use super::{
     high_level::Agent,
-    low_level::{Grid, LocationTime},
+    low_level::{heuristic::DistanceMap, obstacles::ConstraintTable, Grid, LocationTime},
     search::bfs,
 };
 
-struct MDDNode<T> {
-    location: T,
-    goal_reachable: bool,
-    last_visited_level: i32,
-    level: i32,
-}
+/// The cells of an MDD, by level.
+pub(crate) type LevelSets = Vec<Vec<(i32, i32)>>;
+
+/// A cell of an MDD, and how many more timesteps the agent has to stay in
+/// it before it may move on, see [`Grid::arrival_time`].
+type MDDState = ((i32, i32), i32);
 
 #[derive(Debug, Clone)]
 pub(crate) enum MDDError {
     GoalUnreachable,
 }
 
-#[cached(
-    type = "SizedCache<String, Result<Vec<Vec<(i32, i32)>>, MDDError>>",
-    create = "{ SizedCache::with_size(10000) }",
-    convert = r#"{ format!("{:?}{:?}{:?}", agent, scenario, c) }"#
-)]
 pub(crate) fn mdd(
     agent: &Agent,
     scenario: &Grid,
     c: i32,
 ) -> Result<Vec<Vec<(i32, i32)>>, MDDError> {
-    let mut mdd = Vec::<Vec<(i32, i32)>>::new();
-    for _ in 0..c + 1 {
-        mdd.push(Vec::<(i32, i32)>::new());
-    }
-    let mut nodes = HashMap::<(i32, i32), MDDNode<(i32, i32)>>::new();
-    let goal_node = MDDNode {
-        location: agent.goal.clone(),
-        goal_reachable: true,
-        last_visited_level: -1,
-        level: 0,
-    };
-    nodes.insert(agent.goal.clone(), goal_node);
-    bfs(
-        &mut nodes,
-        agent.goal.clone(),
-        c,
-        |nodes, node, level| {
-            let cur_node = nodes.get_mut(&node).unwrap();
-
-            cur_node.goal_reachable = true;
-            cur_node.level = level;
-            false
-        },
-        |nodes, node_location, level| {
-            let node = nodes.get_mut(&node_location).unwrap();
-            let mut neighbours = Vec::<(i32, i32)>::new();
-            let mut neighbouring_cells = vec![
-                (node.location.0 - 1, node.location.1),
-                (node.location.0 + 1, node.location.1),
-                (node.location.0, node.location.1 - 1),
-                (node.location.0, node.location.1 + 1),
-            ];
-            neighbouring_cells.retain(|cell| {
-                // scenario.is_valid_location_time(
-                //     &LocationTime::new(*cell, c - (level + 1)),
-                //     &node_location,
-                // )
-                scenario.is_valid_location(cell, &node_location)
-            });
-            for neighbour in neighbouring_cells {
-                if nodes.contains_key(&neighbour) {
-                    continue;
-                }
-                let neighbour_node = MDDNode {
-                    location: neighbour.clone(),
-                    goal_reachable: false,
-                    last_visited_level: -1,
-                    level: 0,
-                };
-
-                nodes.insert(neighbour.clone(), neighbour_node);
-                neighbours.push(neighbour);
-            }
-            neighbours
-        },
-    );
-    if !nodes[&agent.start].goal_reachable {
+    let distances = DistanceMap::new(scenario, agent.goal);
+    if distances
+        .distance(agent.start)
+        .is_none_or(|distance| distance > c)
+    {
         return Err(MDDError::GoalUnreachable);
     }
+    let reaches_goal_in_time = |(cell, stay): MDDState, level: i32| {
+        distances
+            .distance(cell)
+            .is_some_and(|distance| level + stay + distance <= c)
+    };
+    let successors = |(cell, stay): MDDState, level: i32| -> Vec<MDDState> {
+        if stay > 0 {
+            let stays = scenario.is_valid_location_time(&LocationTime::new(cell, level + 1), &cell);
+            return if stays {
+                vec![(cell, stay - 1)]
+            } else {
+                vec![]
+            };
+        }
+        let (x, y) = cell;
+        [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1), (x, y)]
+            .into_iter()
+            .filter(|next| {
+                scenario.is_valid_location_time(&LocationTime::new(*next, level + 1), &cell)
+            })
+            .map(|next| match next == cell {
+                true => (next, 0),
+                false => (next, scenario.terrain_cost(next) as i32 - 1),
+            })
+            .filter(|state| reaches_goal_in_time(*state, level + 1))
+            .collect()
+    };
+    let mut levels = vec![Vec::<MDDState>::new(); (c + 1) as usize];
+    let mut last_visited_levels = HashMap::<MDDState, i32>::new();
     bfs(
-        &mut nodes,
-        agent.start.clone(),
+        &mut last_visited_levels,
+        (agent.start, 0),
         c,
-        |nodes, node, level| {
-            let cur_node = nodes.get_mut(&node).unwrap();
-            if cur_node.last_visited_level == level {
+        |last_visited_levels, state, level| {
+            if last_visited_levels.insert(state, level) == Some(level)
+                || !reaches_goal_in_time(state, level)
+            {
                 return true;
             }
-            cur_node.last_visited_level = level;
-            if cur_node.goal_reachable && cur_node.level + level <= c {
-                mdd[level as usize].push(node);
-            }
+            levels[level as usize].push(state);
             false
         },
-        |nodes, node_location, level| {
-            let node = nodes.get_mut(&node_location).unwrap();
-            let mut neighbours = Vec::<(i32, i32)>::new();
-            let mut neighbouring_cells = vec![
-                (node.location.0 - 1, node.location.1),
-                (node.location.0 + 1, node.location.1),
-                (node.location.0, node.location.1 - 1),
-                (node.location.0, node.location.1 + 1),
-                (node.location.0, node.location.1),
-            ];
-            neighbouring_cells.retain(|cell| {
-                scenario
-                    .is_valid_location_time(&LocationTime::new(*cell, level + 1), &node_location)
-            });
-            for neighbour in neighbouring_cells {
-                if !nodes.contains_key(&neighbour) {
-                    continue;
+        |_, state, level| successors(state, level),
+    );
+    // keep the states on a path that is at the goal at level `c`
+    levels[c as usize].retain(|state| *state == (agent.goal, 0));
+    for level in (0..c).rev() {
+        let next = levels[level as usize + 1]
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        levels[level as usize].retain(|state| {
+            successors(*state, level)
+                .iter()
+                .any(|successor| next.contains(successor))
+        });
+    }
+    Ok(levels
+        .into_iter()
+        .map(|states| {
+            let mut cells = Vec::<(i32, i32)>::with_capacity(states.len());
+            for (cell, _) in states {
+                if !cells.contains(&cell) {
+                    cells.push(cell);
                 }
-                neighbours.push(neighbour);
             }
-            neighbours
-        },
-    );
-    Ok(mdd)
+            cells
+        })
+        .collect())
 }
 
-#[cached(
-    type = "SizedCache<String, Vec<Vec<((i32, i32), (i32, i32))>>>",
-    create = "{ SizedCache::with_size(10000) }",
-    convert = r#"{ format!("{:?}{:?}{:?}", mdd1, mdd2, c) }"#
-)]
-pub(crate) fn merge_mdds(
-    mdd1: &Vec<Vec<(i32, i32)>>,
-    mdd2: &Vec<Vec<(i32, i32)>>,
-    c: i32,
-) -> Vec<Vec<((i32, i32), (i32, i32))>> {
-    let mut mdd = Vec::<Vec<((i32, i32), (i32, i32))>>::new();
-    for _ in 0..c + 1 {
-        mdd.push(Vec::<((i32, i32), (i32, i32))>::new());
-    }
-    for level in 0..c + 1 {
-        let mdd1_level = mdd1
-            .get(level as usize)
-            .or_else(|| mdd1.last())
-            .expect("mdd1 should not be empty");
-        let mdd2_level = mdd2
-            .get(level as usize)
-            .or_else(|| mdd2.last())
-            .expect("mdd2 should not be empty");
-        for node1 in mdd1_level {
-            for node2 in mdd2_level {
-                let joint_loc = (node1.clone(), node2.clone());
-                if node1 != node2
-                    && !mdd[(level - 1).max(0) as usize].contains(&(joint_loc.1, joint_loc.0))
-                {
-                    mdd[level as usize].push(joint_loc);
-                }
+/// The MDD of the same cost as `parent` under the obstacles of `scenario`,
+/// which must include those `parent` was built under: the cells of `parent`
+/// that can still be reached from the start and still lead to the goal. The
+/// cells of a level may be in another order than in [`mdd`]. Only for maps
+/// without terrain costs, as the agents move a cell per level.
+pub(crate) fn restrict_mdd(parent: &LevelSets, scenario: &Grid) -> LevelSets {
+    let mut mdd = LevelSets::with_capacity(parent.len());
+    let Some(start) = parent.first() else {
+        return mdd;
+    };
+    mdd.push(start.clone());
+    for (level, cells) in parent.iter().enumerate().skip(1) {
+        let previous = mdd[level - 1].iter().copied().collect::<HashSet<_>>();
+        let reachable = cells
+            .iter()
+            .copied()
+            .filter(|&(x, y)| {
+                [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .iter()
+                    .any(|from| {
+                        previous.contains(from)
+                            && scenario.is_valid_location_time(
+                                &LocationTime::new((x, y), level as i32),
+                                from,
+                            )
+                    })
+            })
+            .collect();
+        mdd.push(reachable);
+    }
+    prune_dead_ends(&mut mdd, scenario);
+    mdd
+}
+
+/// Removes the cells from which no cell of the next level can be entered,
+/// from the last level back to the start, so that every cell is on a path
+/// to the goal. Constraints can leave such dead ends behind, as the cells
+/// are only checked against the obstacles on the way from the start.
+fn prune_dead_ends(mdd: &mut LevelSets, scenario: &Grid) {
+    for level in (0..mdd.len().saturating_sub(1)).rev() {
+        let next = mdd[level + 1].iter().copied().collect::<HashSet<_>>();
+        mdd[level].retain(|&(x, y)| {
+            [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                .iter()
+                .any(|to| {
+                    next.contains(to)
+                        && scenario.is_valid_location_time(
+                            &LocationTime::new(*to, level as i32 + 1),
+                            &(x, y),
+                        )
+                })
+        });
+    }
+}
+
+/// How often a [`MDDCache`] found an MDD, and how it built the others.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MDDCacheStatistics {
+    pub hits: usize,
+    /// Built from the MDD of the parent conflict tree node.
+    pub incremental: usize,
+    /// Built from scratch.
+    pub built: usize,
+}
+
+impl MDDCacheStatistics {
+    pub fn lookups(&self) -> usize {
+        self.hits + self.incremental + self.built
+    }
+
+    /// The share of lookups that were hits, 0 without lookups.
+    pub fn hit_rate(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+struct MDDKey {
+    agent: String,
+    constraints: ConstraintTable,
+    cost: i32,
+}
+
+impl MDDKey {
+    fn new(agent: &Agent, constraints: &ConstraintTable, cost: i32) -> Self {
+        MDDKey {
+            agent: agent.id.clone(),
+            constraints: constraints.clone(),
+            cost,
+        }
+    }
+}
+
+/// The MDDs of a search, by agent, constraints and cost. Like
+/// the keys, the MDDs do not depend on the map, so a cache must only be
+/// used on a single map.
+#[derive(Debug, Default)]
+pub(crate) struct MDDCache {
+    mdds: RwLock<HashMap<MDDKey, Result<Arc<LevelSets>, MDDError>>>,
+    hits: AtomicUsize,
+    incremental: AtomicUsize,
+    built: AtomicUsize,
+}
+
+impl MDDCache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// The MDD of cost `cost` of `agent` on `map` under `constraints`. If
+    /// `parent_constraints` are `constraints` without the one added last and
+    /// the MDD of the same cost under them is known, it is restricted to the
+    /// new constraint instead of building the MDD from scratch.
+    pub(crate) fn get(
+        &self,
+        agent: &Agent,
+        map: &Grid,
+        constraints: &ConstraintTable,
+        parent_constraints: Option<&ConstraintTable>,
+        cost: i32,
+    ) -> Result<Arc<LevelSets>, MDDError> {
+        let key = MDDKey::new(agent, constraints, cost);
+        if let Some(mdd) = self.lookup(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return mdd;
+        }
+        let parent = parent_constraints
+            .filter(|_| !map.has_terrain_costs())
+            .and_then(|parent_constraints| {
+                self.lookup(&MDDKey::new(agent, parent_constraints, cost))
+            });
+        let mut scenario = map.clone();
+        scenario.add_constraints(constraints);
+        // built without the lock, as other threads may want other MDDs
+        let mdd = match parent {
+            Some(parent) => {
+                self.incremental.fetch_add(1, Ordering::Relaxed);
+                parent.map(|parent| Arc::new(restrict_mdd(&parent, &scenario)))
+            }
+            None => {
+                self.built.fetch_add(1, Ordering::Relaxed);
+                mdd(agent, &scenario, cost).map(Arc::new)
             }
+        };
+        let mut mdds = self.mdds.write().expect("lock is not poisoned");
+        mdds.entry(key).or_insert(mdd).clone()
+    }
+
+    fn lookup(&self, key: &MDDKey) -> Option<Result<Arc<LevelSets>, MDDError>> {
+        self.mdds
+            .read()
+            .expect("lock is not poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    pub(crate) fn statistics(&self) -> MDDCacheStatistics {
+        MDDCacheStatistics {
+            hits: self.hits.load(Ordering::Relaxed),
+            incremental: self.incremental.load(Ordering::Relaxed),
+            built: self.built.load(Ordering::Relaxed),
         }
     }
-    mdd
+}
+
+/// Searches the cross product of `mdds` for paths, one per MDD, without
+/// vertex or swap conflicts. Agents whose MDD is shorter wait at their goal.
+/// Agents may leave costly cells early, so with terrain costs, a joint path
+/// is only a relaxation of the agents' movement.
+pub(crate) fn joint_mdd_paths(
+    mdds: &[&Vec<Vec<(i32, i32)>>],
+    scenario: &Grid,
+) -> Option<Vec<Vec<(i32, i32)>>> {
+    let levels = mdds
+        .iter()
+        .map(|mdd| {
+            mdd.iter()
+                .map(|level| level.iter().copied().collect::<HashSet<_>>())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let depth = mdds.iter().map(|mdd| mdd.len()).max()?.checked_sub(1)?;
+    let start = mdds
+        .iter()
+        .map(|mdd| mdd.first().and_then(|level| level.first()).copied())
+        .collect::<Option<Vec<_>>>()?;
+    let mut dead_ends = HashSet::<(usize, Vec<(i32, i32)>)>::new();
+    let mut joint_path = vec![start];
+    if !extend_joint_path(&levels, scenario, depth, &mut joint_path, &mut dead_ends) {
+        return None;
+    }
+    Some(
+        (0..mdds.len())
+            .map(|i| joint_path.iter().map(|locations| locations[i]).collect())
+            .collect(),
+    )
+}
+
+fn extend_joint_path(
+    levels: &[Vec<HashSet<(i32, i32)>>],
+    scenario: &Grid,
+    depth: usize,
+    joint_path: &mut Vec<Vec<(i32, i32)>>,
+    dead_ends: &mut HashSet<(usize, Vec<(i32, i32)>)>,
+) -> bool {
+    let level = joint_path.len() - 1;
+    if level == depth {
+        return true;
+    }
+    let current = joint_path[level].clone();
+    if dead_ends.contains(&(level, current.clone())) {
+        return false;
+    }
+    let options = current
+        .iter()
+        .zip(levels)
+        .map(
+            |(&(x, y), agent_levels)| match agent_levels.get(level + 1) {
+                Some(next) => [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .into_iter()
+                    .filter(|cell| {
+                        next.contains(cell)
+                            && scenario.is_valid_location_time(
+                                &LocationTime::new(*cell, level as i32 + 1),
+                                &(x, y),
+                            )
+                    })
+                    .collect(),
+                None => vec![(x, y)],
+            },
+        )
+        .collect::<Vec<Vec<_>>>();
+    let mut successors = Vec::new();
+    collect_successors(&current, &options, &mut Vec::new(), &mut successors);
+    for successor in successors {
+        joint_path.push(successor);
+        if extend_joint_path(levels, scenario, depth, joint_path, dead_ends) {
+            return true;
+        }
+        joint_path.pop();
+    }
+    dead_ends.insert((level, current));
+    false
+}
+
+/// Collects every combination of `options` that has no vertex or swap
+/// conflict when moving from `current`.
+fn collect_successors(
+    current: &[(i32, i32)],
+    options: &[Vec<(i32, i32)>],
+    next: &mut Vec<(i32, i32)>,
+    successors: &mut Vec<Vec<(i32, i32)>>,
+) {
+    let i = next.len();
+    if i == options.len() {
+        successors.push(next.clone());
+        return;
+    }
+    for &cell in &options[i] {
+        let conflicts = next
+            .iter()
+            .enumerate()
+            .any(|(j, &other)| other == cell || (other == current[i] && current[j] == cell));
+        if conflicts {
+            continue;
+        }
+        next.push(cell);
+        collect_successors(current, options, next, successors);
+        next.pop();
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,7 @@
 use crate::cbs::low_level::LocationTime;
 
 use super::*;
+use proptest::{collection::vec, option, prelude::*};
 use rstest::rstest;
 #[rstest]
 #[case::simple(
@@ -64,6 +65,31 @@ Ok(vec![
     vec![(5,5)],
 ])
 )]
+#[case::stays_in_swamp(
+Grid::try_from("type octile\nheight 2\nwidth 3\nmap\n.S.\n...\n".to_string()).unwrap(),
+(0, 0),
+(2, 0),
+3,
+Ok(vec![
+    vec![(0,0)],
+    vec![(1,0)],
+    vec![(1,0)],
+    vec![(2,0)],
+])
+)]
+#[case::swamp_or_detour(
+Grid::try_from("type octile\nheight 2\nwidth 3\nmap\n.S.\n...\n".to_string()).unwrap(),
+(0, 0),
+(2, 0),
+4,
+Ok(vec![
+    vec![(0,0)],
+    vec![(1,0), (0,1), (0,0)],
+    vec![(1,0), (1,1)],
+    vec![(2,0), (1,0), (2,1)],
+    vec![(2,0)],
+])
+)]
 #[case::goal_unreachable_error(
 crate::cbs::low_level::Grid::new(10, 10, Grid::to_conditional_obstacles(vec![]), (0, 0)),
 (0, 0),
@@ -95,81 +121,128 @@ fn test_mdd(
 }
 
 #[rstest]
-#[case::simple(
-    vec![
-        vec![(0,0)],
-        vec![(1,0),(0,1)],
-        vec![(1,1)],
-    ],
-    vec![
-        vec![(1,1)],
-        vec![(1,0),(0,1)],
-        vec![(0,0)],
-    ],
-    vec![
-        vec![((0,0),(1,1))],
-        vec![((1,0),(0,1)),((0,1),(1,0))],
-        vec![((1,1),(0,0))],
-    ],
+#[case::crossing_waits(
+    vec![vec![(0,1)], vec![(1,1)], vec![(2,1)]],
+    vec![vec![(1,0)], vec![(1,0),(1,1)], vec![(1,1)], vec![(1,2)]],
+    Some(vec![
+        vec![(0,1), (1,1), (2,1), (2,1)],
+        vec![(1,0), (1,0), (1,1), (1,2)],
+    ]),
 )]
-#[case::empty_joint_mdd(
-    vec![
-        vec![(0,0)],
-        vec![(1,0)],
-        vec![(1,1)],
-    ],
-    vec![
-        vec![(1,1)],
-        vec![(1,0)],
-        vec![(0,0)],
-    ],
-    vec![
-        vec![((0,0),(1,1))],
-        vec![],
-        vec![((1,1),(0,0))],
-    ],
+#[case::swap(
+    vec![vec![(0,0)], vec![(1,0)]],
+    vec![vec![(1,0)], vec![(0,0)]],
+    None,
 )]
-#[case::different_lengths(
-    vec![
-        vec![(0,0)],
-        vec![(1,0), (0,1)],
-        vec![(1,1)],
-        vec![(1,2)],
-    ],
-    vec![
-        vec![(1,1)],
-        vec![(1,0)],
-        vec![(0,0)],
-    ],
-    vec![
-        vec![((0,0),(1,1))],
-        vec![((0,1), (1,0))],
-        vec![((1,1),(0,0))],
-        vec![((1,2),(0,0))],
-    ],
+#[case::blocked_goal(
+    vec![vec![(0,0)], vec![(1,0)]],
+    vec![vec![(2,0)], vec![(1,0)], vec![(0,0)]],
+    None,
 )]
-#[case::empty_mid_layers(
-    vec![
-        vec![(0,0)],
-        vec![(0,1)],
-        vec![(1,1)],
-    ],
-    vec![
-        vec![(1,1)],
-        vec![(0,1)],
-        vec![(0,0)],
-    ],
-    vec![
-        vec![((0,0),(1,1))],
-        vec![],
-        vec![((1,1),(0,0))],
-    ],
-)]
-fn test_merge_mdds(
+fn test_joint_mdd_paths(
     #[case] mdd1: Vec<Vec<(i32, i32)>>,
     #[case] mdd2: Vec<Vec<(i32, i32)>>,
-    #[case] expected: Vec<Vec<((i32, i32), (i32, i32))>>,
+    #[case] expected: Option<Vec<Vec<(i32, i32)>>>,
 ) {
-    let merged = super::merge_mdds(&mdd1, &mdd2, (mdd1.len().max(mdd2.len()) - 1) as i32);
-    assert_eq!(merged, expected);
+    let scenario = Grid::new(3, 3, Grid::to_conditional_obstacles(vec![]), (0, 0));
+    assert_eq!(super::joint_mdd_paths(&[&mdd1, &mdd2], &scenario), expected);
+}
+
+fn sorted(mdd: &LevelSets) -> LevelSets {
+    mdd.iter()
+        .map(|level| {
+            let mut level = level.clone();
+            level.sort();
+            level
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn test_restricted_mdd_is_the_mdd_under_the_new_constraint(
+        start in (0..5, 0..5),
+        goal in (0..5, 0..5),
+        slack in 0..3,
+        constraints in vec(((0..5, 0..5), 1..10, option::of((0..5, 0..5))), 1..6),
+    ) {
+        let map = Grid::new(5, 5, Grid::to_conditional_obstacles(vec![]), (0, 0));
+        let agent = crate::cbs::high_level::Agent { id: "a".to_string(), start, goal };
+        let cost = (start.0 - goal.0).abs() + (start.1 - goal.1).abs() + slack;
+        let mut parent_scenario = map.clone();
+        let mut scenario = map.clone();
+        for (i, (location, time, coming_from)) in constraints.iter().enumerate() {
+            let mut table = ConstraintTable::default();
+            table.insert(LocationTime::new(*location, *time), *coming_from);
+            if i + 1 < constraints.len() {
+                parent_scenario.add_constraints(&table);
+            }
+            scenario.add_constraints(&table);
+        }
+        let parent = mdd(&agent, &parent_scenario, cost).unwrap();
+
+        let restricted = restrict_mdd(&parent, &scenario);
+
+        prop_assert_eq!(sorted(&restricted), sorted(&mdd(&agent, &scenario, cost).unwrap()));
+    }
+}
+
+#[test]
+fn test_restricted_mdd_drops_dead_ends() {
+    let map = Grid::new(3, 1, Grid::to_conditional_obstacles(vec![]), (0, 0));
+    let agent = crate::cbs::high_level::Agent {
+        id: "a".to_string(),
+        start: (0, 0),
+        goal: (2, 0),
+    };
+    let parent = mdd(&agent, &map, 3).unwrap();
+    let mut constraints = ConstraintTable::default();
+    constraints.insert(LocationTime::new((1, 0), 2), None);
+    let mut scenario = map.clone();
+    scenario.add_constraints(&constraints);
+
+    // waiting at the start leaves no way to the goal in time
+    assert_eq!(
+        restrict_mdd(&parent, &scenario),
+        vec![vec![(0, 0)], vec![(1, 0)], vec![(2, 0)], vec![(2, 0)]]
+    );
+}
+
+#[test]
+fn test_mdd_cache() {
+    let map = Grid::new(10, 10, Grid::to_conditional_obstacles(vec![]), (0, 0));
+    let agent = crate::cbs::high_level::Agent {
+        id: "a".to_string(),
+        start: (0, 0),
+        goal: (3, 3),
+    };
+    let parent_constraints = ConstraintTable::default();
+    let mut constraints = ConstraintTable::default();
+    constraints.insert(LocationTime::new((1, 0), 1), None);
+    let cache = MDDCache::new();
+
+    let root = cache
+        .get(&agent, &map, &parent_constraints, None, 6)
+        .unwrap();
+    let child = cache
+        .get(&agent, &map, &constraints, Some(&parent_constraints), 6)
+        .unwrap();
+    let again = cache.get(&agent, &map, &constraints, None, 6).unwrap();
+    let other_cost = cache
+        .get(&agent, &map, &constraints, Some(&parent_constraints), 8)
+        .unwrap();
+
+    assert_eq!(*root, mdd(&agent, &map, 6).unwrap());
+    assert_eq!(child[1], vec![(0, 1)]);
+    assert!(Arc::ptr_eq(&child, &again));
+    assert_eq!(other_cost.len(), 9);
+    assert_eq!(
+        cache.statistics(),
+        MDDCacheStatistics {
+            hits: 1,
+            incremental: 1,
+            built: 2,
+        }
+    );
+    assert_eq!(cache.statistics().hit_rate(), 0.25);
 }
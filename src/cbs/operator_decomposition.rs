@@ -0,0 +1,229 @@
+//! Standley's operator decomposition: A* over the joint state of all agents,
+//! where a joint move is split into one move per agent so that the open list
+//! grows by at most five nodes per expansion. Exponential in the number of
+//! agents, so only meant as an exact reference for small instances.
+
+use std::{collections::HashMap, error::Error, hash::Hash};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    low_level::{heuristic::DistanceMap, Grid, LocationTime},
+    search::{a_star_until, AStarNode, SearchError},
+    CBSError, CBSInstance,
+};
+
+pub struct OperatorDecomposition {
+    instance: CBSInstance,
+    cancellation: Option<CancellationToken>,
+    pub nodes_generated: usize,
+}
+
+impl OperatorDecomposition {
+    pub fn new(instance: CBSInstance) -> Self {
+        Self {
+            instance,
+            cancellation: None,
+            nodes_generated: 0,
+        }
+    }
+
+    /// Makes [`Self::solve`] fail with [`SearchError::Cancelled`] once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        self.instance.require_unit_costs("Operator decomposition")?;
+        let map = &self.instance.map;
+        let agents = &self.instance.agents;
+        let operators = Operators {
+            map,
+            goals: agents.iter().map(|agent| agent.goal).collect(),
+            distances: agents
+                .iter()
+                .map(|agent| DistanceMap::new(map, agent.goal))
+                .collect(),
+            horizon: map.timed_obstacles.latest_time().unwrap_or(-1),
+        };
+        let start = JointNode::start(&operators, agents).ok_or(SearchError::NotFound)?;
+        let cancellation = self.cancellation.clone();
+        let solution = a_star_until(start, &|| {
+            cancellation
+                .as_ref()
+                .is_some_and(|cancellation| cancellation.is_cancelled())
+        })?;
+        self.nodes_generated = solution.nodes_generated as usize;
+        let steps = solution
+            .path
+            .iter()
+            .filter(|node| node.next_agent == 0)
+            .collect::<Vec<_>>();
+        let mut paths = HashMap::<&Agent, Path>::new();
+        for (i, agent) in agents.iter().enumerate() {
+            let mut path = steps.iter().map(|node| node.locations[i]).collect::<Path>();
+            while path.len() > 1 && path[path.len() - 2] == path[path.len() - 1] {
+                path.pop();
+            }
+            paths.insert(agent, path);
+        }
+        Ok(paths)
+    }
+}
+
+/// What every node of the search shares: the map and each agent's goal
+/// along with its distance table on the static map.
+struct Operators<'a> {
+    map: &'a Grid,
+    goals: Vec<(i32, i32)>,
+    distances: Vec<DistanceMap>,
+    /// The time of the last timed obstacle, after which time can be dropped from the state.
+    horizon: i32,
+}
+
+/// A joint state in which the agents before `next_agent` have already made
+/// their move of the current timestep and the others have not.
+#[derive(Clone)]
+struct JointNode<'a> {
+    locations: Vec<(i32, i32)>,
+    /// Where every agent was at the start of the timestep.
+    previous: Vec<(i32, i32)>,
+    next_agent: usize,
+    time: i32,
+    /// Waits at the goal since the agent arrived, owed if it leaves again.
+    goal_waits: Vec<i32>,
+    g: f64,
+    h: f64,
+    operators: &'a Operators<'a>,
+}
+
+impl<'a> JointNode<'a> {
+    fn start(operators: &'a Operators<'a>, agents: &[Agent]) -> Option<Self> {
+        let locations = agents.iter().map(|agent| agent.start).collect::<Vec<_>>();
+        let h = locations
+            .iter()
+            .zip(&operators.distances)
+            .map(|(location, distances)| distances.distance(*location))
+            .sum::<Option<i32>>()?;
+        Some(Self {
+            previous: locations.clone(),
+            locations,
+            next_agent: 0,
+            time: 0,
+            goal_waits: vec![0; agents.len()],
+            g: 0.0,
+            h: h as f64,
+            operators,
+        })
+    }
+
+    /// Moves `next_agent` to `cell`, if that does not collide with the
+    /// agents that already moved during this timestep.
+    fn move_next_agent(&self, cell: (i32, i32)) -> Option<Self> {
+        let i = self.next_agent;
+        let from = self.previous[i];
+        let operators = self.operators;
+        if !operators
+            .map
+            .is_valid_location_time(&LocationTime::new(cell, self.time + 1), &from)
+        {
+            return None;
+        }
+        let conflicts = (0..i).any(|j| {
+            self.locations[j] == cell || (self.locations[j] == from && self.previous[j] == cell)
+        });
+        if conflicts {
+            return None;
+        }
+        let distance = operators.distances[i].distance(cell)?;
+        let mut next = self.clone();
+        if cell == operators.goals[i] && from == operators.goals[i] {
+            next.goal_waits[i] += 1;
+        } else {
+            next.g += 1.0 + next.goal_waits[i] as f64;
+            next.goal_waits[i] = 0;
+        }
+        let from_distance = operators.distances[i]
+            .distance(from)
+            .expect("agents only move to cells their goal is reachable from");
+        next.h += (distance - from_distance) as f64;
+        next.locations[i] = cell;
+        next.next_agent += 1;
+        if next.next_agent == next.locations.len() {
+            next.next_agent = 0;
+            next.time = (next.time + 1).min(operators.horizon + 1);
+            next.previous = next.locations.clone();
+        }
+        Some(next)
+    }
+}
+
+impl PartialEq for JointNode<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.locations == other.locations
+            && self.previous == other.previous
+            && self.next_agent == other.next_agent
+            && self.time == other.time
+            && self.goal_waits == other.goal_waits
+    }
+}
+
+impl Eq for JointNode<'_> {}
+
+impl Hash for JointNode<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.locations.hash(state);
+        self.previous.hash(state);
+        self.next_agent.hash(state);
+        self.time.hash(state);
+        self.goal_waits.hash(state);
+    }
+}
+
+impl AStarNode<'_> for JointNode<'_> {
+    fn g(&self) -> f64 {
+        self.g
+    }
+
+    fn h(&self) -> f64 {
+        self.h
+    }
+
+    fn expand(&self) -> Option<Vec<Box<Self>>> {
+        if self.locations.is_empty() {
+            return Some(Vec::new());
+        }
+        let (x, y) = self.previous[self.next_agent];
+        Some(
+            [(x, y), (x, y + 1), (x + 1, y), (x, y - 1), (x - 1, y)]
+                .into_iter()
+                .filter_map(|cell| self.move_next_agent(cell))
+                .map(Box::new)
+                .collect(),
+        )
+    }
+
+    fn is_goal(&self) -> bool {
+        self.next_agent == 0
+            && self.time > self.operators.horizon
+            && self.locations == self.operators.goals
+    }
+
+    fn id(&self) -> String {
+        format!("{:?}@{}/{}", self.locations, self.time, self.next_agent)
+    }
+
+    fn tie_breaker(&self, other: &Self) -> std::cmp::Ordering {
+        self.next_agent
+            .cmp(&other.next_agent)
+            .reverse()
+            .then_with(|| self.locations.cmp(&other.locations))
+    }
+}
+
+#[cfg(test)]
+mod tests;
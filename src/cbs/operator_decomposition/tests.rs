@@ -0,0 +1,88 @@
+use rstest::rstest;
+
+use super::*;
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+fn empty_grid(width: i32, height: i32) -> Grid {
+    Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(Vec::new()),
+        (0, 0),
+    )
+}
+
+fn sum_of_costs(paths: &HashMap<&Agent, Path>) -> usize {
+    paths.values().map(|path| path.len() - 1).sum()
+}
+
+#[rstest]
+#[case::independent(vec![agent("a", (0, 0), (0, 3)), agent("b", (3, 3), (3, 0))], 6)]
+#[case::crossing(vec![agent("a", (0, 1), (2, 1)), agent("b", (1, 0), (1, 2))], 5)]
+#[case::swapping(
+    vec![agent("a", (0, 0), (3, 0)), agent("b", (3, 0), (0, 0))],
+    8
+)]
+#[case::already_at_goal(vec![agent("a", (1, 1), (1, 1)), agent("b", (0, 1), (2, 1))], 4)]
+fn test_optimal_cost(#[case] agents: Vec<Agent>, #[case] expected: usize) {
+    let mut solver = OperatorDecomposition::new(CBSInstance::new(empty_grid(4, 4), agents));
+    let paths = solver.solve().unwrap();
+    assert_eq!(sum_of_costs(&paths), expected);
+    for (agent, path) in &paths {
+        assert_eq!(path.first(), Some(&agent.start));
+        assert_eq!(path.last(), Some(&agent.goal));
+    }
+}
+
+#[test]
+fn test_waits_for_timed_obstacle_on_goal() {
+    let grid = Grid::new(
+        3,
+        1,
+        Grid::to_conditional_obstacles(vec![LocationTime::new((2, 0), 3)]),
+        (0, 0),
+    );
+    let mut solver =
+        OperatorDecomposition::new(CBSInstance::new(grid, vec![agent("a", (0, 0), (2, 0))]));
+    let paths = solver.solve().unwrap();
+    let path = paths.values().next().unwrap();
+    assert_eq!(path.len(), 5);
+    assert_ne!(path.get(3), Some(&(2, 0)));
+}
+
+#[test]
+fn test_corridor_swap_not_found() {
+    let grid = empty_grid(3, 1);
+    let mut solver = OperatorDecomposition::new(CBSInstance::new(
+        grid,
+        vec![agent("a", (0, 0), (2, 0)), agent("b", (2, 0), (0, 0))],
+    ));
+    let error = solver.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::NotFound)
+    ));
+}
+
+#[test]
+fn test_cancelled() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let mut solver = OperatorDecomposition::new(CBSInstance::new(
+        empty_grid(4, 4),
+        vec![agent("a", (0, 0), (3, 3))],
+    ))
+    .with_cancellation(cancellation);
+    let error = solver.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::Cancelled)
+    ));
+}
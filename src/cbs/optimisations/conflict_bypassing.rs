@@ -5,10 +5,7 @@ pub fn bypass_conflict<'a>(
     conflict: &Conflict<'a>,
     children: Vec<Box<ConflictTreeNode<'a>>>,
 ) -> Option<Vec<Box<ConflictTreeNode<'a>>>> {
-    let (agent1, agent2) = match conflict {
-        Conflict::Vertex(conflict) => (conflict.agent1, conflict.agent2),
-        Conflict::Edge(conflict) => (conflict.agent1, conflict.agent2),
-    };
+    let (agent1, agent2) = conflict.agents();
     for child in children.iter() {
         for agent in vec![agent1, agent2] {
             let path = child.paths[agent].clone();
@@ -16,8 +13,7 @@ pub fn bypass_conflict<'a>(
                 continue;
             }
             let mut new_parent = parent.clone();
-            new_parent.paths.insert(agent, path.clone());
-            new_parent.compute_conflicts();
+            new_parent.replace_path(agent, path.clone());
             if new_parent.conflicts.len() >= parent.conflicts.len() {
                 continue;
             }
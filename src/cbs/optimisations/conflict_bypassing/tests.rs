@@ -77,7 +77,7 @@ fn test_bypass_conflict(
         None,
         true,
         &low_level_solver,
-        std::rc::Rc::new(heuristic::ZeroHeuristic::new()),
+        std::sync::Arc::new(heuristic::ZeroHeuristic::new()),
     );
     let conflict = parent.conflicts[conflict_idx].clone();
     let children: Vec<ConflictTreeNode> = children_paths
@@ -97,7 +97,7 @@ fn test_bypass_conflict(
                 None,
                 true,
                 &low_level_solver,
-                std::rc::Rc::new(heuristic::ZeroHeuristic::new()),
+                std::sync::Arc::new(heuristic::ZeroHeuristic::new()),
             )
         })
         .collect();
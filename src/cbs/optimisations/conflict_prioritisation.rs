@@ -1,22 +1,21 @@
-use std::collections::HashMap;
-
-use crate::cbs::high_level::Path;
-use crate::cbs::mdd::mdd;
+use crate::cbs::high_level::persistent::PathMap;
+use crate::cbs::mdd::MDDCache;
 use crate::cbs::{
     high_level::{Agent, Conflict},
-    low_level::Grid,
+    low_level::{obstacles::ConstraintTable, Grid},
 };
 
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
-enum ConflictCardinality {
+pub(crate) enum ConflictCardinality {
     Cardinal,
     SemiCardinal,
     NonCardinal,
 }
 
-fn cardinality(
+pub(crate) fn cardinality(
     scenario: &Grid,
-    paths: &HashMap<&Agent, Path>,
+    paths: &PathMap,
+    mdds: &MDDCache,
     conflict: &Conflict,
 ) -> ConflictCardinality {
     let agent1: &Agent;
@@ -36,23 +35,25 @@ fn cardinality(
     }
     let c1 = paths[agent1].len() - 1;
     let c2 = paths[agent2].len() - 1;
-    let agent1_mdd = mdd(agent1, scenario, c1 as i32).unwrap();
-    let agent2_mdd = mdd(agent2, scenario, c2 as i32).unwrap();
-    if agent1_mdd[time.clone() as usize].len() == 1
-        && agent2_mdd
-            .get(time.clone() as usize)
-            .unwrap_or(agent2_mdd.last().unwrap())
-            .len()
-            == 1
-    {
+    let no_constraints = ConstraintTable::default();
+    let agent1_mdd = mdds
+        .get(agent1, scenario, &no_constraints, None, c1 as i32)
+        .unwrap();
+    let agent2_mdd = mdds
+        .get(agent2, scenario, &no_constraints, None, c2 as i32)
+        .unwrap();
+    // an agent past the end of its MDD waits at its goal
+    let agent1_width = agent1_mdd
+        .get(time as usize)
+        .unwrap_or(agent1_mdd.last().unwrap())
+        .len();
+    let agent2_width = agent2_mdd
+        .get(time as usize)
+        .unwrap_or(agent2_mdd.last().unwrap())
+        .len();
+    if agent1_width == 1 && agent2_width == 1 {
         return ConflictCardinality::Cardinal;
-    } else if agent1_mdd[time.clone() as usize].len() == 1
-        || agent2_mdd
-            .get(time.clone() as usize)
-            .unwrap_or(agent2_mdd.last().unwrap())
-            .len()
-            == 1
-    {
+    } else if agent1_width == 1 || agent2_width == 1 {
         return ConflictCardinality::SemiCardinal;
     } else {
         return ConflictCardinality::NonCardinal;
@@ -61,12 +62,13 @@ fn cardinality(
 
 pub fn pick_conflict<'a>(
     scenario: &Grid,
-    paths: &HashMap<&Agent, Path>,
+    paths: &PathMap,
+    mdds: &MDDCache,
     conflicts: &Vec<Box<Conflict<'a>>>,
 ) -> Option<Box<Conflict<'a>>> {
-    let min_conflict = conflicts
-        .iter()
-        .min_by(|a, b| cardinality(scenario, paths, a).cmp(&cardinality(scenario, paths, b)));
+    let min_conflict = conflicts.iter().min_by(|a, b| {
+        cardinality(scenario, paths, mdds, a).cmp(&cardinality(scenario, paths, mdds, b))
+    });
     match min_conflict {
         Some(c) => Some(c.clone()),
         None => None,
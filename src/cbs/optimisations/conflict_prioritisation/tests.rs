@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use super::*;
 use crate::cbs::high_level::{Conflict, VertexConflict};
 use rstest::rstest;
@@ -49,6 +51,27 @@ struct MockVertexConflict {
     ],
     Some(1),
 )]
+#[case::first_agent_at_its_goal(
+    vec![
+        vec![(1, 0)],
+        vec![(1, 2), (1, 1), (1, 0), (0, 0)],
+    ],
+    vec![
+        MockVertexConflict {
+            agent1_idx: 0,
+            agent2_idx: 1,
+            location: (1, 0),
+            time: 2,
+        },
+        MockVertexConflict {
+            agent1_idx: 1,
+            agent2_idx: 0,
+            location: (1, 0),
+            time: 2,
+        },
+    ],
+    Some(0),
+)]
 fn test_pick_conflict(
     #[case] paths: Vec<crate::cbs::high_level::Path>,
     #[case] conflicts: Vec<MockVertexConflict>,
@@ -80,7 +103,8 @@ fn test_pick_conflict(
             }))
         })
         .collect();
-    let selected_conflict = pick_conflict(&grid, &path_map, &processed_conflicts);
+    let path_map = PathMap::new(&agents.iter().collect::<Vec<_>>(), path_map);
+    let selected_conflict = pick_conflict(&grid, &path_map, &MDDCache::new(), &processed_conflicts);
     match expected_idx {
         Some(expected_idx) => match *processed_conflicts[expected_idx].clone() {
             Conflict::Vertex(c) => {
@@ -3,7 +3,7 @@ use std::hash::Hash;
 use std::rc::Rc;
 
 use crate::cbs::high_level::{CTNodePreprocessor, ConflictTreeNode, Path};
-use crate::cbs::low_level::{Grid, LocationTime};
+use crate::cbs::low_level::{obstacles::ConstraintTable, Grid, LocationTime};
 use crate::cbs::search::dfs;
 use crate::cbs::vertex_cover::{k_vertex_cover, min_vertex_cover, MVCGraph};
 use crate::cbs::Agent;
@@ -107,12 +107,7 @@ fn plan_diagonal_kind<'a, 'b>(
     if diagonals.is_empty() {
         return;
     }
-    let mut aux_grid = Grid::new(
-        node.scenario.width,
-        node.scenario.height,
-        node.scenario.obstacles.clone().into_iter().collect(),
-        node.scenario.goal,
-    );
+    let mut aux_grid = node.scenario.clone();
     let mut promoted_agents = HashMap::<&Agent, usize>::new();
     // add a dummy diagonal to handle promoted agents
     // it is another pointer to the last diagonal, but without agents
@@ -153,10 +148,10 @@ fn plan_diagonal_kind<'a, 'b>(
                 log::debug!("promoting agent {}", agent.id);
                 continue;
             }
-            let mut constraint_obstacles = node.constraints_to_obstacles(agent);
-            constraint_obstacles.extend(Grid::to_conditional_obstacles(
-                planned_path_obstacles.clone().into_iter().collect(),
-            ));
+            let mut constraint_obstacles = node.constraint_table(agent);
+            for loc_time in planned_path_obstacles.iter() {
+                constraint_obstacles.insert(*loc_time, None);
+            }
             let (path, found) =
                 plan_agent_path(agent, diagonal, &aux_grid, &constraint_obstacles, slackness);
             if !found {
@@ -184,9 +179,7 @@ fn plan_diagonal_kind<'a, 'b>(
         }
         promoted_agents.retain(|agent, _| !paths.contains_key(agent));
         node.paths.extend(paths);
-        aux_grid
-            .obstacles
-            .extend(Grid::to_conditional_obstacles(target_obstacles));
+        aux_grid.add_obstacles(Grid::to_conditional_obstacles(target_obstacles));
     }
 }
 
@@ -194,14 +187,11 @@ fn plan_agent_path(
     agent: &&Agent,
     diagonal: &Diagonal,
     aux_grid: &Grid,
-    additional_obstacles: &HashMap<LocationTime, Vec<(i32, i32)>>,
+    additional_obstacles: &ConstraintTable,
     slackness: i32,
 ) -> (Vec<(i32, i32)>, bool) {
     let latest_goal_obstacle_time = additional_obstacles
-        .iter()
-        .filter(|(loc_time, coming_from)| loc_time.location == agent.goal && coming_from.is_empty())
-        .map(|(loc, _)| loc.time)
-        .max()
+        .latest_vertex_time(agent.goal)
         .unwrap_or(i32::MIN); // TODO: think if aux_grid obstacles should be considered here
     let mut visited = HashSet::<LocationTime>::new();
     let mut path: Path = vec![];
@@ -238,7 +228,7 @@ fn plan_agent_path(
                 .filter(|loc| {
                     aux_grid.is_valid_location_time(&loc, &cur.location)
                         && is_in_start_goal_box(loc, agent)
-                        && !is_in_additional_obstacles(loc, cur, additional_obstacles)
+                        && !additional_obstacles.is_blocked(loc, &cur.location)
                 })
                 .collect()
         },
@@ -285,17 +275,6 @@ fn build_dependency_graph<'a>(agents: &'_ Vec<&'a Agent>) -> MVCGraph<&'a Agent>
     graph
 }
 
-fn is_in_additional_obstacles(
-    loc_time: &LocationTime,
-    prev: &LocationTime,
-    additional_obstacles: &HashMap<LocationTime, Vec<(i32, i32)>>,
-) -> bool {
-    match additional_obstacles.get(loc_time) {
-        Some(coming_from) => coming_from.is_empty() || coming_from.contains(&prev.location),
-        None => false,
-    }
-}
-
 fn is_in_start_goal_box(loc_time: &LocationTime, agent: &Agent) -> bool {
     let (x, y) = loc_time.location;
     let (start_x, start_y) = agent.start;
@@ -469,7 +469,7 @@ fn test_plan_two_direction_agents(
         None,
         false,
         &solver,
-        Rc::new(crate::cbs::high_level::heuristic::ZeroHeuristic::new()),
+        std::sync::Arc::new(crate::cbs::high_level::heuristic::ZeroHeuristic::new()),
     );
 
     plan_two_direction_agents(&mut node, slackness, promotion_enabled);
@@ -484,7 +484,7 @@ fn test_plan_two_direction_agents(
     assert_eq!(paths.len(), expected_paths.len());
     assert_eq!(
         paths
-            .into_iter()
+            .iter()
             .map(|(a, p)| (a, p.iter().map(|(t, l)| (*t, *l)).collect()))
             .collect::<HashMap<&Agent, Vec<(i32, i32)>>>(),
         expected_paths
@@ -0,0 +1,40 @@
+use std::{cell::Cell, thread};
+
+thread_local! {
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Maps `items` with `f` on up to `threads` scoped threads, each taking a
+/// contiguous chunk, and returns the results in the order of `items`.
+/// Runs on the calling thread if there is nothing to split, or if it is
+/// itself a worker, so nested calls never run more than `threads` threads.
+pub(crate) fn parallel_map<T, R, F>(items: &[T], threads: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if threads <= 1 || items.len() <= 1 || IS_WORKER.get() {
+        return items.iter().map(f).collect();
+    }
+    let chunk_size = items.len().div_ceil(threads);
+    let f = &f;
+    thread::scope(|scope| {
+        let handles = items
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    IS_WORKER.set(true);
+                    chunk.iter().map(f).collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("worker thread should not panic"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,36 @@
+use super::*;
+use rstest::rstest;
+
+#[rstest]
+#[case::sequential(1)]
+#[case::fewer_items_than_threads(64)]
+#[case::uneven_chunks(3)]
+fn test_preserves_order(#[case] threads: usize) {
+    let items = (0..10).collect::<Vec<i32>>();
+    let squares = parallel_map(&items, threads, |x| x * x);
+    assert_eq!(squares, items.iter().map(|x| x * x).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_uses_several_threads() {
+    let items = (0..4).collect::<Vec<i32>>();
+    let thread_ids = parallel_map(&items, 4, |_| thread::current().id());
+    assert!(thread_ids.iter().all(|id| *id != thread::current().id()));
+}
+
+#[test]
+fn test_nested_calls_stay_on_the_worker() {
+    let items = (0..4).collect::<Vec<i32>>();
+    let thread_ids = parallel_map(&items, 4, |_| {
+        let inner = parallel_map(&items, 4, |_| thread::current().id());
+        (thread::current().id(), inner)
+    });
+    for (worker, inner) in thread_ids {
+        assert!(inner.iter().all(|id| *id == worker));
+    }
+}
+
+#[test]
+fn test_empty() {
+    assert!(parallel_map(&Vec::<i32>::new(), 4, |x| *x).is_empty());
+}
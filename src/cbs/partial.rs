@@ -0,0 +1,103 @@
+//! Best-effort results of a search that was cut off before finding a solution.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use super::high_level::{Agent, Conflict, ConflictTreeNode, Path};
+
+/// Why the search stopped early.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cutoff {
+    Cancelled,
+    TimedOut,
+    NodeLimitReached,
+}
+
+impl fmt::Display for Cutoff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cutoff::Cancelled => write!(f, "cancelled"),
+            Cutoff::TimedOut => write!(f, "timed out"),
+            Cutoff::NodeLimitReached => write!(f, "node limit reached"),
+        }
+    }
+}
+
+/// A conflict left in the paths of a [`PartialSolution`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnresolvedConflict {
+    pub agents: (String, String),
+    pub time: i32,
+    /// One location for vertex conflicts, the two swapped locations for edge conflicts.
+    pub locations: Vec<(i32, i32)>,
+}
+
+impl From<&Conflict<'_>> for UnresolvedConflict {
+    fn from(conflict: &Conflict<'_>) -> Self {
+        match conflict {
+            Conflict::Vertex(c) => Self {
+                agents: (c.agent1.id.clone(), c.agent2.id.clone()),
+                time: c.time,
+                locations: vec![c.location],
+            },
+            Conflict::Edge(c) => Self {
+                agents: (c.agent1.id.clone(), c.agent2.id.clone()),
+                time: c.time,
+                locations: vec![c.location1, c.location2],
+            },
+        }
+    }
+}
+
+impl fmt::Display for UnresolvedConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "agents {} and {} at {:?} at time {}",
+            self.agents.0, self.agents.1, self.locations, self.time
+        )
+    }
+}
+
+/// The expanded conflict tree node with the fewest conflicts, ties broken by lowest f.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialSolution {
+    pub cutoff: Cutoff,
+    /// A path for every agent, in the order of the instance.
+    pub paths: Vec<(Agent, Path)>,
+    pub conflicts: Vec<UnresolvedConflict>,
+    /// The highest f value expanded, a lower bound on the optimal sum of costs.
+    pub lower_bound: f64,
+}
+
+impl PartialSolution {
+    pub(crate) fn new(
+        cutoff: Cutoff,
+        agents: &[Agent],
+        node: &ConflictTreeNode,
+        lower_bound: f64,
+    ) -> Self {
+        Self {
+            cutoff,
+            paths: agents
+                .iter()
+                .map(|agent| (agent.clone(), node.paths[agent].clone()))
+                .collect(),
+            conflicts: node.conflicts.iter().map(|c| c.as_ref().into()).collect(),
+            lower_bound,
+        }
+    }
+
+    /// Sum of the arrival times of the agents, as for a solution.
+    pub fn cost(&self) -> usize {
+        self.paths
+            .iter()
+            .map(|(_, path)| path.len().saturating_sub(1))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests;
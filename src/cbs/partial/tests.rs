@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use rstest::rstest;
+
+use super::*;
+use crate::cbs::{cancellation::CancellationToken, low_level::Grid, CBSError, CBSInstance, CBS};
+
+fn crossing_instance() -> CBSInstance {
+    CBSInstance::new(
+        Grid::new(3, 3, Grid::to_conditional_obstacles(Vec::new()), (2, 2)),
+        vec![
+            Agent {
+                id: "a".to_string(),
+                start: (0, 1),
+                goal: (2, 1),
+            },
+            Agent {
+                id: "b".to_string(),
+                start: (1, 0),
+                goal: (1, 2),
+            },
+        ],
+    )
+}
+
+#[test]
+fn test_node_limit() {
+    let mut cbs = CBS::new(crossing_instance(), None).with_node_limit(0);
+    let error = cbs.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<CBSError>(),
+        Some(CBSError::NodeLimitReached(0))
+    ));
+    let partial = cbs.partial_solution().unwrap();
+    assert_eq!(partial.cutoff, Cutoff::NodeLimitReached);
+    assert_eq!(partial.paths[0].0.id, "a");
+    assert_eq!(partial.paths[1].0.id, "b");
+    assert_eq!(partial.cost(), 4);
+    assert_eq!(partial.lower_bound, 4.0);
+    assert_eq!(partial.conflicts.len(), 1);
+    assert_eq!(partial.conflicts[0].time, 1);
+    assert_eq!(partial.conflicts[0].locations, vec![(1, 1)]);
+}
+
+#[test]
+fn test_solution_within_node_limit() {
+    let mut cbs = CBS::new(crossing_instance(), None).with_node_limit(1);
+    let paths = cbs.solve().unwrap();
+    assert_eq!(paths.values().map(|p| p.len() - 1).sum::<usize>(), 5);
+    assert_eq!(cbs.partial_solution(), None);
+}
+
+#[rstest]
+#[case::cancelled(CancellationToken::new(), Cutoff::Cancelled)]
+#[case::timed_out(CancellationToken::with_timeout(Duration::ZERO), Cutoff::TimedOut)]
+fn test_cancellation(#[case] cancellation: CancellationToken, #[case] cutoff: Cutoff) {
+    cancellation.cancel();
+    let mut cbs = CBS::new(crossing_instance(), None).with_cancellation(cancellation);
+    assert!(cbs.solve().is_err());
+    let partial = cbs.partial_solution().unwrap();
+    assert_eq!(partial.cutoff, cutoff);
+    assert_eq!(partial.conflicts.len(), 1);
+}
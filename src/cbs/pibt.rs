@@ -0,0 +1,286 @@
+//! Okumura et al.'s Priority Inheritance with Backtracking: every timestep,
+//! agents pick their next location in order of priority, greedily moving
+//! closer to their goal. An agent that wants the location of a lower
+//! priority agent makes it move first, and tries another location if that
+//! agent is stuck. Fast but incomplete, see [`super::lacam`] for a complete
+//! search built on top of it.
+//!
+//! Only the static obstacles of the map are considered.
+
+use std::{collections::HashMap, error::Error, sync::Arc};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    low_level::{
+        distance_cache::DistanceCache,
+        heuristic::{DistanceMap, Heuristic},
+        Grid, LocationTime,
+    },
+    search::SearchError,
+    CBSError, CBSInstance,
+};
+
+pub type Configuration = Vec<(i32, i32)>;
+
+/// A [`DistanceMap`] per goal, shared by the agents with that goal.
+pub(crate) struct DistanceTables {
+    tables: Vec<Arc<DistanceMap>>,
+}
+
+impl DistanceTables {
+    pub(crate) fn new(map: &Grid, agents: &[Agent]) -> Self {
+        let cache = DistanceCache::new();
+        let tables = agents
+            .iter()
+            .map(|agent| cache.get(map, agent.goal))
+            .collect();
+        Self { tables }
+    }
+
+    pub(crate) fn distance(&self, agent: usize, location: (i32, i32)) -> f64 {
+        self.tables[agent].h(&LocationTime::new(location, 0))
+    }
+}
+
+/// Generates the next configuration of a set of agents with PIBT.
+pub(crate) struct ConfigurationGenerator<'a> {
+    map: &'a Grid,
+    distances: &'a DistanceTables,
+    rng: StdRng,
+    occupied_now: HashMap<(i32, i32), usize>,
+    occupied_next: HashMap<(i32, i32), usize>,
+    next: Vec<Option<(i32, i32)>>,
+}
+
+impl<'a> ConfigurationGenerator<'a> {
+    pub(crate) fn new(map: &'a Grid, distances: &'a DistanceTables, seed: u64) -> Self {
+        Self {
+            map,
+            distances,
+            rng: StdRng::seed_from_u64(seed),
+            occupied_now: HashMap::new(),
+            occupied_next: HashMap::new(),
+            next: Vec::new(),
+        }
+    }
+
+    /// The locations an agent at `location` can be at in the next timestep.
+    pub(crate) fn moves(&self, location: (i32, i32)) -> Vec<(i32, i32)> {
+        let (x, y) = location;
+        [(x, y + 1), (x + 1, y), (x, y - 1), (x - 1, y)]
+            .into_iter()
+            .filter(|cell| self.map.is_valid_location(cell, &location))
+            .chain([location])
+            .collect()
+    }
+
+    /// Moves every agent of `current` one step, planning agents in `order`.
+    /// The moves in `constraints` are fixed in advance. Returns `None` if the
+    /// constraints collide or some agent cannot be given a location.
+    pub(crate) fn next_configuration(
+        &mut self,
+        current: &[(i32, i32)],
+        order: &[usize],
+        constraints: &[(usize, (i32, i32))],
+    ) -> Option<Configuration> {
+        self.occupied_now = current.iter().enumerate().map(|(i, &l)| (l, i)).collect();
+        self.occupied_next.clear();
+        self.next = vec![None; current.len()];
+        for &(agent, location) in constraints {
+            if self.occupied_next.contains_key(&location) {
+                return None;
+            }
+            let swapped = self
+                .occupied_now
+                .get(&location)
+                .is_some_and(|&other| self.next[other] == Some(current[agent]));
+            if swapped {
+                return None;
+            }
+            self.occupied_next.insert(location, agent);
+            self.next[agent] = Some(location);
+        }
+        for &agent in order {
+            if self.next[agent].is_none() && !self.plan(agent, current) {
+                return None;
+            }
+        }
+        Some(self.next.iter().map(|location| location.unwrap()).collect())
+    }
+
+    /// Reserves a next location for `agent`, pushing away the agents in
+    /// the way. Returns false if `agent` has to stay and was pushed.
+    fn plan(&mut self, agent: usize, current: &[(i32, i32)]) -> bool {
+        let location = current[agent];
+        let mut candidates = self
+            .moves(location)
+            .into_iter()
+            .map(|cell| {
+                let tie_breaker = self.rng.gen::<f64>();
+                (self.distances.distance(agent, cell) + tie_breaker, cell)
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        for (_, cell) in candidates {
+            if self.occupied_next.contains_key(&cell) {
+                continue;
+            }
+            let occupant = self.occupied_now.get(&cell).copied();
+            if occupant.is_some_and(|other| self.next[other] == Some(location)) {
+                continue;
+            }
+            self.occupied_next.insert(cell, agent);
+            self.next[agent] = Some(cell);
+            let Some(occupant) = occupant else {
+                return true;
+            };
+            if occupant == agent || self.next[occupant].is_some() || self.plan(occupant, current) {
+                return true;
+            }
+        }
+        self.occupied_next.insert(location, agent);
+        self.next[agent] = Some(location);
+        false
+    }
+}
+
+/// Priorities grow by one every timestep an agent is away from its goal,
+/// and drop back to their initial fraction when it arrives.
+pub(crate) fn update_priorities(
+    priorities: &mut [f64],
+    configuration: &[(i32, i32)],
+    goals: &[(i32, i32)],
+) {
+    for (i, priority) in priorities.iter_mut().enumerate() {
+        if configuration[i] == goals[i] {
+            *priority -= priority.floor();
+        } else {
+            *priority += 1.0;
+        }
+    }
+}
+
+/// The agents by decreasing priority.
+pub(crate) fn priority_order(priorities: &[f64]) -> Vec<usize> {
+    let mut order = (0..priorities.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| priorities[b].total_cmp(&priorities[a]));
+    order
+}
+
+/// Initial priorities, higher for agents further from their goal and all below 1.
+pub(crate) fn initial_priorities(distances: &DistanceTables, starts: &[(i32, i32)]) -> Vec<f64> {
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| distances.distance(i, start) / (starts.len() as f64 * 10000.0))
+        .collect()
+}
+
+/// Splits a sequence of configurations into one path per agent,
+/// dropping the waits at the goal at the end of each path.
+pub(crate) fn configurations_to_paths<'a>(
+    agents: &'a [Agent],
+    configurations: &[Configuration],
+) -> HashMap<&'a Agent, Path> {
+    agents
+        .iter()
+        .enumerate()
+        .map(|(i, agent)| {
+            let mut path = configurations
+                .iter()
+                .map(|configuration| configuration[i])
+                .collect::<Path>();
+            while path.len() > 1 && path[path.len() - 2] == path[path.len() - 1] {
+                path.pop();
+            }
+            (agent, path)
+        })
+        .collect()
+}
+
+pub const DEFAULT_MAX_TIMESTEPS: usize = 10000;
+
+pub struct Pibt {
+    instance: CBSInstance,
+    seed: u64,
+    max_timesteps: usize,
+    cancellation: Option<CancellationToken>,
+    pub timesteps: usize,
+}
+
+impl Pibt {
+    pub fn new(instance: CBSInstance) -> Self {
+        Self {
+            instance,
+            seed: 0,
+            max_timesteps: DEFAULT_MAX_TIMESTEPS,
+            cancellation: None,
+            timesteps: 0,
+        }
+    }
+
+    /// Seeds the random tie-breaking between equally close locations.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Makes [`Self::solve`] fail with [`SearchError::NotFound`] if not
+    /// every agent is at its goal after `max_timesteps`.
+    pub fn with_max_timesteps(mut self, max_timesteps: usize) -> Self {
+        self.max_timesteps = max_timesteps;
+        self
+    }
+
+    /// Makes [`Self::solve`] fail with [`SearchError::Cancelled`] once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        self.instance.require_unit_costs("PIBT")?;
+        self.instance.require_no_timed_obstacles("PIBT")?;
+        let map = &self.instance.map;
+        let agents = &self.instance.agents;
+        let distances = DistanceTables::new(map, agents);
+        let goals = agents.iter().map(|agent| agent.goal).collect::<Vec<_>>();
+        let starts = agents.iter().map(|agent| agent.start).collect::<Vec<_>>();
+        if (0..agents.len()).any(|i| distances.distance(i, starts[i]).is_infinite()) {
+            return Err(Box::new(SearchError::NotFound));
+        }
+        let mut generator = ConfigurationGenerator::new(map, &distances, self.seed);
+        let mut priorities = initial_priorities(&distances, &starts);
+        let mut configurations = vec![starts];
+        while configurations.last() != Some(&goals) {
+            if self
+                .cancellation
+                .as_ref()
+                .is_some_and(|cancellation| cancellation.is_cancelled())
+            {
+                return Err(Box::new(SearchError::Cancelled));
+            }
+            if configurations.len() > self.max_timesteps {
+                return Err(Box::new(SearchError::NotFound));
+            }
+            let current = configurations.last().unwrap();
+            let next = generator
+                .next_configuration(current, &priority_order(&priorities), &[])
+                .ok_or(SearchError::NotFound)?;
+            update_priorities(&mut priorities, &next, &goals);
+            configurations.push(next);
+        }
+        self.timesteps = configurations.len() - 1;
+        let paths = configurations_to_paths(agents, &configurations);
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests;
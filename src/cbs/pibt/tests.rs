@@ -0,0 +1,191 @@
+use proptest::{prelude::*, sample::subsequence};
+use rstest::rstest;
+
+use super::*;
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+fn empty_grid(width: i32, height: i32) -> Grid {
+    Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(Vec::new()),
+        (0, 0),
+    )
+}
+
+fn assert_valid(map: &Grid, paths: &HashMap<&Agent, Path>) {
+    let horizon = paths.values().map(|path| path.len()).max().unwrap_or(0);
+    let at = |path: &Path, t: usize| path[t.min(path.len() - 1)];
+    for (agent, path) in paths {
+        assert_eq!(path.first(), Some(&agent.start));
+        assert_eq!(path.last(), Some(&agent.goal));
+        for step in path.windows(2) {
+            assert!(map.is_valid_location(&step[1], &step[0]));
+        }
+    }
+    let paths = paths.values().collect::<Vec<_>>();
+    for t in 0..horizon {
+        for (i, a) in paths.iter().enumerate() {
+            for b in &paths[i + 1..] {
+                assert_ne!(at(a, t), at(b, t), "vertex conflict at {}", t);
+                assert!(
+                    at(a, t) != at(b, t + 1) || at(a, t + 1) != at(b, t),
+                    "swapping conflict at {}",
+                    t
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_distance_tables() {
+    let grid = Grid::new(
+        3,
+        3,
+        Grid::to_conditional_obstacles(vec![LocationTime::new((1, 1), -1)]),
+        (0, 0),
+    );
+    let agents = vec![agent("a", (0, 0), (2, 2)), agent("b", (2, 0), (2, 2))];
+    let distances = DistanceTables::new(&grid, &agents);
+    assert_eq!(distances.distance(0, (0, 0)), 4.0);
+    assert_eq!(distances.distance(1, (2, 0)), 2.0);
+    assert_eq!(distances.distance(1, (0, 1)), 3.0);
+    assert!(Arc::ptr_eq(&distances.tables[0], &distances.tables[1]));
+}
+
+#[rstest]
+#[case::independent(vec![agent("a", (0, 0), (0, 3)), agent("b", (3, 3), (3, 0))])]
+#[case::crossing(vec![agent("a", (0, 1), (2, 1)), agent("b", (1, 0), (1, 2))])]
+#[case::swapping(vec![agent("a", (0, 0), (3, 0)), agent("b", (3, 0), (0, 0))])]
+#[case::pushed_off_goal(vec![agent("a", (1, 1), (1, 1)), agent("b", (0, 1), (2, 1))])]
+fn test_valid_paths(#[case] agents: Vec<Agent>) {
+    let grid = empty_grid(4, 4);
+    let mut pibt = Pibt::new(CBSInstance::new(grid.clone(), agents));
+    let paths = pibt.solve().unwrap();
+    assert_valid(&grid, &paths);
+    let makespan = paths.values().map(|path| path.len() - 1).max().unwrap();
+    assert!(pibt.timesteps >= makespan);
+}
+
+#[test]
+fn test_follows_shortest_path_when_alone() {
+    let grid = Grid::new(
+        3,
+        3,
+        Grid::to_conditional_obstacles(vec![
+            LocationTime::new((1, 0), -1),
+            LocationTime::new((1, 1), -1),
+        ]),
+        (0, 0),
+    );
+    let mut pibt = Pibt::new(CBSInstance::new(grid, vec![agent("a", (0, 0), (2, 0))]));
+    let paths = pibt.solve().unwrap();
+    assert_eq!(
+        paths.values().next().unwrap(),
+        &vec![(0, 0), (0, 1), (0, 2), (1, 2), (2, 2), (2, 1), (2, 0)]
+    );
+}
+
+#[test]
+fn test_gives_up_after_max_timesteps() {
+    // neither agent can get past the other in the corridor
+    let mut pibt = Pibt::new(CBSInstance::new(
+        empty_grid(3, 1),
+        vec![agent("a", (0, 0), (2, 0)), agent("b", (2, 0), (0, 0))],
+    ))
+    .with_max_timesteps(20);
+    let error = pibt.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::NotFound)
+    ));
+}
+
+#[test]
+fn test_cancelled() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let mut pibt = Pibt::new(CBSInstance::new(
+        empty_grid(4, 4),
+        vec![agent("a", (0, 0), (3, 3))],
+    ))
+    .with_cancellation(cancellation);
+    let error = pibt.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::Cancelled)
+    ));
+}
+
+#[test]
+fn test_rejects_timed_obstacles() {
+    let map = Grid::new(
+        4,
+        4,
+        Grid::to_conditional_obstacles(vec![crate::cbs::low_level::LocationTime::new((1, 1), 2)]),
+        (0, 0),
+    );
+    let mut pibt = Pibt::new(CBSInstance::new(map, vec![agent("a", (0, 0), (3, 3))]));
+    let error = pibt.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::InvalidArguments(_))
+    ));
+}
+
+// with 100 agents, an agent whose goal is in a corridor is pushed off it forever
+#[test]
+fn test_warehouse() {
+    let instance = CBSInstance::from_files(
+        "tests/testdata/maps/warehouse-10-20-10-2-1.map",
+        "tests/testdata/scenarios/warehouse-10-20-10-2-1-even-1.scen",
+        Some(50),
+    )
+    .expect("should be valid scenario files");
+    let map = instance.map.clone();
+    let mut pibt = Pibt::new(instance);
+    let paths = pibt.solve().unwrap();
+    assert_eq!(paths.len(), 50);
+    assert_valid(&map, &paths);
+}
+
+fn open_grid_instance() -> impl Strategy<Value = Vec<Agent>> {
+    let cells = (0..5)
+        .flat_map(|x| (0..5).map(move |y| (x, y)))
+        .collect::<Vec<_>>();
+    (1..9usize)
+        .prop_flat_map(move |n| {
+            (
+                subsequence(cells.clone(), n).prop_shuffle(),
+                subsequence(cells.clone(), n).prop_shuffle(),
+            )
+        })
+        .prop_map(|(starts, goals)| {
+            starts
+                .into_iter()
+                .zip(goals)
+                .enumerate()
+                .map(|(i, (start, goal))| agent(&i.to_string(), start, goal))
+                .collect()
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+    #[test]
+    fn test_valid_paths_on_open_grid(agents in open_grid_instance(), seed in any::<u64>()) {
+        let grid = empty_grid(5, 5);
+        let mut pibt = Pibt::new(CBSInstance::new(grid.clone(), agents)).with_seed(seed);
+        // PIBT is incomplete, but reaches all goals on open grids in practice
+        let paths = pibt.solve().unwrap();
+        assert_valid(&grid, &paths);
+    }
+}
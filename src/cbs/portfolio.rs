@@ -0,0 +1,300 @@
+//! Runs several solvers on the same instance, each on its own thread.
+//! Every member builds its own solver from a copy of the instance, so only
+//! the instance and the resulting paths cross threads. The first optimal
+//! solution wins and cancels the other members. Otherwise, the cheapest
+//! solution found by a suboptimal member is returned once the other members
+//! have finished or the deadline has passed. Either way, the portfolio waits
+//! for the cancelled members to stop before returning.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use super::{
+    cancellation::CancellationToken,
+    high_level::{Agent, Path},
+    icts::IncreasingCostTreeSearch,
+    lacam::LaCAM,
+    low_level::Grid,
+    m_star::MStar,
+    search::SearchError,
+    CBSError, CBSInstance, CBSOptimisationConfig, DiagonalSubsolverConfig, HighLevelHeuristic, CBS,
+};
+
+/// How often the portfolio checks its own cancellation while members run.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Debug)]
+pub enum PortfolioMember {
+    Cbs(CBSOptimisationConfig),
+    IncreasingCostTreeSearch,
+    MStar,
+    Lacam,
+}
+
+impl fmt::Display for PortfolioMember {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PortfolioMember::Cbs(config) => write!(f, "CBS {:?}", config),
+            PortfolioMember::IncreasingCostTreeSearch => write!(f, "ICTS"),
+            PortfolioMember::MStar => write!(f, "M*"),
+            PortfolioMember::Lacam => write!(f, "LaCAM"),
+        }
+    }
+}
+
+/// A member's solution: a path per agent, in the order of the instance's agents,
+/// and the number of nodes the member generated.
+type MemberSolution = (Vec<Path>, usize);
+
+impl PortfolioMember {
+    /// CBS with the DG heuristic, CBS with the diagonal sub-solver, ICBS,
+    /// ICTS, M*, and LaCAM as a fast suboptimal fallback.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            PortfolioMember::cbs_with_dg(),
+            PortfolioMember::cbs_with_diagonal_subsolver(),
+            PortfolioMember::icbs(),
+            PortfolioMember::IncreasingCostTreeSearch,
+            PortfolioMember::MStar,
+            PortfolioMember::Lacam,
+        ]
+    }
+
+    /// CBS with the DG heuristic.
+    pub fn cbs_with_dg() -> Self {
+        PortfolioMember::Cbs(CBSOptimisationConfig::new(
+            false,
+            false,
+            None,
+            false,
+            Some(HighLevelHeuristic::DGHeuristic),
+        ))
+    }
+
+    /// CBS with the diagonal sub-solver, without slackness or promotion.
+    pub fn cbs_with_diagonal_subsolver() -> Self {
+        PortfolioMember::Cbs(CBSOptimisationConfig::new(
+            false,
+            false,
+            Some(DiagonalSubsolverConfig::new(0, false)),
+            false,
+            None,
+        ))
+    }
+
+    /// CBS with conflict prioritisation, bypassing and a CAT.
+    pub fn icbs() -> Self {
+        PortfolioMember::Cbs(CBSOptimisationConfig::new(true, true, None, true, None))
+    }
+
+    /// Whether the member's solutions have the minimal sum of costs.
+    pub fn is_optimal(&self) -> bool {
+        match self {
+            // slackness and promotion add waits, trading optimality for speed
+            PortfolioMember::Cbs(config) => config
+                .diagonal_subsolver
+                .as_ref()
+                .is_none_or(|subsolver| subsolver.slackness == 0 && !subsolver.promotion_enabled),
+            PortfolioMember::IncreasingCostTreeSearch | PortfolioMember::MStar => true,
+            PortfolioMember::Lacam => false,
+        }
+    }
+
+    /// Whether the member can plan on `map`: only CBS without the diagonal
+    /// sub-solver plans for terrain costs, and LaCAM ignores timed obstacles.
+    fn supports(&self, map: &Grid) -> bool {
+        match self {
+            PortfolioMember::Cbs(config) => {
+                config.diagonal_subsolver.is_none() || !map.has_terrain_costs()
+            }
+            PortfolioMember::IncreasingCostTreeSearch | PortfolioMember::MStar => {
+                !map.has_terrain_costs()
+            }
+            PortfolioMember::Lacam => map.timed_obstacles.is_empty() && !map.has_terrain_costs(),
+        }
+    }
+
+    fn solve(
+        &self,
+        instance: CBSInstance,
+        agents: &[Agent],
+        cancellation: CancellationToken,
+    ) -> Result<MemberSolution, SearchError> {
+        let in_agent_order = |paths: HashMap<&Agent, Path>| {
+            agents
+                .iter()
+                .map(|agent| paths[agent].clone())
+                .collect::<Vec<_>>()
+        };
+        let outcome = match self {
+            PortfolioMember::Cbs(config) => {
+                let mut solver =
+                    CBS::new(instance, Some(config.clone())).with_cancellation(cancellation);
+                let paths = solver.solve().map(in_agent_order);
+                paths.map(|paths| (paths, solver.high_level_generated))
+            }
+            PortfolioMember::IncreasingCostTreeSearch => {
+                let mut solver =
+                    IncreasingCostTreeSearch::new(instance).with_cancellation(cancellation);
+                let paths = solver.solve().map(in_agent_order);
+                paths.map(|paths| (paths, solver.ict_nodes_generated))
+            }
+            PortfolioMember::MStar => {
+                let mut solver = MStar::new(instance).with_cancellation(cancellation);
+                let paths = solver.solve().map(in_agent_order);
+                paths.map(|paths| (paths, solver.nodes_generated))
+            }
+            PortfolioMember::Lacam => {
+                let mut solver = LaCAM::new(instance).with_cancellation(cancellation);
+                let paths = solver.solve().map(in_agent_order);
+                paths.map(|paths| (paths, solver.high_level_generated))
+            }
+        };
+        outcome.map_err(|error| match error.downcast::<SearchError>() {
+            Ok(error) => *error,
+            Err(error) => SearchError::InvalidArguments(error.to_string()),
+        })
+    }
+}
+
+pub struct Portfolio {
+    instance: CBSInstance,
+    members: Vec<PortfolioMember>,
+    cancellation: Option<CancellationToken>,
+    /// The member whose solution was returned.
+    pub winner: Option<PortfolioMember>,
+    pub nodes_generated: usize,
+}
+
+impl Portfolio {
+    pub fn new(instance: CBSInstance) -> Self {
+        Self {
+            instance,
+            members: PortfolioMember::defaults(),
+            cancellation: None,
+            winner: None,
+            nodes_generated: 0,
+        }
+    }
+
+    /// Replaces the [default members](PortfolioMember::defaults).
+    pub fn with_members(mut self, members: Vec<PortfolioMember>) -> Self {
+        self.members = members;
+        self
+    }
+
+    /// Stops all members once `cancellation` is cancelled. [`Self::solve`] then
+    /// returns the best suboptimal solution found so far, or fails with
+    /// [`SearchError::Cancelled`] if there is none.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<HashMap<&Agent, Path>, Box<dyn Error>> {
+        self.instance
+            .validate()
+            .map_err(|problems| Box::new(CBSError::InvalidInstance(problems)))?;
+        let members = self
+            .members
+            .iter()
+            .filter(|member| member.supports(&self.instance.map))
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            return Err(Box::new(SearchError::InvalidArguments(
+                "no portfolio member can solve this instance".to_string(),
+            )));
+        }
+        let stop = CancellationToken::new();
+        let (sender, receiver) = mpsc::channel();
+        let mut best = None::<(usize, MemberSolution)>;
+        let mut error = None::<SearchError>;
+        // the scope joins the members, which stop at their next cancellation
+        // check, so none of them outlives the solve
+        thread::scope(|scope| {
+            for (index, member) in members.iter().enumerate() {
+                let instance = self.instance.clone();
+                let agents = &self.instance.agents;
+                let stop = stop.clone();
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let outcome = member.solve(instance, agents, stop);
+                    // the receiver is gone once a winner has been picked
+                    let _ = sender.send((index, outcome));
+                });
+            }
+            drop(sender);
+            loop {
+                match receiver.recv_timeout(POLL_INTERVAL) {
+                    Ok((index, Ok(solution))) => {
+                        log::debug!("portfolio member {} found a solution", members[index]);
+                        if members[index].is_optimal() {
+                            best = Some((index, solution));
+                            break;
+                        }
+                        let cost = sum_of_costs(&solution.0);
+                        if best
+                            .as_ref()
+                            .is_none_or(|(_, best)| cost < sum_of_costs(&best.0))
+                        {
+                            best = Some((index, solution));
+                        }
+                    }
+                    Ok((index, Err(member_error))) => {
+                        log::debug!(
+                            "portfolio member {} failed: {}",
+                            members[index],
+                            member_error
+                        );
+                        // members give up on a bound with other errors, so
+                        // NotFound proves that there is no solution
+                        let proven_unsolvable = matches!(member_error, SearchError::NotFound);
+                        if !matches!(member_error, SearchError::Cancelled) {
+                            error = Some(member_error);
+                        }
+                        if proven_unsolvable {
+                            break;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        if self
+                            .cancellation
+                            .as_ref()
+                            .is_some_and(|cancellation| cancellation.is_cancelled())
+                        {
+                            break;
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            drop(receiver);
+            stop.cancel();
+        });
+        let Some((index, (paths, nodes_generated))) = best else {
+            return Err(Box::new(error.unwrap_or(SearchError::Cancelled)));
+        };
+        self.winner = Some(members[index].clone());
+        self.nodes_generated = nodes_generated;
+        let paths = self
+            .instance
+            .agents
+            .iter()
+            .zip(paths)
+            .collect::<HashMap<_, _>>();
+        Ok(paths)
+    }
+}
+
+fn sum_of_costs(paths: &[Path]) -> usize {
+    paths.iter().map(|path| path.len() - 1).sum()
+}
+
+#[cfg(test)]
+mod tests;
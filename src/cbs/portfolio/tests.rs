@@ -0,0 +1,152 @@
+use rstest::rstest;
+
+use super::*;
+use crate::cbs::{
+    low_level::{Grid, LocationTime},
+    operator_decomposition::OperatorDecomposition,
+};
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+fn empty_grid(width: i32, height: i32) -> Grid {
+    Grid::new(
+        width,
+        height,
+        Grid::to_conditional_obstacles(Vec::new()),
+        (0, 0),
+    )
+}
+
+#[rstest]
+#[case::independent(vec![agent("a", (0, 0), (0, 3)), agent("b", (3, 3), (3, 0))])]
+#[case::crossing(vec![agent("a", (0, 1), (2, 1)), agent("b", (1, 0), (1, 2))])]
+#[case::swapping(vec![agent("a", (0, 0), (3, 0)), agent("b", (3, 0), (0, 0))])]
+#[case::already_at_goal(vec![agent("a", (1, 1), (1, 1)), agent("b", (0, 1), (2, 1))])]
+fn test_optimal_cost(#[case] agents: Vec<Agent>) {
+    let mut oracle = OperatorDecomposition::new(CBSInstance::new(empty_grid(4, 4), agents.clone()));
+    let expected = oracle
+        .solve()
+        .unwrap()
+        .values()
+        .map(|path| path.len() - 1)
+        .sum::<usize>();
+    let mut portfolio = Portfolio::new(CBSInstance::new(empty_grid(4, 4), agents));
+    let paths = portfolio.solve().unwrap();
+    assert_eq!(
+        paths.values().map(|path| path.len() - 1).sum::<usize>(),
+        expected
+    );
+    for (agent, path) in &paths {
+        assert_eq!(path.first(), Some(&agent.start));
+        assert_eq!(path.last(), Some(&agent.goal));
+    }
+    assert!(portfolio.winner.unwrap().is_optimal());
+}
+
+#[test]
+fn test_suboptimal_members_only() {
+    let agents = vec![agent("a", (0, 0), (3, 0)), agent("b", (3, 0), (0, 0))];
+    let mut portfolio = Portfolio::new(CBSInstance::new(empty_grid(4, 4), agents))
+        .with_members(vec![PortfolioMember::Lacam]);
+    let paths = portfolio.solve().unwrap();
+    assert_eq!(paths.len(), 2);
+    assert!(matches!(portfolio.winner, Some(PortfolioMember::Lacam)));
+}
+
+#[test]
+fn test_skips_members_without_timed_obstacles() {
+    let grid = Grid::new(
+        3,
+        1,
+        Grid::to_conditional_obstacles(vec![LocationTime::new((1, 0), 1)]),
+        (0, 0),
+    );
+    let mut portfolio = Portfolio::new(CBSInstance::new(grid, vec![agent("a", (0, 0), (2, 0))]))
+        .with_members(vec![PortfolioMember::Lacam, PortfolioMember::MStar]);
+    let paths = portfolio.solve().unwrap();
+    assert_eq!(
+        paths.values().next().unwrap(),
+        &vec![(0, 0), (0, 0), (1, 0), (2, 0)]
+    );
+    assert!(matches!(portfolio.winner, Some(PortfolioMember::MStar)));
+}
+
+#[test]
+fn test_skips_members_without_terrain_costs() {
+    let grid = Grid::try_from("type octile\nheight 1\nwidth 3\nmap\n.S.\n".to_string()).unwrap();
+    let mut portfolio = Portfolio::new(CBSInstance::new(grid, vec![agent("a", (0, 0), (2, 0))]))
+        .with_members(vec![
+            PortfolioMember::Lacam,
+            PortfolioMember::cbs_with_diagonal_subsolver(),
+            PortfolioMember::cbs_with_dg(),
+        ]);
+    let paths = portfolio.solve().unwrap();
+    assert_eq!(
+        paths.values().next().unwrap(),
+        &vec![(0, 0), (1, 0), (1, 0), (2, 0)]
+    );
+    assert!(matches!(
+        portfolio.winner,
+        Some(PortfolioMember::Cbs(config)) if config.diagonal_subsolver.is_none()
+    ));
+}
+
+#[test]
+fn test_unsolvable() {
+    let mut portfolio = Portfolio::new(CBSInstance::new(
+        empty_grid(3, 1),
+        vec![agent("a", (0, 0), (2, 0)), agent("b", (2, 0), (0, 0))],
+    ))
+    .with_members(vec![PortfolioMember::MStar, PortfolioMember::Lacam]);
+    let error = portfolio.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::NotFound)
+    ));
+}
+
+#[test]
+fn test_cancelled() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    // without a deadline, CBS would search the unsolvable instance forever
+    let mut portfolio = Portfolio::new(CBSInstance::new(
+        empty_grid(3, 1),
+        vec![agent("a", (0, 0), (2, 0)), agent("b", (2, 0), (0, 0))],
+    ))
+    .with_members(vec![PortfolioMember::Cbs(CBSOptimisationConfig::new(
+        false, false, None, false, None,
+    ))])
+    .with_cancellation(cancellation);
+    let error = portfolio.solve().unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SearchError>(),
+        Some(SearchError::Cancelled)
+    ));
+}
+
+#[test]
+fn test_returns_suboptimal_solution_at_deadline() {
+    // too crowded for CBS to finish within the deadline
+    let cells = (0..4)
+        .flat_map(|y| (0..4).map(move |x| (x, y)))
+        .collect::<Vec<_>>();
+    let agents = (0..12)
+        .map(|i| agent(&i.to_string(), cells[i], cells[15 - i]))
+        .collect::<Vec<_>>();
+    let mut portfolio = Portfolio::new(CBSInstance::new(empty_grid(4, 4), agents))
+        .with_members(vec![
+            PortfolioMember::Cbs(CBSOptimisationConfig::new(false, false, None, false, None)),
+            PortfolioMember::Lacam,
+        ])
+        .with_cancellation(CancellationToken::with_timeout(Duration::from_millis(500)));
+    let paths = portfolio.solve().unwrap();
+    assert_eq!(paths.len(), 12);
+    assert!(matches!(portfolio.winner, Some(PortfolioMember::Lacam)));
+}
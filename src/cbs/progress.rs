@@ -0,0 +1,135 @@
+//! Live reports on a running high-level search.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+use super::search::SearchProgress;
+
+/// Receives the reports of [`super::CBS::with_progress`].
+pub type ProgressObserver = Box<dyn FnMut(&ProgressReport) + Send>;
+
+/// Why a report was made. When several apply, the first one listed wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// The search ended, with or without a solution.
+    Finished,
+    LowerBoundImproved,
+    ConflictsImproved,
+    /// The report interval has passed since the previous report.
+    Periodic,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProgressReport {
+    pub event: ProgressEvent,
+    pub nodes_generated: usize,
+    pub nodes_expanded: usize,
+    pub open_list_size: usize,
+    /// The highest f value expanded so far, a lower bound on the optimal sum of costs.
+    pub lower_bound: f64,
+    /// The fewest conflicts of any expanded node.
+    pub best_num_conflicts: usize,
+    #[serde(rename = "elapsed_ms", serialize_with = "serialize_millis")]
+    pub elapsed: Duration,
+}
+
+fn serialize_millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+impl fmt::Display for ProgressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:>9.3}s] lower bound {}, best conflicts {}, generated {}, expanded {}, open {}",
+            self.elapsed.as_secs_f64(),
+            self.lower_bound,
+            self.best_num_conflicts,
+            self.nodes_generated,
+            self.nodes_expanded,
+            self.open_list_size
+        )?;
+        match self.event {
+            ProgressEvent::Finished => write!(f, " (finished)"),
+            ProgressEvent::LowerBoundImproved => write!(f, " (lower bound improved)"),
+            ProgressEvent::ConflictsImproved => write!(f, " (conflicts improved)"),
+            ProgressEvent::Periodic => Ok(()),
+        }
+    }
+}
+
+/// Turns the nodes popped by the search into reports for an observer.
+pub(crate) struct ProgressTracker {
+    observer: ProgressObserver,
+    interval: Duration,
+    start: Instant,
+    last_report: Instant,
+    report: ProgressReport,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(observer: ProgressObserver, interval: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            observer,
+            interval,
+            start: now,
+            last_report: now,
+            report: ProgressReport {
+                event: ProgressEvent::Periodic,
+                nodes_generated: 0,
+                nodes_expanded: 0,
+                open_list_size: 0,
+                lower_bound: 0.0,
+                best_num_conflicts: usize::MAX,
+                elapsed: Duration::ZERO,
+            },
+        }
+    }
+
+    /// Records that a node with cost `f` and `num_conflicts` conflicts is about to be expanded.
+    pub(crate) fn expanding(&mut self, f: f64, num_conflicts: usize, search: &SearchProgress) {
+        self.update(search);
+        let mut event = None;
+        if self.last_report.elapsed() >= self.interval {
+            event = Some(ProgressEvent::Periodic);
+        }
+        if num_conflicts < self.report.best_num_conflicts {
+            self.report.best_num_conflicts = num_conflicts;
+            event = Some(ProgressEvent::ConflictsImproved);
+        }
+        if f > self.report.lower_bound {
+            self.report.lower_bound = f;
+            event = Some(ProgressEvent::LowerBoundImproved);
+        }
+        if let Some(event) = event {
+            self.emit(event);
+        }
+    }
+
+    pub(crate) fn finished(&mut self) {
+        self.report.elapsed = self.start.elapsed();
+        self.emit(ProgressEvent::Finished);
+    }
+
+    fn update(&mut self, search: &SearchProgress) {
+        self.report.nodes_generated = search.nodes_generated;
+        self.report.nodes_expanded = search.nodes_expanded;
+        self.report.open_list_size = search.open_list_size;
+        self.report.elapsed = self.start.elapsed();
+    }
+
+    fn emit(&mut self, event: ProgressEvent) {
+        self.report.event = event;
+        self.last_report = Instant::now();
+        (self.observer)(&self.report);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,100 @@
+use std::sync::{Arc, Mutex};
+
+use super::*;
+use crate::cbs::{high_level::Agent, low_level::Grid, CBSInstance, CBS};
+
+fn recorder() -> (ProgressObserver, Arc<Mutex<Vec<ProgressReport>>>) {
+    let reports = Arc::new(Mutex::new(Vec::<ProgressReport>::new()));
+    let recorded = Arc::clone(&reports);
+    (
+        Box::new(move |report| recorded.lock().unwrap().push(report.clone())),
+        reports,
+    )
+}
+
+fn search(nodes_generated: usize, nodes_expanded: usize) -> SearchProgress {
+    SearchProgress {
+        nodes_generated,
+        nodes_expanded,
+        open_list_size: nodes_generated - nodes_expanded,
+    }
+}
+
+#[test]
+fn test_reports_improvements() {
+    let (observer, reports) = recorder();
+    let mut tracker = ProgressTracker::new(observer, Duration::from_secs(3600));
+    tracker.expanding(10.0, 4, &search(0, 0));
+    tracker.expanding(10.0, 4, &search(2, 1));
+    tracker.expanding(10.0, 2, &search(4, 2));
+    tracker.expanding(11.0, 3, &search(6, 3));
+    tracker.expanding(11.0, 3, &search(8, 4));
+    tracker.finished();
+    let reports = reports.lock().unwrap();
+    let events = reports.iter().map(|r| r.event).collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            ProgressEvent::LowerBoundImproved,
+            ProgressEvent::ConflictsImproved,
+            ProgressEvent::LowerBoundImproved,
+            ProgressEvent::Finished,
+        ]
+    );
+    assert_eq!(reports[2].lower_bound, 11.0);
+    assert_eq!(reports[2].best_num_conflicts, 2);
+    assert_eq!(reports[3].nodes_generated, 8);
+    assert_eq!(reports[3].open_list_size, 4);
+}
+
+#[test]
+fn test_reports_periodically() {
+    let (observer, reports) = recorder();
+    let mut tracker = ProgressTracker::new(observer, Duration::ZERO);
+    tracker.expanding(0.0, 0, &search(0, 0));
+    tracker.expanding(0.0, 0, &search(2, 1));
+    let events = reports
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|r| r.event)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![ProgressEvent::ConflictsImproved, ProgressEvent::Periodic]
+    );
+}
+
+#[test]
+fn test_solve_reports_progress() {
+    let instance = CBSInstance::new(
+        Grid::new(3, 3, Grid::to_conditional_obstacles(Vec::new()), (2, 2)),
+        vec![
+            Agent {
+                id: "a".to_string(),
+                start: (0, 1),
+                goal: (2, 1),
+            },
+            Agent {
+                id: "b".to_string(),
+                start: (1, 0),
+                goal: (1, 2),
+            },
+        ],
+    );
+    let (observer, reports) = recorder();
+    let mut cbs = CBS::new(instance, None).with_progress(observer, Duration::from_secs(3600));
+    cbs.solve().expect("crossing agents are solvable");
+    let reports = reports.lock().unwrap();
+    let first = reports.first().unwrap();
+    assert_eq!(first.lower_bound, 4.0);
+    assert_eq!(first.best_num_conflicts, 1);
+    let last = reports.last().unwrap();
+    assert_eq!(last.event, ProgressEvent::Finished);
+    assert_eq!(last.lower_bound, 5.0);
+    assert_eq!(last.best_num_conflicts, 0);
+    assert!(last.nodes_generated >= 2);
+    let json = serde_json::to_value(last).unwrap();
+    assert_eq!(json["event"], "finished");
+    assert!(json["elapsed_ms"].is_u64());
+}
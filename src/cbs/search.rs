@@ -3,7 +3,7 @@ use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::hash::Hash;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub trait AStarNode<'a> {
     fn g(&'a self) -> f64;
@@ -18,6 +18,9 @@ pub trait AStarNode<'a> {
 pub enum SearchError {
     InvalidArguments(String),
     NotFound,
+    Cancelled,
+    /// The search gave up once the sum of costs exceeded the given bound.
+    CostLimitReached(i32),
 }
 
 impl std::fmt::Display for SearchError {
@@ -25,6 +28,10 @@ impl std::fmt::Display for SearchError {
         match self {
             SearchError::InvalidArguments(s) => write!(f, "Invalid arguments: {}", s),
             SearchError::NotFound => write!(f, "Not found"),
+            SearchError::Cancelled => write!(f, "Cancelled"),
+            SearchError::CostLimitReached(limit) => {
+                write!(f, "No solution with a sum of costs up to {}", limit)
+            }
         }
     }
 }
@@ -36,15 +43,15 @@ pub(crate) struct HeapNode<T>
 where
     for<'a> T: AStarNode<'a> + Clone,
 {
-    node: Rc<T>,
-    prev: Option<Rc<HeapNode<T>>>,
+    node: Arc<T>,
+    prev: Option<Arc<HeapNode<T>>>,
 }
 
 impl<T> HeapNode<T>
 where
     for<'a> T: AStarNode<'a> + Clone,
 {
-    pub(crate) fn new(node: Rc<T>) -> HeapNode<T> {
+    pub(crate) fn new(node: Arc<T>) -> HeapNode<T> {
         HeapNode { node, prev: None }
     }
 }
@@ -100,6 +107,14 @@ where
     }
 }
 
+/// Counters of a running search, passed to the monitor of [`a_star_monitored`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchProgress {
+    pub nodes_generated: usize,
+    pub nodes_expanded: usize,
+    pub open_list_size: usize,
+}
+
 pub struct AStarSolution<T>
 where
     for<'a> T: AStarNode<'a> + Clone,
@@ -108,7 +123,7 @@ where
     pub nodes_generated: i32,
 }
 
-fn reconstruct_path<T>(mut current: Rc<HeapNode<T>>) -> Vec<T>
+fn reconstruct_path<T>(mut current: Arc<HeapNode<T>>) -> Vec<T>
 where
     for<'a> T: AStarNode<'a> + Clone,
 {
@@ -116,7 +131,7 @@ where
     loop {
         path.push((*current.node).clone());
         if let Some(prev) = &current.prev {
-            current = Rc::clone(prev);
+            current = Arc::clone(prev);
         } else {
             break;
         }
@@ -126,35 +141,74 @@ where
 }
 
 pub fn a_star<T>(start: T) -> Result<AStarSolution<T>, SearchError>
+where
+    for<'a> T: AStarNode<'a> + Clone + std::hash::Hash + Eq,
+{
+    a_star_until(start, &|| false)
+}
+
+/// Like [`a_star`], but gives up with [`SearchError::Cancelled`] as soon as
+/// `should_stop` returns true. It is polled before every expansion.
+pub fn a_star_until<T>(
+    start: T,
+    should_stop: &dyn Fn() -> bool,
+) -> Result<AStarSolution<T>, SearchError>
+where
+    for<'a> T: AStarNode<'a> + Clone + std::hash::Hash + Eq,
+{
+    a_star_monitored(start, &mut |_, _| should_stop())
+}
+
+/// Like [`a_star_until`], but `monitor` also sees every node popped from
+/// the open list before it is expanded, along with the search counters.
+/// Stale copies of nodes reached again more cheaply are skipped unseen.
+pub fn a_star_monitored<T>(
+    start: T,
+    monitor: &mut dyn FnMut(&T, &SearchProgress) -> bool,
+) -> Result<AStarSolution<T>, SearchError>
 where
     for<'a> T: AStarNode<'a> + Clone + std::hash::Hash + Eq,
 {
     let mut frontier = BinaryHeap::<Reverse<HeapNode<T>>>::new();
-    let mut best_g = HashMap::<Rc<T>, f64>::new();
-    let start = Rc::new(start);
+    let mut best_g = HashMap::<Arc<T>, f64>::new();
+    let start = Arc::new(start);
     let start_g = start.g();
-    frontier.push(Reverse(HeapNode::new(Rc::clone(&start))));
+    frontier.push(Reverse(HeapNode::new(Arc::clone(&start))));
     best_g.insert(start, start_g);
-    return stateful_a_star(&mut frontier, &mut best_g, f64::INFINITY);
+    return stateful_a_star(&mut frontier, &mut best_g, f64::INFINITY, monitor);
 }
 
 pub(crate) fn stateful_a_star<T>(
     frontier: &mut BinaryHeap<Reverse<HeapNode<T>>>,
-    best_g: &mut HashMap<Rc<T>, f64>,
+    best_g: &mut HashMap<Arc<T>, f64>,
     max_g: f64,
+    monitor: &mut dyn FnMut(&T, &SearchProgress) -> bool,
 ) -> Result<AStarSolution<T>, SearchError>
 where
     for<'a> T: AStarNode<'a> + Clone + std::hash::Hash + Eq,
 {
     let t0 = std::time::Instant::now();
     let mut nodes_generated = 0;
+    let mut nodes_expanded = 0;
 
     loop {
         if frontier.is_empty() {
             return Err(SearchError::NotFound);
         }
         let Reverse(current) = frontier.pop().expect("heap should not be empty");
-        let current = Rc::new(current);
+        let current = Arc::new(current);
+        // a cheaper copy of the node was found after this one was pushed
+        if current.node.g() > *best_g.get(&current.node).unwrap_or(&f64::INFINITY) {
+            continue;
+        }
+        let progress = SearchProgress {
+            nodes_generated: nodes_generated as usize,
+            nodes_expanded,
+            open_list_size: frontier.len(),
+        };
+        if monitor(&current.node, &progress) {
+            return Err(SearchError::Cancelled);
+        }
         if current.node.is_goal() {
             debug!(
                 "A* took {:?} - {:} nodes generated",
@@ -162,43 +216,41 @@ where
                 nodes_generated
             );
             return Ok(AStarSolution {
-                path: reconstruct_path(Rc::clone(&current)),
+                path: reconstruct_path(Arc::clone(&current)),
                 nodes_generated,
             });
         }
         if current.node.g() > max_g {
             frontier.push(Reverse(HeapNode {
-                node: Rc::clone(&current.node),
+                node: Arc::clone(&current.node),
                 prev: match &current.prev {
-                    Some(prev) => Some(Rc::clone(prev)),
+                    Some(prev) => Some(Arc::clone(prev)),
                     None => None,
                 },
             }));
             return Err(SearchError::NotFound);
         }
-        if current.node.g() > *best_g.get(&current.node).unwrap_or(&f64::INFINITY) {
-            continue;
-        }
+        nodes_expanded += 1;
         match current.node.expand() {
             Some(expand) => {
                 for neighbor in expand {
-                    let neighbor = Rc::new(*neighbor);
+                    let neighbor = Arc::new(*neighbor);
                     if neighbor.g() >= *best_g.get(&neighbor).unwrap_or(&f64::INFINITY) {
                         continue;
                     }
-                    best_g.insert(Rc::clone(&neighbor), neighbor.g());
+                    best_g.insert(Arc::clone(&neighbor), neighbor.g());
                     frontier.push(Reverse(HeapNode {
-                        node: Rc::clone(&neighbor),
-                        prev: Some(Rc::clone(&current)),
+                        node: Arc::clone(&neighbor),
+                        prev: Some(Arc::clone(&current)),
                     }));
                     nodes_generated += 1;
                 }
             }
             None => {
                 frontier.push(Reverse(HeapNode {
-                    node: Rc::clone(&current.node),
+                    node: Arc::clone(&current.node),
                     prev: match &current.prev {
-                        Some(prev) => Some(Rc::clone(prev)),
+                        Some(prev) => Some(Arc::clone(prev)),
                         None => None,
                     },
                 }));
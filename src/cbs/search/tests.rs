@@ -87,6 +87,116 @@ fn test_a_star() {
     assert_eq!(result.path[result.path.len() - 4].id, "a");
 }
 
+/// A node equal to the other nodes with the same id, whatever its cost.
+#[derive(Debug, Clone)]
+struct RevisitedNode {
+    id: &'static str,
+    g: f64,
+}
+
+impl Hash for RevisitedNode {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl PartialEq for RevisitedNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for RevisitedNode {}
+
+impl AStarNode<'_> for RevisitedNode {
+    fn g(&self) -> f64 {
+        self.g
+    }
+
+    fn h(&self) -> f64 {
+        if self.is_goal() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    fn is_goal(&self) -> bool {
+        self.id == "goal"
+    }
+
+    fn tie_breaker(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+
+    fn expand(&self) -> Option<Vec<Box<Self>>> {
+        let node = |id, g| Box::new(RevisitedNode { id, g });
+        Some(match self.id {
+            "a" => vec![node("b", 1.0), node("x", 4.0)],
+            "b" => vec![node("x", 2.0)],
+            "x" => vec![node("goal", self.g + 8.0)],
+            _ => vec![],
+        })
+    }
+
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+#[test]
+fn test_a_star_monitored_skips_stale_nodes() {
+    let mut seen = Vec::<(&str, f64)>::new();
+    let result = a_star_monitored(RevisitedNode { id: "a", g: 0.0 }, &mut |node, _| {
+        seen.push((node.id, node.g));
+        false
+    });
+    assert_eq!(result.unwrap().path.last().unwrap().g, 10.0);
+    // the copy of x reached first, at cost 4, is popped but not seen
+    assert_eq!(seen, vec![("a", 0.0), ("b", 1.0), ("x", 2.0), ("goal", 10.0)]);
+}
+
+#[test]
+fn test_a_star_monitored() {
+    let c = TestNode {
+        id: "c".to_string(),
+        score: 2.0,
+        h: 1.0,
+        expand: Vec::new(),
+    };
+    let b = TestNode {
+        id: "b".to_string(),
+        score: 1.0,
+        h: 3.0,
+        expand: Vec::new(),
+    };
+    let a = TestNode {
+        id: "a".to_string(),
+        score: 0.0,
+        h: 1.0,
+        expand: vec![Box::new(b), Box::new(c)],
+    };
+    let mut seen = Vec::<(String, SearchProgress)>::new();
+    let result = a_star_monitored(a, &mut |node, progress| {
+        seen.push((node.id.clone(), progress.clone()));
+        node.id == "b"
+    });
+    assert!(matches!(result, Err(SearchError::Cancelled)));
+    let expected = [("a", 0, 0, 0), ("c", 2, 1, 1), ("b", 2, 2, 0)];
+    assert_eq!(seen.len(), expected.len());
+    for ((id, progress), (expected_id, generated, expanded, open)) in seen.iter().zip(expected) {
+        assert_eq!(id, expected_id);
+        assert_eq!(
+            *progress,
+            SearchProgress {
+                nodes_generated: generated,
+                nodes_expanded: expanded,
+                open_list_size: open,
+            }
+        );
+    }
+}
+
 #[rstest]
 #[case(
     vec![].into_iter().collect::<HashSet<String>>(),
@@ -0,0 +1,415 @@
+//! A resident HTTP/JSON planning service. The worker threads share the parsed
+//! maps and their distance tables, so requests on the same map share the warmup.
+//!
+//! - `GET /maps` lists the preloaded maps.
+//! - `POST /plan` solves a [`PlanRequest`] and returns a result like the batch mode.
+//! - `POST /cancel/<id>` cancels the running or queued request with that id.
+//! - `GET /progress/<id>` returns the latest progress report of the running request with that id.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    fs::File,
+    net::SocketAddr,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::{
+    batch::{solve_instance, BatchAgent, BatchOutcome, BatchResult},
+    cancellation::CancellationToken,
+    io::read_map,
+    low_level::{AStarLowLevelSolver, Grid},
+    progress::{ProgressObserver, ProgressReport},
+    terrain::TerrainTable,
+    CBSOptimisationConfig, DiagonalSubsolverConfig, HighLevelHeuristic,
+};
+
+pub struct ServiceConfig {
+    /// Address to listen on, e.g. `127.0.0.1:8080`.
+    pub address: String,
+    /// Names and files of the maps requests may refer to.
+    pub maps: Vec<(String, String)>,
+    pub terrain: TerrainTable,
+    /// Number of requests solved concurrently. Further requests wait for a
+    /// free worker, and can be cancelled while they wait.
+    pub workers: usize,
+    /// Directory to keep the distance tables of the maps in between runs.
+    pub distance_cache: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlanRequest {
+    /// Lets the request be cancelled while it runs.
+    id: Option<String>,
+    map: String,
+    agents: Vec<BatchAgent>,
+    #[serde(default)]
+    config: PlanConfig,
+    timeout_ms: Option<u64>,
+    /// Stop after expanding this many high-level nodes.
+    node_limit: Option<usize>,
+}
+
+/// The optimisations to use. By default, conflicts are prioritised and
+/// bypassed, and the diagonal subsolver and a CAT are used with the zero
+/// heuristic.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlanConfig {
+    prioritising_conflicts: bool,
+    bypassing_conflicts: bool,
+    /// `null` disables the diagonal subsolver.
+    diagonal_subsolver: Option<PlanDiagonalSubsolver>,
+    conflict_avoidance_table: bool,
+    heuristic: PlanHeuristic,
+}
+
+impl Default for PlanConfig {
+    fn default() -> Self {
+        Self {
+            prioritising_conflicts: true,
+            bypassing_conflicts: true,
+            diagonal_subsolver: Some(PlanDiagonalSubsolver::default()),
+            conflict_avoidance_table: true,
+            heuristic: PlanHeuristic::Zero,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlanDiagonalSubsolver {
+    slackness: i32,
+    promotion: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PlanHeuristic {
+    Zero,
+    Dg,
+}
+
+impl From<&PlanConfig> for CBSOptimisationConfig {
+    fn from(config: &PlanConfig) -> Self {
+        CBSOptimisationConfig::new(
+            config.prioritising_conflicts,
+            config.bypassing_conflicts,
+            config.diagonal_subsolver.as_ref().map(|subsolver| {
+                DiagonalSubsolverConfig::new(subsolver.slackness, subsolver.promotion)
+            }),
+            config.conflict_avoidance_table,
+            Some(match config.heuristic {
+                PlanHeuristic::Zero => HighLevelHeuristic::ZeroHeuristic,
+                PlanHeuristic::Dg => HighLevelHeuristic::DGHeuristic,
+            }),
+        )
+    }
+}
+
+/// How often running requests update their progress report.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+struct RunningRequest {
+    cancellation: CancellationToken,
+    progress: Option<ProgressReport>,
+}
+
+type RunningRequests = Arc<Mutex<HashMap<String, RunningRequest>>>;
+type LoadedMaps = HashMap<String, (Grid, Arc<AStarLowLevelSolver>)>;
+
+pub struct Service {
+    server: Arc<Server>,
+    config: Arc<ServiceConfig>,
+    maps: Arc<LoadedMaps>,
+    running: RunningRequests,
+}
+
+impl Service {
+    /// Loads every map and starts listening.
+    pub fn bind(config: ServiceConfig) -> Result<Self, String> {
+        let maps = load_maps(&config)?;
+        let server = Server::http(&config.address).map_err(|e| e.to_string())?;
+        Ok(Self {
+            server: Arc::new(server),
+            config: Arc::new(config),
+            maps: Arc::new(maps),
+            running: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Serves requests until the process exits. Plans are queued for the
+    /// worker threads, so control requests are answered while every worker
+    /// is busy solving.
+    pub fn run(self) {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..self.config.workers.max(1) {
+            let worker = Worker {
+                maps: Arc::clone(&self.maps),
+                running: Arc::clone(&self.running),
+                jobs: Arc::clone(&receiver),
+            };
+            thread::spawn(move || worker.run());
+        }
+        let dispatcher = Dispatcher {
+            server: self.server,
+            maps: self.maps,
+            running: self.running,
+            jobs: sender,
+        };
+        dispatcher.run();
+    }
+}
+
+/// A plan admitted by the dispatcher, waiting for a worker.
+struct Job {
+    request: Request,
+    plan: PlanRequest,
+    cancellation: CancellationToken,
+}
+
+/// Answers the control requests itself and queues plans for the workers.
+struct Dispatcher {
+    server: Arc<Server>,
+    maps: Arc<LoadedMaps>,
+    running: RunningRequests,
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Dispatcher {
+    fn run(self) {
+        for mut request in self.server.incoming_requests() {
+            if (request.method(), request.url()) == (&Method::Post, "/plan") {
+                let admitted = serde_json::from_reader::<_, PlanRequest>(request.as_reader())
+                    .map_err(|e| (400, json!({ "error": format!("invalid request: {}", e) })))
+                    .and_then(|plan| {
+                        let cancellation = self.admit(&plan)?;
+                        Ok((plan, cancellation))
+                    });
+                match admitted {
+                    Ok((plan, cancellation)) => self
+                        .jobs
+                        .send(Job {
+                            request,
+                            plan,
+                            cancellation,
+                        })
+                        .expect("workers should not exit"),
+                    Err((status, body)) => respond(request, status, body),
+                }
+            } else {
+                let (status, body) = self.handle(&request);
+                respond(request, status, body);
+            }
+        }
+    }
+
+    fn handle(&self, request: &Request) -> (u16, serde_json::Value) {
+        let url = request.url();
+        match request.method() {
+            Method::Get if url == "/maps" => {
+                let mut names = self.maps.keys().collect::<Vec<_>>();
+                names.sort();
+                (200, json!({ "maps": names }))
+            }
+            Method::Post if url.starts_with("/cancel/") => {
+                let Some(id) = percent_decode(&url["/cancel/".len()..]) else {
+                    return (400, json!({ "error": "invalid request id" }));
+                };
+                match self.running.lock().expect("lock is not poisoned").get(&id) {
+                    Some(running) => {
+                        running.cancellation.cancel();
+                        (200, json!({ "cancelled": id }))
+                    }
+                    None => (
+                        404,
+                        json!({ "error": format!("no running request '{}'", id) }),
+                    ),
+                }
+            }
+            Method::Get if url.starts_with("/progress/") => {
+                let Some(id) = percent_decode(&url["/progress/".len()..]) else {
+                    return (400, json!({ "error": "invalid request id" }));
+                };
+                match self.running.lock().expect("lock is not poisoned").get(&id) {
+                    Some(running) => (200, json!({ "id": id, "progress": running.progress })),
+                    None => (
+                        404,
+                        json!({ "error": format!("no running request '{}'", id) }),
+                    ),
+                }
+            }
+            _ => (404, json!({ "error": "not found" })),
+        }
+    }
+
+    /// Checks the map of a plan and registers its id, so the plan can be
+    /// cancelled while it waits for a worker.
+    fn admit(&self, plan: &PlanRequest) -> Result<CancellationToken, (u16, serde_json::Value)> {
+        if !self.maps.contains_key(&plan.map) {
+            return Err((
+                400,
+                json!({ "error": format!("unknown map '{}'", plan.map) }),
+            ));
+        }
+        let cancellation = match plan.timeout_ms {
+            Some(timeout) => CancellationToken::with_timeout(Duration::from_millis(timeout)),
+            None => CancellationToken::new(),
+        };
+        if let Some(id) = &plan.id {
+            let mut running = self.running.lock().expect("lock is not poisoned");
+            if running.contains_key(id) {
+                return Err((
+                    409,
+                    json!({ "error": format!("request '{}' is already running", id) }),
+                ));
+            }
+            running.insert(
+                id.clone(),
+                RunningRequest {
+                    cancellation: cancellation.clone(),
+                    progress: None,
+                },
+            );
+        }
+        Ok(cancellation)
+    }
+}
+
+/// Solves the queued plans, one at a time.
+struct Worker {
+    maps: Arc<LoadedMaps>,
+    running: RunningRequests,
+    jobs: Arc<Mutex<mpsc::Receiver<Job>>>,
+}
+
+impl Worker {
+    fn run(self) {
+        loop {
+            let job = self.jobs.lock().expect("lock is not poisoned").recv();
+            let Ok(job) = job else {
+                return;
+            };
+            let (status, body) = self.plan(job.plan, job.cancellation);
+            respond(job.request, status, body);
+        }
+    }
+
+    fn plan(&self, plan: PlanRequest, cancellation: CancellationToken) -> (u16, serde_json::Value) {
+        let (map, low_level_solver) = &self.maps[&plan.map];
+        let progress = plan.id.clone().map(|id| {
+            let running = Arc::clone(&self.running);
+            let observer = move |report: &ProgressReport| {
+                if let Some(request) = running.lock().expect("lock is not poisoned").get_mut(&id) {
+                    request.progress = Some(report.clone());
+                }
+            };
+            (Box::new(observer) as ProgressObserver, PROGRESS_INTERVAL)
+        });
+        // a panicking solve fails its request instead of the worker
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            solve_instance(
+                map.clone(),
+                Arc::clone(low_level_solver),
+                plan.agents,
+                Some((&plan.config).into()),
+                Some(cancellation),
+                plan.node_limit,
+                progress,
+            )
+        }));
+        if let Some(id) = &plan.id {
+            self.running
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .remove(id);
+        }
+        let (status, outcome) = match outcome {
+            Ok(outcome) => (200, outcome),
+            Err(payload) => {
+                let error = format!("the solver panicked: {}", panic_message(payload.as_ref()));
+                log::error!("{}", error);
+                (500, BatchOutcome::Error { error })
+            }
+        };
+        let result = BatchResult {
+            id: plan.id.unwrap_or_default(),
+            outcome,
+        };
+        (
+            status,
+            serde_json::to_value(result).expect("results are serialisable"),
+        )
+    }
+}
+
+fn respond(request: Request, status: u16, body: serde_json::Value) {
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes("Content-Type", "application/json").expect("header is valid"),
+        );
+    if let Err(e) = request.respond(response) {
+        log::warn!("could not respond: {}", e);
+    }
+}
+
+/// Decodes the `%XX` escapes of a URL path segment, `None` if an escape is
+/// malformed or the result is not UTF-8.
+fn percent_decode(segment: &str) -> Option<String> {
+    let mut bytes = Vec::<u8>::with_capacity(segment.len());
+    let mut rest = segment.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+fn load_maps(config: &ServiceConfig) -> Result<LoadedMaps, String> {
+    config
+        .maps
+        .iter()
+        .map(|(name, map_file)| {
+            let file = File::open(map_file).map_err(|e| format!("{}: {}", map_file, e))?;
+            let map =
+                read_map(file, &config.terrain).map_err(|e| format!("{}: {}", map_file, e))?;
+            let mut low_level_solver = AStarLowLevelSolver::new();
+            if let Some(directory) = &config.distance_cache {
+                low_level_solver = low_level_solver.with_distance_cache(directory);
+            }
+            Ok((name.clone(), (map, Arc::new(low_level_solver))))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,161 @@
+use super::*;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+fn start_service(workers: usize) -> SocketAddr {
+    let service = Service::bind(ServiceConfig {
+        address: "127.0.0.1:0".to_string(),
+        maps: vec![
+            (
+                "empty".to_string(),
+                "tests/testdata/maps/empty-16-16.map".to_string(),
+            ),
+            (
+                "corridor".to_string(),
+                "tests/testdata/maps/corridor-5-1.map".to_string(),
+            ),
+        ],
+        terrain: TerrainTable::default(),
+        workers,
+        distance_cache: None,
+    })
+    .unwrap();
+    let address = service.address().unwrap();
+    thread::spawn(move || service.run());
+    address
+}
+
+fn send(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[test]
+fn test_service() {
+    let address = start_service(2);
+    assert_eq!(
+        send(address, "GET", "/maps", ""),
+        (200, json!({ "maps": ["corridor", "empty"] }))
+    );
+
+    let (status, result) = send(
+        address,
+        "POST",
+        "/plan",
+        r#"{"id": "a", "map": "empty", "agents": [{"start": [0, 0], "goal": [3, 0]}, {"start": [3, 0], "goal": [3, 2]}], "config": {"heuristic": "dg", "diagonal_subsolver": null}}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(result["id"], "a");
+    assert_eq!(result["status"], "solved");
+    assert_eq!(result["cost"], 5);
+
+    let (status, result) = send(
+        address,
+        "POST",
+        "/plan",
+        r#"{"map": "empty", "agents": [{"start": [0, 0], "goal": [3, 0]}], "timeout_ms": 0}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(result["status"], "timed_out");
+
+    assert_eq!(
+        send(
+            address,
+            "POST",
+            "/plan",
+            r#"{"map": "other", "agents": []}"#
+        )
+        .0,
+        400
+    );
+    assert_eq!(send(address, "POST", "/plan", r#"{"map": "empty"}"#).0, 400);
+    assert_eq!(send(address, "POST", "/cancel/a", "").0, 404);
+    assert_eq!(send(address, "GET", "/progress/a", "").0, 404);
+    assert_eq!(send(address, "GET", "/plan", "").0, 404);
+}
+
+#[test]
+fn test_cancel_while_only_worker_is_busy() {
+    let address = start_service(1);
+    // swapping in a corridor has no solution, so the search runs until cancelled
+    let solve = thread::spawn(move || {
+        send(
+            address,
+            "POST",
+            "/plan",
+            r#"{"id": "swap 1/2", "map": "corridor", "agents": [{"start": [0, 0], "goal": [4, 0]}, {"start": [4, 0], "goal": [0, 0]}]}"#,
+        )
+    });
+    while send(address, "GET", "/progress/swap%201%2F2", "").0 != 200 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(
+        send(address, "POST", "/cancel/swap%201%2F2", ""),
+        (200, json!({ "cancelled": "swap 1/2" }))
+    );
+    let (status, result) = solve.join().unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(result["id"], "swap 1/2");
+    assert_eq!(result["status"], "cancelled");
+    assert_eq!(send(address, "GET", "/progress/swap%201%2F2", "").0, 404);
+    assert_eq!(send(address, "GET", "/progress/%zz", "").0, 400);
+}
+
+#[test]
+fn test_percent_decode() {
+    assert_eq!(percent_decode("a%20b%2Fc").as_deref(), Some("a b/c"));
+    assert_eq!(percent_decode("plain").as_deref(), Some("plain"));
+    assert_eq!(percent_decode("%e2%9c%93").as_deref(), Some("\u{2713}"));
+    assert_eq!(percent_decode("%2"), None);
+    assert_eq!(percent_decode("%zz"), None);
+    assert_eq!(percent_decode("%ff"), None);
+}
+
+#[test]
+fn test_cancelled_request() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let map = read_map(
+        File::open("tests/testdata/maps/empty-16-16.map").unwrap(),
+        &TerrainTable::default(),
+    )
+    .unwrap();
+    let agents =
+        serde_json::from_str::<Vec<BatchAgent>>(r#"[{"start": [0, 0], "goal": [3, 0]}]"#).unwrap();
+    let outcome = solve_instance(
+        map,
+        Arc::new(AStarLowLevelSolver::new()),
+        agents,
+        None,
+        Some(cancellation),
+        None,
+        None,
+    );
+    assert!(matches!(
+        outcome,
+        crate::cbs::batch::BatchOutcome::Cancelled { .. }
+    ));
+}
+
+#[test]
+fn test_panic_message() {
+    let payload = panic::catch_unwind(|| panic!("no path for agent {}", 3)).unwrap_err();
+    assert_eq!(panic_message(payload.as_ref()), "no path for agent 3");
+    let payload = panic::catch_unwind(|| panic!("static message")).unwrap_err();
+    assert_eq!(panic_message(payload.as_ref()), "static message");
+}
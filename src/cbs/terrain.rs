@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// How agents may use a cell of a given terrain type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TerrainClass {
+    Traversable,
+    Blocked,
+    /// Traversable, but entering the cell takes this many timesteps, during
+    /// which the agent stays in the cell.
+    Costly(u32),
+    /// Like [`TerrainClass::Costly`], but only entered from another
+    /// enclosed cell, like Moving AI water. Agents may leave it for any cell.
+    Enclosed(u32),
+}
+
+/// Maps Moving AI terrain characters to [`TerrainClass`]es.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerrainTable {
+    classes: HashMap<char, TerrainClass>,
+}
+
+impl Default for TerrainTable {
+    /// The Moving AI terrain types: passable terrain ('.', 'G'), out of
+    /// bounds ('@', 'O'), trees ('T'), swamp ('S'), which is passable, and
+    /// water ('W'), which is only passable from water.
+    fn default() -> Self {
+        Self {
+            classes: HashMap::from([
+                ('.', TerrainClass::Traversable),
+                ('G', TerrainClass::Traversable),
+                ('@', TerrainClass::Blocked),
+                ('O', TerrainClass::Blocked),
+                ('T', TerrainClass::Blocked),
+                ('S', TerrainClass::Costly(2)),
+                ('W', TerrainClass::Enclosed(2)),
+            ]),
+        }
+    }
+}
+
+impl TerrainTable {
+    pub fn set(&mut self, terrain: char, class: TerrainClass) {
+        self.classes.insert(terrain, class);
+    }
+
+    pub fn classify(&self, terrain: char) -> Option<TerrainClass> {
+        self.classes.get(&terrain).copied()
+    }
+}
+
+impl FromStr for TerrainTable {
+    type Err = String;
+
+    /// Parses overrides of the default table, e.g.
+    /// `S=costly:3,W=enclosed:3,T=traversable`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut table = TerrainTable::default();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (terrain, class) = entry
+                .split_once('=')
+                .ok_or(format!("expected <char>=<class>, got '{}'", entry))?;
+            let mut terrain_chars = terrain.trim().chars();
+            let terrain = match (terrain_chars.next(), terrain_chars.next()) {
+                (Some(c), None) if !c.is_whitespace() => c,
+                _ => return Err(format!("terrain must be a single character: '{}'", terrain)),
+            };
+            let class = match class.trim().split_once(':') {
+                None if class.trim() == "traversable" => TerrainClass::Traversable,
+                None if class.trim() == "blocked" => TerrainClass::Blocked,
+                Some(("costly", cost)) => TerrainClass::Costly(parse_cost(cost)?),
+                Some(("enclosed", cost)) => TerrainClass::Enclosed(parse_cost(cost)?),
+                _ => return Err(format!("unknown terrain class: '{}'", class)),
+            };
+            table.set(terrain, class);
+        }
+        Ok(table)
+    }
+}
+
+fn parse_cost(cost: &str) -> Result<u32, String> {
+    match cost.parse::<u32>() {
+        Ok(0) => Err("cost must be at least 1".to_string()),
+        Ok(cost) => Ok(cost),
+        Err(_) => Err(format!("cost is not a number: '{}'", cost)),
+    }
+}
+
+/// The terrain character of every cell of a map, in row-major order.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Terrain {
+    width: i32,
+    height: i32,
+    cells: Vec<char>,
+    table: TerrainTable,
+}
+
+impl fmt::Debug for Terrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Terrain")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Terrain {
+    pub(crate) fn new(width: i32, height: i32, cells: Vec<char>, table: TerrainTable) -> Self {
+        debug_assert_eq!(cells.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            cells,
+            table,
+        }
+    }
+
+    pub fn get(&self, location: (i32, i32)) -> Option<char> {
+        let (x, y) = location;
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return None;
+        }
+        Some(self.cells[(y * self.width + x) as usize])
+    }
+
+    /// Returns the locations whose terrain is one of `terrain`, in row-major order.
+    pub fn locations_of(&self, terrain: &[char]) -> Vec<(i32, i32)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| terrain.contains(c))
+            .map(|(i, _)| (i as i32 % self.width, i as i32 / self.width))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,40 @@
+use super::*;
+use rstest::rstest;
+
+#[rstest]
+#[case::empty(
+    "",
+    vec![
+        ('S', TerrainClass::Costly(2)),
+        ('W', TerrainClass::Enclosed(2)),
+        ('@', TerrainClass::Blocked),
+    ],
+)]
+#[case::overrides(
+    "S=costly:3, W=blocked,T=traversable",
+    vec![
+        ('S', TerrainClass::Costly(3)),
+        ('W', TerrainClass::Blocked),
+        ('T', TerrainClass::Traversable),
+        ('.', TerrainClass::Traversable),
+    ],
+)]
+#[case::enclosed("S=enclosed:4", vec![('S', TerrainClass::Enclosed(4))])]
+#[case::new_terrain("x=blocked", vec![('x', TerrainClass::Blocked)])]
+fn test_terrain_table_from_str(#[case] spec: &str, #[case] expected: Vec<(char, TerrainClass)>) {
+    let table = spec.parse::<TerrainTable>().unwrap();
+    for (terrain, class) in expected {
+        assert_eq!(table.classify(terrain), Some(class), "terrain: {}", terrain);
+    }
+}
+
+#[rstest]
+#[case::missing_class("S")]
+#[case::long_terrain("SW=blocked")]
+#[case::unknown_class("S=slow")]
+#[case::bad_cost("S=costly:x")]
+#[case::zero_cost("S=costly:0")]
+#[case::bad_enclosed_cost("W=enclosed:-1")]
+fn test_terrain_table_from_str_errors(#[case] spec: &str) {
+    assert!(spec.parse::<TerrainTable>().is_err());
+}
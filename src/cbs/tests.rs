@@ -1,5 +1,8 @@
 use super::*;
+use crate::cbs::{low_level::LocationTime, operator_decomposition::OperatorDecomposition};
+use proptest::{prelude::*, sample::subsequence};
 use rstest::rstest;
+use std::ops::Range;
 
 #[rstest]
 #[case::simple(
@@ -153,13 +156,7 @@ fn test_cbs(
     #[case] grid: Grid,
     #[case] exp_path_lengths: Vec<usize>,
 ) {
-    let mut cbs = CBS::new(
-        CBSInstance {
-            map: grid,
-            agents: agents.clone(),
-        },
-        optimisation_config,
-    );
+    let mut cbs = CBS::new(CBSInstance::new(grid, agents.clone()), optimisation_config);
     match cbs.solve() {
         Ok(paths) => {
             assert_eq!(paths.len(), exp_path_lengths.len());
@@ -286,3 +283,243 @@ fn test_cbs_from_files(
         Err(e) => panic!("Error: {:?}", e),
     }
 }
+
+fn optimisation_config() -> impl Strategy<Value = CBSOptimisationConfig> {
+    (0..32usize).prop_map(|i| all_optimisation_configs().swap_remove(i))
+}
+
+/// Every combination of the optimisations. Slackness and promotion of the
+/// diagonal subsolver add waits, trading optimality for speed, so the
+/// subsolver is only used without them.
+fn all_optimisation_configs() -> Vec<CBSOptimisationConfig> {
+    (0..32)
+        .map(|bits: u32| {
+            let flag = |bit: u32| bits & (1 << bit) != 0;
+            CBSOptimisationConfig::new(
+                flag(0),
+                flag(1),
+                flag(2).then(|| DiagonalSubsolverConfig::new(0, false)),
+                flag(3),
+                Some(if flag(4) {
+                    HighLevelHeuristic::DGHeuristic
+                } else {
+                    HighLevelHeuristic::ZeroHeuristic
+                }),
+            )
+        })
+        .collect()
+}
+
+fn agents_on(
+    cells: Vec<(i32, i32)>,
+    num_agents: Range<usize>,
+) -> impl Strategy<Value = Vec<Agent>> {
+    num_agents
+        .prop_flat_map(move |n| {
+            (
+                subsequence(cells.clone(), n).prop_shuffle(),
+                subsequence(cells.clone(), n).prop_shuffle(),
+            )
+        })
+        .prop_map(|(starts, goals)| {
+            starts
+                .into_iter()
+                .zip(goals)
+                .enumerate()
+                .map(|(i, (start, goal))| Agent {
+                    id: i.to_string(),
+                    start,
+                    goal,
+                })
+                .collect()
+        })
+}
+
+fn open_grid_agents() -> impl Strategy<Value = Vec<Agent>> {
+    agents_on(free_cells(&open_grid()), 2..5)
+}
+
+fn open_grid() -> Grid {
+    Grid::new(4, 4, Grid::to_conditional_obstacles(Vec::new()), (0, 0))
+}
+
+fn grid_from_rows(rows: &[&str]) -> Grid {
+    let obstacles = rows
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.chars()
+                .enumerate()
+                .filter(|(_, cell)| *cell == '@')
+                .map(move |(x, _)| LocationTime::new((x as i32, y as i32), -1))
+        })
+        .collect();
+    Grid::new(
+        rows[0].len() as i32,
+        rows.len() as i32,
+        Grid::to_conditional_obstacles(obstacles),
+        (0, 0),
+    )
+}
+
+/// Small maps on which every placement of up to four agents is solvable:
+/// each is 2-connected, not a cycle, and keeps plenty of cells free.
+/// Corridors make four agents too slow to solve without the optimisations.
+fn oracle_grids() -> Vec<Grid> {
+    vec![
+        open_grid(),
+        grid_from_rows(&["....", ".@..", "..@.", "...."]),
+        // two rooms joined by two corridors
+        grid_from_rows(&["......", "..@@..", "......"]),
+        grid_from_rows(&[".....", ".@.@.", "....."]),
+    ]
+}
+
+fn free_cells(map: &Grid) -> Vec<(i32, i32)> {
+    (0..map.width)
+        .flat_map(|x| (0..map.height).map(move |y| (x, y)))
+        .filter(|cell| map.is_valid_location(cell, cell))
+        .collect()
+}
+
+fn oracle_instance() -> impl Strategy<Value = (Grid, Vec<Agent>)> {
+    (0..oracle_grids().len()).prop_flat_map(|i| {
+        let map = oracle_grids().swap_remove(i);
+        let num_agents = if i == 0 { 2..5 } else { 2..4 };
+        let agents = agents_on(free_cells(&map), num_agents);
+        (Just(map), agents)
+    })
+}
+
+proptest! {
+    // every case solves the instance once per optimisation combination
+    #![proptest_config(ProptestConfig::with_cases(32))]
+    #[test]
+    fn test_optimal_cost_matches_joint_search((map, agents) in oracle_instance()) {
+        let mut oracle = OperatorDecomposition::new(CBSInstance::new(map.clone(), agents.clone()));
+        let expected = oracle
+            .solve()
+            .unwrap()
+            .values()
+            .map(|path| path.len() - 1)
+            .sum::<usize>();
+        for optimisation_config in all_optimisation_configs() {
+            let mut cbs = CBS::new(
+                CBSInstance::new(map.clone(), agents.clone()),
+                Some(optimisation_config.clone()),
+            );
+            let paths = cbs.solve().unwrap();
+            prop_assert_eq!(
+                paths.values().map(|path| path.len() - 1).sum::<usize>(),
+                expected,
+                "{:?}",
+                optimisation_config
+            );
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+    #[test]
+    fn test_threads_keep_the_cost_optimal(
+        agents in open_grid_agents(),
+        optimisation_config in optimisation_config(),
+    ) {
+        let cost = |threads| {
+            let mut cbs = CBS::new(
+                CBSInstance::new(open_grid(), agents.clone()),
+                Some(optimisation_config.clone()),
+            )
+            .with_threads(threads)
+            .record_conflict_tree();
+            cbs.solve()
+                .unwrap()
+                .values()
+                .map(|path| path.len() - 1)
+                .sum::<usize>()
+        };
+        prop_assert_eq!(cost(4), cost(1));
+    }
+}
+
+#[test]
+fn test_terrain_costs() {
+    let map =
+        Grid::try_from("type octile\nheight 3\nwidth 3\nmap\n...\n.S.\n...\n".to_string()).unwrap();
+    let agents = vec![
+        Agent {
+            id: "a".to_string(),
+            start: (0, 1),
+            goal: (2, 1),
+        },
+        Agent {
+            id: "b".to_string(),
+            start: (1, 0),
+            goal: (1, 2),
+        },
+    ];
+    for config in all_optimisation_configs() {
+        let uses_subsolver = config.diagonal_subsolver.is_some();
+        let mut cbs = CBS::new(CBSInstance::new(map.clone(), agents.clone()), Some(config));
+        let solution = cbs.solve();
+        if uses_subsolver {
+            let error = solution.expect_err("the sub-solver moves a cell per timestep");
+            assert!(matches!(
+                error.downcast_ref::<SearchError>(),
+                Some(SearchError::InvalidArguments(_))
+            ));
+            continue;
+        }
+        let paths = solution.expect("should find a solution");
+        // crossing the swamp takes 3 timesteps instead of 4 around it, but
+        // only one agent can be in it at a time
+        assert_eq!(paths.values().map(|path| path.len() - 1).sum::<usize>(), 7);
+        for path in paths.values() {
+            assert!(path.windows(3).all(|cells| cells[0] == cells[1]
+                || map.terrain_cost(cells[1]) == 1
+                || cells[1] == cells[2]));
+        }
+    }
+}
+
+#[test]
+fn test_solvers_are_thread_safe() {
+    fn assert_send<T: Send>() {}
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send::<CBS>();
+    assert_send_sync::<CBSInstance>();
+    assert_send_sync::<AStarLowLevelSolver>();
+
+    let agents = vec![
+        Agent {
+            id: "a".to_string(),
+            start: (0, 0),
+            goal: (3, 3),
+        },
+        Agent {
+            id: "b".to_string(),
+            start: (3, 0),
+            goal: (0, 3),
+        },
+    ];
+    let low_level_solver = Arc::new(AStarLowLevelSolver::new());
+    let costs = std::thread::scope(|scope| {
+        let handles = (0..4)
+            .map(|_| {
+                let instance = CBSInstance::new(open_grid(), agents.clone());
+                let low_level_solver = Arc::clone(&low_level_solver);
+                scope.spawn(move || {
+                    let mut cbs = CBS::new(instance, None).with_low_level_solver(low_level_solver);
+                    let paths = cbs.solve().expect("should find a solution");
+                    paths.values().map(|path| path.len() - 1).sum::<usize>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("thread should not panic"))
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(costs, vec![12; 4]);
+}
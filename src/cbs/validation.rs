@@ -0,0 +1,212 @@
+use std::{collections::HashMap, fmt};
+
+use super::{
+    high_level::Agent,
+    low_level::{heuristic::DistanceMap, Grid},
+    CBSInstance,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstanceProblem {
+    StartOutOfBounds {
+        agent: String,
+        location: (i32, i32),
+    },
+    StartOnObstacle {
+        agent: String,
+        location: (i32, i32),
+    },
+    GoalOutOfBounds {
+        agent: String,
+        location: (i32, i32),
+    },
+    GoalOnObstacle {
+        agent: String,
+        location: (i32, i32),
+    },
+    DuplicateStart {
+        agents: Vec<String>,
+        location: (i32, i32),
+    },
+    DuplicateGoal {
+        agents: Vec<String>,
+        location: (i32, i32),
+    },
+    GoalUnreachable {
+        agent: String,
+    },
+    DuplicateId {
+        agent: String,
+    },
+}
+
+impl fmt::Display for InstanceProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstanceProblem::StartOutOfBounds { agent, location } => {
+                write!(
+                    f,
+                    "agent {}: start {:?} is outside the map",
+                    agent, location
+                )
+            }
+            InstanceProblem::StartOnObstacle { agent, location } => {
+                write!(f, "agent {}: start {:?} is an obstacle", agent, location)
+            }
+            InstanceProblem::GoalOutOfBounds { agent, location } => {
+                write!(f, "agent {}: goal {:?} is outside the map", agent, location)
+            }
+            InstanceProblem::GoalOnObstacle { agent, location } => {
+                write!(f, "agent {}: goal {:?} is an obstacle", agent, location)
+            }
+            InstanceProblem::DuplicateStart { agents, location } => write!(
+                f,
+                "agents {} share the start {:?}",
+                agents.join(", "),
+                location
+            ),
+            InstanceProblem::DuplicateGoal { agents, location } => write!(
+                f,
+                "agents {} share the goal {:?}",
+                agents.join(", "),
+                location
+            ),
+            InstanceProblem::GoalUnreachable { agent } => {
+                write!(f, "agent {}: goal is not reachable from start", agent)
+            }
+            InstanceProblem::DuplicateId { agent } => {
+                write!(f, "agent {}: id is used by more than one agent", agent)
+            }
+        }
+    }
+}
+
+impl CBSInstance {
+    /// Checks the instance for problems that make it unsolvable,
+    /// reporting every offending agent.
+    pub fn validate(&self) -> Result<(), Vec<InstanceProblem>> {
+        let problems = find_problems(&self.map, &self.agents);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+fn find_problems(map: &Grid, agents: &[Agent]) -> Vec<InstanceProblem> {
+    let mut problems = Vec::<InstanceProblem>::new();
+    let mut placeable = Vec::<&Agent>::new();
+    for agent in agents.iter() {
+        let mut endpoints_valid = true;
+        if !is_in_bounds(map, agent.start) {
+            problems.push(InstanceProblem::StartOutOfBounds {
+                agent: agent.id.clone(),
+                location: agent.start,
+            });
+            endpoints_valid = false;
+        } else if !map.is_valid_location(&agent.start, &agent.start) {
+            problems.push(InstanceProblem::StartOnObstacle {
+                agent: agent.id.clone(),
+                location: agent.start,
+            });
+            endpoints_valid = false;
+        }
+        if !is_in_bounds(map, agent.goal) {
+            problems.push(InstanceProblem::GoalOutOfBounds {
+                agent: agent.id.clone(),
+                location: agent.goal,
+            });
+            endpoints_valid = false;
+        } else if !map.is_valid_location(&agent.goal, &agent.goal) {
+            problems.push(InstanceProblem::GoalOnObstacle {
+                agent: agent.id.clone(),
+                location: agent.goal,
+            });
+            endpoints_valid = false;
+        }
+        if endpoints_valid {
+            placeable.push(agent);
+        }
+    }
+    for agent in duplicate_ids(agents) {
+        problems.push(InstanceProblem::DuplicateId { agent });
+    }
+    for (location, agents) in group_by_location(agents, |agent| agent.start) {
+        problems.push(InstanceProblem::DuplicateStart { agents, location });
+    }
+    for (location, agents) in group_by_location(agents, |agent| agent.goal) {
+        problems.push(InstanceProblem::DuplicateGoal { agents, location });
+    }
+    if !placeable.is_empty() {
+        let components = map.connected_components();
+        for agent in placeable {
+            // enclosed cells may be left but not entered, so an agent can
+            // still reach a goal outside of its component
+            if components.get(&agent.start) != components.get(&agent.goal)
+                && DistanceMap::new(map, agent.goal)
+                    .distance(agent.start)
+                    .is_none()
+            {
+                problems.push(InstanceProblem::GoalUnreachable {
+                    agent: agent.id.clone(),
+                });
+            }
+        }
+    }
+    problems
+}
+
+fn is_in_bounds(map: &Grid, location: (i32, i32)) -> bool {
+    location.0 >= 0 && location.0 < map.width && location.1 >= 0 && location.1 < map.height
+}
+
+/// Ids shared by more than one agent, in order of first appearance.
+/// Paths and cached MDDs are keyed by id, so these agents would be
+/// confused with each other during search.
+fn duplicate_ids(agents: &[Agent]) -> Vec<String> {
+    let mut counts = HashMap::<&str, usize>::new();
+    for agent in agents.iter() {
+        *counts.entry(agent.id.as_str()).or_default() += 1;
+    }
+    let mut duplicates = Vec::<String>::new();
+    for agent in agents.iter() {
+        if counts
+            .remove(agent.id.as_str())
+            .is_some_and(|count| count > 1)
+        {
+            duplicates.push(agent.id.clone());
+        }
+    }
+    duplicates
+}
+
+/// Groups agent ids by a location of theirs, keeping only locations
+/// shared by more than one agent, in order of first appearance.
+fn group_by_location(
+    agents: &[Agent],
+    location_of: fn(&Agent) -> (i32, i32),
+) -> Vec<((i32, i32), Vec<String>)> {
+    let mut order = Vec::<(i32, i32)>::new();
+    let mut groups = HashMap::<(i32, i32), Vec<String>>::new();
+    for agent in agents.iter() {
+        let location = location_of(agent);
+        groups
+            .entry(location)
+            .or_insert_with(|| {
+                order.push(location);
+                Vec::new()
+            })
+            .push(agent.id.clone());
+    }
+    order
+        .into_iter()
+        .filter_map(|location| {
+            let ids = groups.remove(&location)?;
+            (ids.len() > 1).then_some((location, ids))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests;
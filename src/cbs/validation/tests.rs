@@ -0,0 +1,124 @@
+use super::*;
+use crate::cbs::low_level::LocationTime;
+use rstest::rstest;
+
+fn agent(id: &str, start: (i32, i32), goal: (i32, i32)) -> Agent {
+    Agent {
+        id: id.to_string(),
+        start,
+        goal,
+    }
+}
+
+#[rstest]
+#[case::valid(
+    Grid::new(3, 3, Grid::to_conditional_obstacles(vec![]), (0, 0)),
+    vec![agent("0", (0, 0), (2, 2)), agent("1", (2, 2), (0, 0))],
+    vec![],
+)]
+#[case::out_of_bounds(
+    Grid::new(3, 3, Grid::to_conditional_obstacles(vec![]), (0, 0)),
+    vec![agent("0", (-1, 0), (2, 2)), agent("1", (0, 0), (3, 1))],
+    vec![
+        InstanceProblem::StartOutOfBounds { agent: "0".to_string(), location: (-1, 0) },
+        InstanceProblem::GoalOutOfBounds { agent: "1".to_string(), location: (3, 1) },
+    ],
+)]
+#[case::on_obstacle(
+    Grid::new(3, 3, Grid::to_conditional_obstacles(vec![
+        LocationTime::new((1, 1), -1),
+    ]), (0, 0)),
+    vec![agent("0", (1, 1), (2, 2)), agent("1", (0, 0), (1, 1))],
+    vec![
+        InstanceProblem::StartOnObstacle { agent: "0".to_string(), location: (1, 1) },
+        InstanceProblem::GoalOnObstacle { agent: "1".to_string(), location: (1, 1) },
+    ],
+)]
+#[case::duplicates(
+    Grid::new(3, 3, Grid::to_conditional_obstacles(vec![]), (0, 0)),
+    vec![
+        agent("0", (0, 0), (2, 2)),
+        agent("1", (0, 0), (2, 1)),
+        agent("2", (1, 0), (2, 2)),
+        agent("3", (1, 1), (2, 2)),
+    ],
+    vec![
+        InstanceProblem::DuplicateStart {
+            agents: vec!["0".to_string(), "1".to_string()],
+            location: (0, 0),
+        },
+        InstanceProblem::DuplicateGoal {
+            agents: vec!["0".to_string(), "2".to_string(), "3".to_string()],
+            location: (2, 2),
+        },
+    ],
+)]
+#[case::duplicate_ids(
+    Grid::new(3, 3, Grid::to_conditional_obstacles(vec![]), (0, 0)),
+    vec![
+        agent("a", (0, 0), (2, 2)),
+        agent("b", (1, 0), (2, 1)),
+        agent("a", (2, 0), (0, 2)),
+        agent("b", (0, 1), (1, 2)),
+        agent("c", (1, 1), (0, 0)),
+    ],
+    vec![
+        InstanceProblem::DuplicateId { agent: "a".to_string() },
+        InstanceProblem::DuplicateId { agent: "b".to_string() },
+    ],
+)]
+#[case::different_components(
+    Grid::new(3, 3, Grid::to_conditional_obstacles(vec![
+        LocationTime::new((1, 0), -1),
+        LocationTime::new((1, 1), -1),
+        LocationTime::new((1, 2), -1),
+    ]), (0, 0)),
+    vec![agent("0", (0, 0), (2, 2)), agent("1", (0, 2), (0, 0))],
+    vec![InstanceProblem::GoalUnreachable { agent: "0".to_string() }],
+)]
+#[case::timed_obstacles_ignored(
+    Grid::new(3, 1, Grid::to_conditional_obstacles(vec![
+        LocationTime::new((1, 0), 1),
+    ]), (0, 0)),
+    vec![agent("0", (0, 0), (2, 0))],
+    vec![],
+)]
+fn test_find_problems(
+    #[case] map: Grid,
+    #[case] agents: Vec<Agent>,
+    #[case] expected: Vec<InstanceProblem>,
+) {
+    assert_eq!(find_problems(&map, &agents), expected);
+}
+
+#[test]
+fn test_enclosed_cells_are_left_but_not_entered() {
+    let map = crate::cbs::io::parse_map(
+        "type octile\nheight 1\nwidth 4\nmap\n..WW\n",
+        &crate::cbs::terrain::TerrainTable::default(),
+    )
+    .unwrap();
+    let agents = vec![agent("in", (0, 0), (3, 0)), agent("out", (2, 0), (1, 0))];
+    assert_eq!(
+        find_problems(&map, &agents),
+        vec![InstanceProblem::GoalUnreachable {
+            agent: "in".to_string()
+        }]
+    );
+}
+
+#[test]
+fn test_solve_rejects_invalid_instance() {
+    let mut cbs = crate::cbs::CBS::new(
+        CBSInstance::new(
+            Grid::new(3, 3, Grid::to_conditional_obstacles(vec![]), (0, 0)),
+            vec![agent("0", (0, 0), (2, 2)), agent("1", (1, 0), (2, 2))],
+        ),
+        None,
+    );
+    let err = cbs.solve().expect_err("duplicate goals should be rejected");
+    assert_eq!(
+        err.to_string(),
+        "invalid CBS instance: agents 0, 1 share the goal (2, 2)"
+    );
+}
@@ -1,27 +1,81 @@
 mod cbs;
 
+use std::collections::HashMap;
+use std::error::Error;
 use std::fs;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::{thread::JoinHandle, time::Duration};
+use std::time::Duration;
 
-use cbs::io::paths_to_string;
-use cbs::{CBSInstance, DiagonalSubsolverConfig, CBS};
-use clap::{ArgGroup, Parser};
+use cbs::batch::BatchSolver;
+use cbs::cancellation::CancellationToken;
+use cbs::generators::maps::{generate_map, MapConfig, MapKind, WarehouseLayout};
+use cbs::generators::scenarios::{generate_scenario, ScenarioConfig, ScenarioKind};
+use cbs::icts::IncreasingCostTreeSearch;
+use cbs::independence::IndependenceDetection;
+use cbs::io::yaml::schedule_to_yaml;
+use cbs::io::{paths_to_string, scenario_to_string, AgentSelection, LoadOptions};
+use cbs::lacam::LaCAM;
+use cbs::m_star::MStar;
+use cbs::operator_decomposition::OperatorDecomposition;
+use cbs::pibt::{Pibt, DEFAULT_MAX_TIMESTEPS};
+use cbs::portfolio::{Portfolio, PortfolioMember};
+use cbs::service::{Service, ServiceConfig};
+use cbs::terrain::TerrainTable;
+use cbs::{
+    AStarLowLevelSolver, Agent, CBSInstance, CBSOptimisationConfig, DiagonalSubsolverConfig, Path,
+    CBS,
+};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-#[clap(group(
-    ArgGroup::new("diagonal-subsolver")
-        .required(false)
-        .args(&["disable_diagonal_subsolver", "diagonal_subsolver_slackness"]),
-))]
-struct Args {
-    #[arg(short, long)]
-    map_file: String,
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(short, long)]
-    agents_file: String,
+    #[command(flatten)]
+    solve: SolveArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate a Moving AI scenario file for a map.
+    GenerateScen(GenerateScenArgs),
+    /// Generate a random, warehouse or maze map.
+    GenerateMap(GenerateMapArgs),
+    /// Solve JSON-lines instances read from stdin, writing a JSON line per result.
+    Batch(BatchArgs),
+    /// Serve planning requests over HTTP.
+    Serve(ServeArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct SolveArgs {
+    #[arg(
+        short,
+        long,
+        required_unless_present = "yaml_instance",
+        help = "Moving AI map file, or '-' to read it from stdin."
+    )]
+    map_file: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        required_unless_present = "yaml_instance",
+        help = "Moving AI scenario file, or '-' to read it from stdin."
+    )]
+    agents_file: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["map_file", "agents_file"],
+        help = "Read a libMultiRobotPlanning YAML instance instead of Moving AI files. Paths are written as a YAML schedule."
+    )]
+    yaml_instance: Option<String>,
 
     #[arg(
         short,
@@ -36,10 +90,29 @@ struct Args {
     #[arg(
         short,
         long,
-        help = "Stop if a solution is not found withing this number of seconds."
+        help = "Stop if a solution is not found withing this number of seconds. The best paths found so far are written instead, and the exit code is 2."
     )]
     timeout: Option<u64>,
 
+    #[arg(
+        long,
+        help = "Stop after expanding this many high-level nodes, like --timeout."
+    )]
+    node_limit: Option<usize>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        help = "Threads CBS uses to generate conflict tree nodes, plan the root's paths without a CAT, and compute the DG heuristic."
+    )]
+    threads: usize,
+
+    #[arg(
+        long,
+        help = "Directory to keep the distance tables of CBS in between runs, computing them once per map and goal."
+    )]
+    distance_cache: Option<PathBuf>,
+
     #[arg(
         short = 'k',
         long,
@@ -47,6 +120,169 @@ struct Args {
     )]
     num_agents: Option<usize>,
 
+    #[arg(
+        long,
+        value_parser = parse_inclusive_range,
+        help = "Consider only agents from these scenario buckets, e.g. '3' or '3-7'."
+    )]
+    buckets: Option<RangeInclusive<usize>>,
+
+    #[arg(
+        long,
+        value_parser = parse_inclusive_range,
+        help = "Consider only agents in these rows of the scenario (starting at 0), e.g. '10-19'."
+    )]
+    agent_indices: Option<RangeInclusive<usize>>,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Skip checking single-agent optimal costs against the scenario file."
+    )]
+    skip_optimal_length_check: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        help = "Print search progress to stderr periodically and when the lower bound or the best conflict count improves."
+    )]
+    progress: bool,
+
+    #[arg(
+        long,
+        default_value = "1000",
+        requires = "progress",
+        help = "Milliseconds between periodic progress reports."
+    )]
+    progress_interval: u64,
+
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["node_limit", "progress", "conflict_tree_dot", "conflict_tree_json"],
+        help = "Plan agents alone and merge conflicting agents into groups solved with CBS (Standley's Independence Detection)."
+    )]
+    independence_detection: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["independence_detection", "node_limit", "progress", "conflict_tree_dot", "conflict_tree_json"],
+        help = "Solve with Increasing Cost Tree Search instead of CBS."
+    )]
+    icts: bool,
+
+    #[arg(
+        long,
+        default_value = "2",
+        requires = "icts",
+        help = "Size of the agent groups whose MDDs ICTS checks for a joint path before all agents together, below 2 to disable."
+    )]
+    icts_pruning: usize,
+
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["independence_detection", "icts", "node_limit", "progress", "conflict_tree_dot", "conflict_tree_json"],
+        help = "Solve with A* and operator decomposition over the joint state of all agents instead of CBS. Only practical for a handful of agents."
+    )]
+    operator_decomposition: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["independence_detection", "icts", "operator_decomposition", "node_limit", "progress", "conflict_tree_dot", "conflict_tree_json"],
+        help = "Solve with M*, which searches jointly only for the agents that collide, instead of CBS."
+    )]
+    m_star: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["independence_detection", "icts", "operator_decomposition", "m_star", "node_limit", "progress", "conflict_tree_dot", "conflict_tree_json"],
+        help = "Solve with PIBT instead of CBS. Fast but suboptimal, and may fail to find a solution. Ignores timed obstacles."
+    )]
+    pibt: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["independence_detection", "icts", "operator_decomposition", "m_star", "pibt", "node_limit", "progress", "conflict_tree_dot", "conflict_tree_json"],
+        help = "Solve with LaCAM, a complete search built on PIBT, instead of CBS. Fast but suboptimal. Ignores timed obstacles."
+    )]
+    lacam: bool,
+
+    #[arg(
+        long,
+        default_value = "false",
+        conflicts_with_all = ["independence_detection", "icts", "operator_decomposition", "m_star", "pibt", "lacam", "node_limit", "progress", "conflict_tree_dot", "conflict_tree_json"],
+        help = "Run several CBS configurations, ICTS, M* and LaCAM on separate threads, returning the first optimal solution, or the best one found within --timeout."
+    )]
+    portfolio: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        requires = "portfolio",
+        help = "Comma-separated members of --portfolio. Defaults to all of them."
+    )]
+    portfolio_members: Vec<PortfolioMemberArg>,
+
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Seed for the random tie-breaking of --pibt and --lacam."
+    )]
+    seed: u64,
+
+    #[arg(
+        long,
+        default_value_t = DEFAULT_MAX_TIMESTEPS,
+        requires = "pibt",
+        help = "Give up --pibt if the agents are not all at their goals after this many timesteps."
+    )]
+    max_timesteps: usize,
+
+    #[arg(
+        long,
+        help = "Write the explored conflict tree to this file as Graphviz DOT."
+    )]
+    conflict_tree_dot: Option<String>,
+
+    #[arg(long, help = "Write the explored conflict tree to this file as JSON.")]
+    conflict_tree_json: Option<String>,
+
+    #[command(flatten)]
+    optimisations: OptimisationArgs,
+
+    #[command(flatten)]
+    terrain: TerrainArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct TerrainArgs {
+    #[arg(
+        long,
+        help = "Override how map terrain is treated, e.g. 'S=costly:3,W=enclosed:3,T=traversable'. Entering costly terrain takes its cost in timesteps. Enclosed terrain, like water by default, is only entered from enclosed terrain. Only CBS without the diagonal sub-solver plans for terrain costs, the other solvers reject maps with them."
+    )]
+    terrain: Option<TerrainTable>,
+}
+
+impl TerrainArgs {
+    /// The default table with the overrides, if any.
+    fn table(&self) -> TerrainTable {
+        self.terrain.clone().unwrap_or_default()
+    }
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(group(
+    ArgGroup::new("diagonal-subsolver")
+        .required(false)
+        .args(&["disable_diagonal_subsolver", "diagonal_subsolver_slackness"]),
+))]
+struct OptimisationArgs {
     #[arg(long, default_value = "false", group = "diagonal-subsolver")]
     disable_diagonal_subsolver: bool,
 
@@ -88,17 +324,645 @@ struct Args {
     heuristic: Option<cbs::HighLevelHeuristic>,
 }
 
+#[derive(clap::Args, Debug)]
+struct BatchArgs {
+    #[command(flatten)]
+    optimisations: OptimisationArgs,
+
+    #[command(flatten)]
+    terrain: TerrainArgs,
+
+    #[arg(
+        long,
+        help = "Directory to keep the distance tables in between runs, computing them once per map and goal."
+    )]
+    distance_cache: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    address: String,
+
+    #[arg(
+        short,
+        long = "map",
+        required = true,
+        value_parser = parse_named_map,
+        help = "A map to preload, as 'name=file' or 'file'. Without a name, the file name is used."
+    )]
+    maps: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        help = "Number of requests solved concurrently. Defaults to the number of CPUs."
+    )]
+    workers: Option<usize>,
+
+    #[command(flatten)]
+    terrain: TerrainArgs,
+
+    #[arg(
+        long,
+        help = "Directory to keep the distance tables in between runs, computing them once per map and goal."
+    )]
+    distance_cache: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum PortfolioMemberArg {
+    /// CBS with the DG heuristic
+    CbsDg,
+    /// CBS with the diagonal sub-solver
+    CbsDiagonal,
+    /// CBS with conflict prioritisation, bypassing and a CAT
+    Icbs,
+    /// Increasing cost tree search
+    Icts,
+    MStar,
+    /// LaCAM, suboptimal
+    Lacam,
+}
+
+impl From<PortfolioMemberArg> for PortfolioMember {
+    fn from(member: PortfolioMemberArg) -> Self {
+        match member {
+            PortfolioMemberArg::CbsDg => PortfolioMember::cbs_with_dg(),
+            PortfolioMemberArg::CbsDiagonal => PortfolioMember::cbs_with_diagonal_subsolver(),
+            PortfolioMemberArg::Icbs => PortfolioMember::icbs(),
+            PortfolioMemberArg::Icts => PortfolioMember::IncreasingCostTreeSearch,
+            PortfolioMemberArg::MStar => PortfolioMember::MStar,
+            PortfolioMemberArg::Lacam => PortfolioMember::Lacam,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum ScenarioKindArg {
+    Random,
+    Even,
+}
+
+#[derive(clap::Args, Debug)]
+struct GenerateScenArgs {
+    #[arg(short, long)]
+    map_file: String,
+
+    #[arg(
+        short,
+        long,
+        help = "Output file for the scenario. If not specified, will print to stdout"
+    )]
+    output: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value = "random",
+        help = "Either uniformly random start/goal pairs, or pairs spread evenly over buckets of optimal length."
+    )]
+    kind: ScenarioKindArg,
+
+    #[arg(
+        short = 'n',
+        long,
+        default_value = "100",
+        help = "Number of start/goal pairs of a random scenario."
+    )]
+    count: usize,
+
+    #[arg(
+        long,
+        default_value = "10",
+        help = "Number of start/goal pairs per bucket of an even scenario."
+    )]
+    per_bucket: usize,
+
+    #[arg(long, default_value = "0")]
+    seed: u64,
+
+    #[arg(
+        long,
+        help = "Place starts only on cells with one of these terrain characters, e.g. 'G'."
+    )]
+    start_terrain: Option<String>,
+
+    #[arg(
+        long,
+        help = "Place goals only on cells with one of these terrain characters, e.g. 'G'."
+    )]
+    goal_terrain: Option<String>,
+
+    #[command(flatten)]
+    terrain: TerrainArgs,
+}
+
+#[derive(ValueEnum, Debug, Clone)]
+enum MapKindArg {
+    Random,
+    Warehouse,
+    Maze,
+}
+
+#[derive(clap::Args, Debug)]
+struct GenerateMapArgs {
+    #[arg(
+        short,
+        long,
+        help = "Output file for the map. If not specified, will print to stdout"
+    )]
+    output: Option<String>,
+
+    #[arg(long, value_enum, default_value = "random")]
+    kind: MapKindArg,
+
+    #[arg(long, default_value = "32", help = "Width of a random map or maze.")]
+    width: i32,
+
+    #[arg(long, default_value = "32", help = "Height of a random map or maze.")]
+    height: i32,
+
+    #[arg(
+        long,
+        default_value = "0.2",
+        help = "Fraction of cells of a random map that are obstacles."
+    )]
+    density: f64,
+
+    #[arg(long, default_value = "1", help = "Width of the corridors of a maze.")]
+    corridor_width: i32,
+
+    #[arg(
+        long,
+        default_value = "10-20-10-2-1",
+        help = "Warehouse layout as <shelf length>-<rows>-<columns>-<shelf width>-<aisle width>."
+    )]
+    layout: WarehouseLayout,
+
+    #[arg(
+        long,
+        help = "Free columns on either side of the warehouse shelves. Defaults to 25."
+    )]
+    margin: Option<i32>,
+
+    #[arg(long, default_value = "0")]
+    seed: u64,
+}
+
 fn main() {
     // env_logger::builder()
     //     .target(env_logger::Target::Pipe(Box::new(
     //         fs::File::create("log.txt").unwrap(),
     //     )))
     //     .init();
-    env_logger::builder().init();
-    let args = Args::parse();
-    let cbs_instance = CBSInstance::from_files(&args.map_file, &args.agents_file, args.num_agents)
-        .expect("should be valid scenario files");
-    let optimisation_config = Some(cbs::CBSOptimisationConfig::new(
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::GenerateScen(args)) => generate_scen(args),
+        Some(Command::GenerateMap(args)) => generate_map_file(args),
+        Some(Command::Batch(args)) => batch(args),
+        Some(Command::Serve(args)) => serve(args),
+        None => solve(cli.solve),
+    }
+}
+
+fn solve(args: SolveArgs) {
+    let cbs_instance = match &args.yaml_instance {
+        Some(instance_file) => CBSInstance::from_yaml_file(instance_file).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }),
+        None => {
+            let map_file = args
+                .map_file
+                .as_ref()
+                .expect("required without a YAML instance");
+            let agents_file = args
+                .agents_file
+                .as_ref()
+                .expect("required without a YAML instance");
+            let options = LoadOptions {
+                agents: AgentSelection {
+                    buckets: args.buckets.clone(),
+                    indices: args.agent_indices.clone(),
+                    max_agents: args.num_agents,
+                },
+                terrain: args.terrain.table(),
+            };
+            let instance = match (map_file.as_str(), agents_file.as_str()) {
+                ("-", "-") => {
+                    Err("only one of the map and the scenario can be read from stdin".to_string())
+                }
+                ("-", _) | (_, "-") => open_input(map_file).and_then(|map| {
+                    CBSInstance::from_readers(map, open_input(agents_file)?, &options)
+                }),
+                _ => CBSInstance::from_files_with_options(map_file, agents_file, &options),
+            };
+            instance.unwrap_or_else(|e| {
+                log::error!("{}", e);
+                std::process::exit(1);
+            })
+        }
+    };
+    if let Err(problems) = cbs_instance.validate() {
+        for problem in problems.iter() {
+            log::error!("{}", problem);
+        }
+        std::process::exit(1);
+    }
+    let low_level_solver = Arc::new(match &args.distance_cache {
+        Some(directory) => AStarLowLevelSolver::new().with_distance_cache(directory.clone()),
+        None => AStarLowLevelSolver::new(),
+    });
+    if !args.skip_optimal_length_check {
+        for mismatch in cbs_instance.check_optimal_lengths(&low_level_solver) {
+            log::warn!("{}", mismatch);
+        }
+    }
+    let optimisation_config = optimisation_config(&args.optimisations);
+    let agents = cbs_instance.agents().to_vec();
+    if args.independence_detection {
+        return solve_independently(args, cbs_instance, optimisation_config);
+    }
+    if args.icts {
+        return solve_with_icts(args, cbs_instance);
+    }
+    if args.operator_decomposition {
+        return solve_with_operator_decomposition(args, cbs_instance);
+    }
+    if args.m_star {
+        return solve_with_m_star(args, cbs_instance);
+    }
+    if args.pibt {
+        return solve_with_pibt(args, cbs_instance);
+    }
+    if args.lacam {
+        return solve_with_lacam(args, cbs_instance);
+    }
+    if args.portfolio {
+        return solve_with_portfolio(args, cbs_instance);
+    }
+    let mut cbs = CBS::new(cbs_instance, optimisation_config)
+        .with_threads(args.threads)
+        .with_low_level_solver(low_level_solver);
+    if args.conflict_tree_dot.is_some() || args.conflict_tree_json.is_some() {
+        cbs = cbs.record_conflict_tree();
+    }
+    if args.progress {
+        cbs = cbs.with_progress(
+            Box::new(|report| eprintln!("{}", report)),
+            Duration::from_millis(args.progress_interval),
+        );
+    }
+    if let Some(cancellation) = timeout(&args) {
+        cbs = cbs.with_cancellation(cancellation);
+    }
+    if let Some(node_limit) = args.node_limit {
+        cbs = cbs.with_node_limit(node_limit);
+    }
+    let solution = cbs.solve();
+    match solution {
+        Ok(paths) => {
+            write_paths(&args, &agents, &paths);
+            log_mdd_statistics(&cbs);
+            write_conflict_tree(&args.conflict_tree_dot, &args.conflict_tree_json, &cbs);
+            if let Some(metrics_file) = args.metrics_file {
+                write_metrics(
+                    metrics_file,
+                    &[
+                        ("high-level generated", cbs.high_level_generated.to_string()),
+                        ("high-level pruned", cbs.high_level_pruned.to_string()),
+                    ],
+                );
+            }
+        }
+        Err(e) => {
+            log_mdd_statistics(&cbs);
+            write_conflict_tree(&args.conflict_tree_dot, &args.conflict_tree_json, &cbs);
+            let Some(partial) = cbs.partial_solution() else {
+                log::error!("CBS found no solution: {}", e);
+                std::process::exit(1);
+            };
+            log::error!(
+                "Search {}: writing the best paths found, with {} conflicts left, cost {} and lower bound {}",
+                partial.cutoff,
+                partial.conflicts.len(),
+                partial.cost(),
+                partial.lower_bound
+            );
+            for conflict in partial.conflicts.iter() {
+                log::warn!("Unresolved conflict between {}", conflict);
+            }
+            let paths = partial
+                .paths
+                .iter()
+                .map(|(agent, path)| (agent, path.clone()))
+                .collect::<HashMap<_, _>>();
+            write_paths(&args, &agents, &paths);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn solve_independently(
+    args: SolveArgs,
+    cbs_instance: CBSInstance,
+    optimisation_config: Option<CBSOptimisationConfig>,
+) {
+    let agents = cbs_instance.agents().to_vec();
+    let solver = IndependenceDetection::new(cbs_instance, optimisation_config);
+    run_solver(
+        args,
+        &agents,
+        "Independence detection",
+        solver,
+        IndependenceDetection::with_cancellation,
+        IndependenceDetection::solve,
+        |solver| {
+            let group_sizes = solver.group_sizes();
+            log::info!(
+                "{} independent groups after {} merges, sizes {:?}",
+                group_sizes.len(),
+                solver.merges,
+                group_sizes
+            );
+            log::debug!("Independent groups: {:?}", solver.groups());
+            let sizes = group_sizes
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            vec![
+                (
+                    "high-level generated",
+                    solver.high_level_generated.to_string(),
+                ),
+                ("high-level pruned", solver.high_level_pruned.to_string()),
+                ("group sizes", sizes.join(" ")),
+            ]
+        },
+    );
+}
+
+fn solve_with_icts(args: SolveArgs, cbs_instance: CBSInstance) {
+    let agents = cbs_instance.agents().to_vec();
+    let solver = IncreasingCostTreeSearch::new(cbs_instance).with_pruning(args.icts_pruning);
+    run_solver(
+        args,
+        &agents,
+        "ICTS",
+        solver,
+        IncreasingCostTreeSearch::with_cancellation,
+        IncreasingCostTreeSearch::solve,
+        |solver| {
+            log::info!(
+                "Increasing cost tree: {} nodes generated, {} expanded, {} pruned",
+                solver.ict_nodes_generated,
+                solver.ict_nodes_expanded,
+                solver.ict_nodes_pruned
+            );
+            vec![
+                (
+                    "ICT nodes generated",
+                    solver.ict_nodes_generated.to_string(),
+                ),
+                ("ICT nodes pruned", solver.ict_nodes_pruned.to_string()),
+            ]
+        },
+    );
+}
+
+fn solve_with_operator_decomposition(args: SolveArgs, cbs_instance: CBSInstance) {
+    let agents = cbs_instance.agents().to_vec();
+    let solver = OperatorDecomposition::new(cbs_instance);
+    run_solver(
+        args,
+        &agents,
+        "Operator decomposition",
+        solver,
+        OperatorDecomposition::with_cancellation,
+        OperatorDecomposition::solve,
+        |solver| {
+            log::info!("Joint A*: {} nodes generated", solver.nodes_generated);
+            vec![("joint nodes generated", solver.nodes_generated.to_string())]
+        },
+    );
+}
+
+fn solve_with_m_star(args: SolveArgs, cbs_instance: CBSInstance) {
+    let agents = cbs_instance.agents().to_vec();
+    let solver = MStar::new(cbs_instance);
+    run_solver(
+        args,
+        &agents,
+        "M*",
+        solver,
+        MStar::with_cancellation,
+        MStar::solve,
+        |solver| {
+            log::info!(
+                "M*: {} nodes generated, largest collision set {}",
+                solver.nodes_generated,
+                solver.max_collision_set
+            );
+            vec![
+                ("joint nodes generated", solver.nodes_generated.to_string()),
+                (
+                    "largest collision set",
+                    solver.max_collision_set.to_string(),
+                ),
+            ]
+        },
+    );
+}
+
+fn solve_with_pibt(args: SolveArgs, cbs_instance: CBSInstance) {
+    let agents = cbs_instance.agents().to_vec();
+    let solver = Pibt::new(cbs_instance)
+        .with_seed(args.seed)
+        .with_max_timesteps(args.max_timesteps);
+    run_solver(
+        args,
+        &agents,
+        "PIBT",
+        solver,
+        Pibt::with_cancellation,
+        Pibt::solve,
+        |solver| {
+            log::info!("PIBT: {} timesteps", solver.timesteps);
+            vec![("timesteps", solver.timesteps.to_string())]
+        },
+    );
+}
+
+fn solve_with_lacam(args: SolveArgs, cbs_instance: CBSInstance) {
+    let agents = cbs_instance.agents().to_vec();
+    let solver = LaCAM::new(cbs_instance).with_seed(args.seed);
+    run_solver(
+        args,
+        &agents,
+        "LaCAM",
+        solver,
+        LaCAM::with_cancellation,
+        LaCAM::solve,
+        |solver| {
+            log::info!(
+                "LaCAM: {} high-level and {} low-level nodes generated",
+                solver.high_level_generated,
+                solver.low_level_generated
+            );
+            vec![
+                (
+                    "configurations generated",
+                    solver.high_level_generated.to_string(),
+                ),
+                (
+                    "constraints generated",
+                    solver.low_level_generated.to_string(),
+                ),
+            ]
+        },
+    );
+}
+
+fn solve_with_portfolio(args: SolveArgs, cbs_instance: CBSInstance) {
+    let agents = cbs_instance.agents().to_vec();
+    let mut solver = Portfolio::new(cbs_instance);
+    if !args.portfolio_members.is_empty() {
+        let members = args
+            .portfolio_members
+            .iter()
+            .cloned()
+            .map(PortfolioMember::from);
+        solver = solver.with_members(members.collect());
+    }
+    run_solver(
+        args,
+        &agents,
+        "Portfolio",
+        solver,
+        Portfolio::with_cancellation,
+        Portfolio::solve,
+        |solver| {
+            let winner = solver
+                .winner
+                .as_ref()
+                .map_or_else(|| "none".to_string(), |winner| winner.to_string());
+            log::info!(
+                "Portfolio: solved by {}, {} nodes generated",
+                winner,
+                solver.nodes_generated
+            );
+            vec![
+                ("solved by", winner),
+                (
+                    "nodes generated by the winner",
+                    solver.nodes_generated.to_string(),
+                ),
+            ]
+        },
+    );
+}
+
+/// Runs a solver with the timeout of `args` and writes its paths, then logs
+/// and writes the metrics returned by `report`. If the solver finds no
+/// solution, the error is logged and the process exits with status 1.
+fn run_solver<S>(
+    args: SolveArgs,
+    agents: &[Agent],
+    name: &str,
+    mut solver: S,
+    with_cancellation: impl FnOnce(S, CancellationToken) -> S,
+    solve: impl FnOnce(&mut S) -> Result<HashMap<&Agent, Path>, Box<dyn Error>>,
+    report: impl FnOnce(&S) -> Vec<(&'static str, String)>,
+) {
+    if let Some(cancellation) = timeout(&args) {
+        solver = with_cancellation(solver, cancellation);
+    }
+    match solve(&mut solver) {
+        Ok(paths) => write_paths(&args, agents, &paths),
+        Err(e) => {
+            log::error!("{} found no solution: {}", name, e);
+            std::process::exit(1);
+        }
+    }
+    let metrics = report(&solver);
+    if let Some(metrics_file) = args.metrics_file {
+        write_metrics(metrics_file, &metrics);
+    }
+}
+
+fn timeout(args: &SolveArgs) -> Option<CancellationToken> {
+    args.timeout
+        .map(|timeout| CancellationToken::with_timeout(Duration::from_secs(timeout)))
+}
+
+fn write_paths(args: &SolveArgs, agents: &[Agent], paths: &HashMap<&Agent, Path>) {
+    let output = if args.yaml_instance.is_some() {
+        schedule_to_yaml(agents, paths)
+    } else {
+        paths_to_string(paths)
+    };
+    write_output(&args.paths_file, output);
+}
+
+fn log_mdd_statistics(cbs: &CBS) {
+    let statistics = cbs.mdd_statistics();
+    log::info!(
+        "{} MDD lookups, {:.1}% hits, {} MDDs built from their parent's, {} from scratch",
+        statistics.lookups(),
+        100.0 * statistics.hit_rate(),
+        statistics.incremental,
+        statistics.built
+    );
+}
+
+fn write_conflict_tree(dot_file: &Option<String>, json_file: &Option<String>, cbs: &CBS) {
+    let Some(tree) = cbs.conflict_tree() else {
+        return;
+    };
+    if let Some(dot_file) = dot_file {
+        fs::write(dot_file, tree.to_dot()).expect("should write the conflict tree");
+    }
+    if let Some(json_file) = json_file {
+        fs::write(json_file, tree.to_json()).expect("should write the conflict tree");
+    }
+}
+
+fn batch(args: BatchArgs) {
+    let mut solver = BatchSolver::new(
+        args.terrain.table(),
+        optimisation_config(&args.optimisations),
+    );
+    if let Some(directory) = args.distance_cache {
+        solver = solver.with_distance_cache(directory);
+    }
+    solver
+        .run(std::io::stdin().lock(), std::io::stdout().lock())
+        .expect("should read instances from stdin and write results to stdout");
+}
+
+fn serve(args: ServeArgs) {
+    let service = Service::bind(ServiceConfig {
+        address: args.address,
+        maps: args.maps,
+        terrain: args.terrain.table(),
+        workers: args.workers.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, |workers| workers.get())
+        }),
+        distance_cache: args.distance_cache,
+    })
+    .unwrap_or_else(|e| {
+        log::error!("Could not start the service: {}", e);
+        std::process::exit(1);
+    });
+    if let Some(address) = service.address() {
+        println!("Listening on http://{}", address);
+    }
+    service.run();
+}
+
+fn optimisation_config(args: &OptimisationArgs) -> Option<cbs::CBSOptimisationConfig> {
+    Some(cbs::CBSOptimisationConfig::new(
         !args.disable_prioritising_conflicts,
         !args.disable_bypassing_conflicts,
         if args.disable_diagonal_subsolver {
@@ -110,50 +974,134 @@ fn main() {
             ))
         },
         !args.disable_conflict_avoidance_table,
-        args.heuristic,
-    ));
-    let mut cbs = CBS::new(cbs_instance, optimisation_config);
-    let is_solving = Arc::new(AtomicBool::new(true));
-    if let Some(timeout) = args.timeout {
-        let timeout = Duration::from_secs(timeout);
-        let is_solving = is_solving.clone();
-        start_timeout_thread(timeout, is_solving);
-    }
-    let solution = cbs.solve();
-    is_solving.store(false, Ordering::SeqCst);
-    match solution {
-        Ok(paths) => {
-            write_paths(&args.paths_file, paths_to_string(&paths));
-            if let Some(metrics_file) = args.metrics_file {
-                write_metrics(metrics_file, cbs);
-            }
+        args.heuristic.clone(),
+    ))
+}
+
+fn generate_scen(args: GenerateScenArgs) {
+    let terrain = args.terrain.table();
+    let map = fs::read_to_string(&args.map_file)
+        .map_err(|e| e.to_string())
+        .and_then(|content| cbs::io::parse_map(&content, &terrain).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            log::error!("{}: {}", args.map_file, e);
+            std::process::exit(1);
+        });
+    let cells_with_terrain = |terrain: Option<String>| {
+        terrain.map(|terrain| {
+            map.terrain
+                .as_ref()
+                .expect("maps loaded from files have terrain")
+                .locations_of(&terrain.chars().collect::<Vec<_>>())
+        })
+    };
+    let config = ScenarioConfig {
+        kind: match args.kind {
+            ScenarioKindArg::Random => ScenarioKind::Random { count: args.count },
+            ScenarioKindArg::Even => ScenarioKind::Even {
+                per_bucket: args.per_bucket,
+            },
+        },
+        seed: args.seed,
+        starts: cells_with_terrain(args.start_terrain),
+        goals: cells_with_terrain(args.goal_terrain),
+    };
+    let map_name = std::path::Path::new(&args.map_file)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&args.map_file);
+    let entries = generate_scenario(&map, map_name, &config).unwrap_or_else(|e| {
+        log::error!("Could not generate scenario: {}", e);
+        std::process::exit(1);
+    });
+    write_output(&args.output, scenario_to_string(&entries));
+}
+
+fn generate_map_file(args: GenerateMapArgs) {
+    let kind = match args.kind {
+        MapKindArg::Random => MapKind::Random {
+            width: args.width,
+            height: args.height,
+            density: args.density,
+        },
+        MapKindArg::Warehouse => MapKind::Warehouse(WarehouseLayout {
+            margin: args.margin.unwrap_or(args.layout.margin),
+            ..args.layout
+        }),
+        MapKindArg::Maze => MapKind::Maze {
+            width: args.width,
+            height: args.height,
+            corridor_width: args.corridor_width,
+        },
+    };
+    let map = generate_map(&MapConfig {
+        kind,
+        seed: args.seed,
+    })
+    .unwrap_or_else(|e| {
+        log::error!("Could not generate map: {}", e);
+        std::process::exit(1);
+    });
+    write_output(
+        &args.output,
+        map.try_into().expect("generated maps should serialise"),
+    );
+}
+
+fn parse_named_map(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, file)) if !name.is_empty() && !file.is_empty() => {
+            Ok((name.to_string(), file.to_string()))
+        }
+        Some(_) => Err(format!("expected name=file, got '{}'", s)),
+        None => {
+            let name = std::path::Path::new(s)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(s);
+            Ok((name.to_string(), s.to_string()))
         }
-        Err(e) => panic!("CBS Error: {:?}", e),
     }
 }
 
-fn write_paths(paths_file: &Option<String>, paths_string: String) {
-    if let Some(paths_file) = paths_file {
-        fs::write(paths_file, paths_string).expect("should write paths file");
-    } else {
-        println!("{}", paths_string);
+fn parse_inclusive_range(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let (from, to) = s.split_once('-').unwrap_or((s, s));
+    let from = from
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("not a number: '{}'", from))?;
+    let to = to
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("not a number: '{}'", to))?;
+    if from > to {
+        return Err(format!("empty range: '{}'", s));
     }
+    Ok(from..=to)
 }
 
-fn write_metrics(metrics_file: String, cbs: CBS) {
-    fs::write(
-        metrics_file,
-        format!("#high-level generated\n{}", cbs.high_level_generated),
-    )
-    .expect("should write metrics file");
+/// Opens `file`, or stdin for `-`.
+fn open_input(file: &str) -> Result<Box<dyn std::io::Read>, String> {
+    if file == "-" {
+        return Ok(Box::new(std::io::stdin()));
+    }
+    let file = fs::File::open(file).map_err(|e| format!("{}: {}", file, e))?;
+    Ok(Box::new(file))
 }
 
-fn start_timeout_thread(timeout: Duration, is_solving: Arc<AtomicBool>) -> JoinHandle<()> {
-    std::thread::spawn(move || {
-        std::thread::sleep(timeout);
-        if is_solving.load(Ordering::SeqCst) {
-            log::error!("Timed out");
-            std::process::exit(1);
-        }
-    })
+fn write_output(output_file: &Option<String>, output: String) {
+    if let Some(output_file) = output_file {
+        fs::write(output_file, output).expect("should write output file");
+    } else {
+        println!("{}", output);
+    }
+}
+
+/// Writes every metric as a `#label` line followed by its value.
+fn write_metrics(metrics_file: String, metrics: &[(&str, String)]) {
+    let metrics = metrics
+        .iter()
+        .map(|(label, value)| format!("#{}\n{}", label, value))
+        .collect::<Vec<_>>();
+    fs::write(metrics_file, metrics.join("\n")).expect("should write metrics file");
 }